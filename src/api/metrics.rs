@@ -11,10 +11,47 @@ pub struct MetricsDb {
 
 #[cfg(feature = "db")]
 impl MetricsDb {
+    /// Opens the database at `path`, creating its tables or adding columns that older
+    /// versions did not have.
     pub fn new(path: &str) -> Result<Self> {
-        Ok(Self {
+        let db = Self {
             path: path.to_string(),
-        })
+        };
+        db.connect()?
+            .execute_batch(
+                "CREATE SEQUENCE IF NOT EXISTS metrics_id_seq;
+                 CREATE TABLE IF NOT EXISTS metrics (
+                    id INTEGER PRIMARY KEY DEFAULT nextval('metrics_id_seq'),
+                    timestamp TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                    processed_rows BIGINT,
+                    latency_ms DOUBLE,
+                    tokens_saved BIGINT,
+                    operation TEXT
+                 );
+                 ALTER TABLE metrics ADD COLUMN IF NOT EXISTS tokens_before BIGINT;
+                 ALTER TABLE metrics ADD COLUMN IF NOT EXISTS tokens_after BIGINT;
+                 CREATE SEQUENCE IF NOT EXISTS processor_metrics_id_seq;
+                 CREATE TABLE IF NOT EXISTS processor_metrics (
+                    id INTEGER PRIMARY KEY DEFAULT nextval('processor_metrics_id_seq'),
+                    timestamp TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                    metric TEXT,
+                    value BIGINT
+                 );
+                 CREATE SEQUENCE IF NOT EXISTS reports_id_seq;
+                 CREATE TABLE IF NOT EXISTS reports (
+                    id INTEGER PRIMARY KEY DEFAULT nextval('reports_id_seq'),
+                    timestamp TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                    processor TEXT,
+                    report TEXT
+                 );",
+            )
+            .map_err(|e| UdoError::Unknown(e.to_string()))?;
+        Ok(db)
+    }
+
+    /// Connections are opened per write, so the file is not held open during a run.
+    fn connect(&self) -> Result<Connection> {
+        Connection::open(&self.path).map_err(|e| UdoError::Unknown(e.to_string()))
     }
 
     pub fn record_metric(
@@ -24,25 +61,57 @@ impl MetricsDb {
         tokens_saved: usize,
         operation: &str,
     ) -> Result<()> {
-        let conn = Connection::open(&self.path).map_err(|e| UdoError::Unknown(e.to_string()))?;
-
-        conn.execute_batch(
-            "CREATE SEQUENCE IF NOT EXISTS metrics_id_seq;
-             CREATE TABLE IF NOT EXISTS metrics (
-                id INTEGER PRIMARY KEY DEFAULT nextval('metrics_id_seq'),
-                timestamp TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-                processed_rows BIGINT,
-                latency_ms DOUBLE,
-                tokens_saved BIGINT,
-                operation TEXT
-            );",
-        )
-        .map_err(|e| UdoError::Unknown(e.to_string()))?;
-
-        conn.execute(
+        self.connect()?.execute(
             "INSERT INTO metrics (processed_rows, latency_ms, tokens_saved, operation) VALUES (?, ?, ?, ?)",
             params![processed_rows as i64, latency_ms, tokens_saved as i64, operation],
         ).map_err(|e| UdoError::Unknown(e.to_string()))?;
         Ok(())
     }
+
+    /// Records a processor's token counts before and after trimming. Latency and
+    /// `tokens_saved` are left null: the run's `pipeline` row carries both.
+    pub fn record_token_usage(
+        &self,
+        operation: &str,
+        processed_rows: u64,
+        tokens_before: u64,
+        tokens_after: u64,
+    ) -> Result<()> {
+        self.connect()?
+            .execute(
+                "INSERT INTO metrics (processed_rows, tokens_before, tokens_after, operation) VALUES (?, ?, ?, ?)",
+                params![
+                    processed_rows as i64,
+                    tokens_before as i64,
+                    tokens_after as i64,
+                    operation
+                ],
+            )
+            .map_err(|e| UdoError::Unknown(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Stores processor counters, keyed `<processor>.<counter>`.
+    pub fn record_processor_metrics(&self, metrics: &[(String, u64)]) -> Result<()> {
+        let conn = self.connect()?;
+        for (metric, value) in metrics {
+            conn.execute(
+                "INSERT INTO processor_metrics (metric, value) VALUES (?, ?)",
+                params![metric, *value as i64],
+            )
+            .map_err(|e| UdoError::Unknown(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// Stores a processor's end-of-run report as JSON text.
+    pub fn record_report(&self, processor: &str, report: &str) -> Result<()> {
+        self.connect()?
+            .execute(
+                "INSERT INTO reports (processor, report) VALUES (?, ?)",
+                params![processor, report],
+            )
+            .map_err(|e| UdoError::Unknown(e.to_string()))?;
+        Ok(())
+    }
 }
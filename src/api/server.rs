@@ -22,7 +22,8 @@ struct Metric {
     id: i64,
     timestamp: String,
     processed_rows: i64,
-    latency_ms: f64,
+    /// Null for per-processor token usage rows.
+    latency_ms: Option<f64>,
     tokens_saved: i64,
     operation: String,
 }
@@ -73,7 +74,7 @@ async fn get_metrics(data: web::Data<AppState>, _user: User) -> impl Responder {
     // Get recent rows
     let recent_metrics: Vec<Metric> = match conn.prepare(
         "
-        SELECT id, CAST(timestamp AS TEXT), processed_rows, latency_ms, tokens_saved, operation 
+        SELECT id, CAST(timestamp AS TEXT), processed_rows, latency_ms, COALESCE(tokens_saved, 0), operation 
         FROM metrics 
         ORDER BY id DESC 
         LIMIT 10
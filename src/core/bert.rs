@@ -0,0 +1,462 @@
+use crate::core::config::Precision;
+use crate::core::error::{Result, UdoError};
+use candle_core::quantized::{gguf_file, GgmlDType, QMatMul, QTensor};
+use candle_core::safetensors::MmapedSafetensors;
+use candle_core::{DType, Device, Module, Shape, Tensor, D};
+use candle_nn::{LayerNorm, VarBuilder};
+use candle_transformers::models::bert::{BertModel, Config, HiddenAct};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+fn model_err(e: impl std::fmt::Display) -> UdoError {
+    UdoError::AiModel(e.to_string())
+}
+
+/// Block format of the quantized precisions.
+fn ggml_dtype(precision: Precision) -> Option<GgmlDType> {
+    match precision {
+        Precision::Q8_0 => Some(GgmlDType::Q8_0),
+        Precision::Q4_0 => Some(GgmlDType::Q4_0),
+        _ => None,
+    }
+}
+
+enum Source {
+    /// Safetensors converted to a float type while loading.
+    Float(VarBuilder<'static>),
+    /// Safetensors whose linear layers are quantized while loading.
+    Quantize(VarBuilder<'static>, GgmlDType),
+    /// A GGUF file written by [`write_gguf`], with the checkpoint's tensor names.
+    Gguf(HashMap<String, Arc<QTensor>>),
+}
+
+/// The tensors of a checkpoint, handed out in the format a [`Precision`] asks for.
+/// Counts the bytes of everything taken, i.e. the memory the loaded model holds.
+pub struct Weights {
+    source: Source,
+    dtype: DType,
+    device: Device,
+    bytes: Cell<u64>,
+}
+
+impl Weights {
+    pub fn open(path: &Path, precision: Precision, device: &Device) -> Result<Self> {
+        let float = |dtype| {
+            unsafe { VarBuilder::from_mmaped_safetensors(&[path], dtype, device) }
+                .map_err(model_err)
+        };
+        let (source, dtype) = match precision {
+            Precision::F32 => (Source::Float(float(DType::F32)?), DType::F32),
+            Precision::F16 => (Source::Float(float(DType::F16)?), DType::F16),
+            // CPUs have no BF16 matmul, so activations stay F32; see `Matrix::Widen`.
+            Precision::Bf16 => (Source::Float(float(DType::BF16)?), DType::F32),
+            Precision::Q8_0 => (
+                Source::Quantize(float(DType::F32)?, GgmlDType::Q8_0),
+                DType::F32,
+            ),
+            Precision::Q4_0 => (
+                Source::Quantize(float(DType::F32)?, GgmlDType::Q4_0),
+                DType::F32,
+            ),
+            Precision::Gguf => {
+                let mut file = std::fs::File::open(path)?;
+                let content = gguf_file::Content::read(&mut file).map_err(model_err)?;
+                let mut tensors = HashMap::new();
+                for name in content.tensor_infos.keys() {
+                    let tensor = content.tensor(&mut file, name, device).map_err(model_err)?;
+                    tensors.insert(name.clone(), Arc::new(tensor));
+                }
+                (Source::Gguf(tensors), DType::F32)
+            }
+        };
+        Ok(Self {
+            source,
+            dtype,
+            device: device.clone(),
+            bytes: Cell::new(0),
+        })
+    }
+
+    /// Type of the activations of a model built from these weights.
+    pub fn dtype(&self) -> DType {
+        self.dtype
+    }
+
+    /// Bytes of the tensors handed out so far.
+    pub fn bytes(&self) -> u64 {
+        self.bytes.get()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        match &self.source {
+            Source::Float(vb) | Source::Quantize(vb, _) => vb.contains_tensor(name),
+            Source::Gguf(tensors) => tensors.contains_key(name),
+        }
+    }
+
+    fn count(&self, tensor: &Tensor) {
+        let bytes = tensor.elem_count() * tensor.dtype().size_in_bytes();
+        self.bytes.set(self.bytes.get() + bytes as u64);
+    }
+
+    fn quantized(&self, name: &str, shape: &Shape) -> Result<Arc<QTensor>> {
+        let Source::Gguf(tensors) = &self.source else {
+            return Err(UdoError::AiModel(format!("{} is not a GGUF tensor", name)));
+        };
+        let tensor = tensors
+            .get(name)
+            .ok_or_else(|| UdoError::AiModel(format!("GGUF file has no tensor {}", name)))?;
+        if tensor.shape() != shape {
+            return Err(UdoError::AiModel(format!(
+                "GGUF tensor {} has shape {:?}, expected {:?}",
+                name,
+                tensor.shape(),
+                shape
+            )));
+        }
+        Ok(tensor.clone())
+    }
+
+    /// A dense tensor in the activation type.
+    pub fn tensor(&self, name: &str, shape: impl Into<Shape>) -> Result<Tensor> {
+        let shape = shape.into();
+        let tensor = match &self.source {
+            Source::Float(vb) | Source::Quantize(vb, _) => vb.get(shape, name),
+            Source::Gguf(_) => self.quantized(name, &shape)?.dequantize(&self.device),
+        }
+        .and_then(|t| t.to_dtype(self.dtype))
+        .map_err(model_err)?;
+        self.count(&tensor);
+        Ok(tensor)
+    }
+
+    /// An embedding table. Quantized precisions keep it in F16, as an F32 table would
+    /// be a large share of the model.
+    fn table(&self, name: &str, shape: impl Into<Shape>) -> Result<Tensor> {
+        let shape = shape.into();
+        let tensor = match &self.source {
+            Source::Float(vb) => vb.get(shape, name),
+            Source::Quantize(vb, _) => vb.get(shape, name).and_then(|t| t.to_dtype(DType::F16)),
+            Source::Gguf(_) => self.quantized(name, &shape)?.dequantize_f16(&self.device),
+        }
+        .map_err(model_err)?;
+        self.count(&tensor);
+        Ok(tensor)
+    }
+
+    fn linear(&self, prefix: &str, in_dim: usize, out_dim: usize) -> Result<Linear> {
+        let bias = self.tensor(&format!("{}.bias", prefix), out_dim)?;
+        let name = format!("{}.weight", prefix);
+        let shape = Shape::from((out_dim, in_dim));
+        let weight = match &self.source {
+            Source::Float(vb) => {
+                let weight = vb.get(shape, &name).map_err(model_err)?;
+                if weight.dtype() == self.dtype {
+                    Matrix::Dense(QMatMul::Tensor(weight))
+                } else {
+                    Matrix::Widen(weight)
+                }
+            }
+            Source::Quantize(vb, dtype) => {
+                let weight = vb.get(shape, &name).map_err(model_err)?;
+                // Rows must split into whole blocks; otherwise the layer stays dense.
+                Matrix::Dense(if in_dim.is_multiple_of(dtype.block_size()) {
+                    let weight = QTensor::quantize(&weight, *dtype).map_err(model_err)?;
+                    QMatMul::QTensor(Arc::new(weight))
+                } else {
+                    QMatMul::Tensor(weight)
+                })
+            }
+            Source::Gguf(_) => {
+                Matrix::Dense(QMatMul::from_arc(self.quantized(&name, &shape)?).map_err(model_err)?)
+            }
+        };
+        match &weight {
+            Matrix::Dense(QMatMul::QTensor(q)) => self
+                .bytes
+                .set(self.bytes.get() + q.storage_size_in_bytes() as u64),
+            Matrix::Dense(QMatMul::Tensor(t) | QMatMul::TensorF16(t)) | Matrix::Widen(t) => {
+                self.count(t)
+            }
+        }
+        Ok(Linear { weight, bias })
+    }
+
+    fn layer_norm(&self, prefix: &str, size: usize, eps: f64) -> Result<LayerNorm> {
+        Ok(LayerNorm::new(
+            self.tensor(&format!("{}.weight", prefix), size)?,
+            self.tensor(&format!("{}.bias", prefix), size)?,
+            eps,
+        ))
+    }
+}
+
+enum Matrix {
+    Dense(QMatMul),
+    /// Kept in a narrower float type than the activations and widened for each call.
+    Widen(Tensor),
+}
+
+struct Linear {
+    weight: Matrix,
+    bias: Tensor,
+}
+
+impl Module for Linear {
+    fn forward(&self, xs: &Tensor) -> candle_core::Result<Tensor> {
+        let ys = match &self.weight {
+            Matrix::Dense(weight) => xs.apply(weight)?,
+            Matrix::Widen(weight) => {
+                let weight = weight.to_dtype(xs.dtype())?;
+                candle_nn::Linear::new(weight, None).forward(xs)?
+            }
+        };
+        ys.broadcast_add(&self.bias)
+    }
+}
+
+struct Layer {
+    query: Linear,
+    key: Linear,
+    value: Linear,
+    attention_output: Linear,
+    attention_norm: LayerNorm,
+    intermediate: Linear,
+    output: Linear,
+    output_norm: LayerNorm,
+}
+
+/// A BERT encoder over [`Weights`] of any [`Precision`]. Unlike candle's reference
+/// model it runs its activations in F16 for F16 weights; every other precision
+/// computes in F32.
+pub struct CompactBert {
+    word_embeddings: Tensor,
+    position_embeddings: Tensor,
+    token_type_embeddings: Tensor,
+    embeddings_norm: LayerNorm,
+    layers: Vec<Layer>,
+    heads: usize,
+    head_size: usize,
+    act: HiddenAct,
+    dtype: DType,
+}
+
+impl CompactBert {
+    /// Loads the encoder; token-classification checkpoints nest its tensors under
+    /// `prefix`, e.g. `bert`.
+    pub fn load(weights: &Weights, config: &Config, prefix: Option<&str>) -> Result<Self> {
+        let root = match prefix {
+            Some(prefix)
+                if weights.contains(&format!("{}.embeddings.word_embeddings.weight", prefix)) =>
+            {
+                format!("{}.", prefix)
+            }
+            _ => String::new(),
+        };
+        let hidden = config.hidden_size;
+        let eps = config.layer_norm_eps;
+        let table = |name: &str, rows: usize| {
+            weights.table(
+                &format!("{}embeddings.{}.weight", root, name),
+                (rows, hidden),
+            )
+        };
+
+        let mut layers = Vec::with_capacity(config.num_hidden_layers);
+        for i in 0..config.num_hidden_layers {
+            let layer = format!("{}encoder.layer.{}", root, i);
+            let linear = |name: &str, in_dim, out_dim| {
+                weights.linear(&format!("{}.{}", layer, name), in_dim, out_dim)
+            };
+            let norm = |name: &str| weights.layer_norm(&format!("{}.{}", layer, name), hidden, eps);
+            layers.push(Layer {
+                query: linear("attention.self.query", hidden, hidden)?,
+                key: linear("attention.self.key", hidden, hidden)?,
+                value: linear("attention.self.value", hidden, hidden)?,
+                attention_output: linear("attention.output.dense", hidden, hidden)?,
+                attention_norm: norm("attention.output.LayerNorm")?,
+                intermediate: linear("intermediate.dense", hidden, config.intermediate_size)?,
+                output: linear("output.dense", config.intermediate_size, hidden)?,
+                output_norm: norm("output.LayerNorm")?,
+            });
+        }
+
+        Ok(Self {
+            word_embeddings: table("word_embeddings", config.vocab_size)?,
+            position_embeddings: table("position_embeddings", config.max_position_embeddings)?,
+            token_type_embeddings: table("token_type_embeddings", config.type_vocab_size)?,
+            embeddings_norm: weights.layer_norm(
+                &format!("{}embeddings.LayerNorm", root),
+                hidden,
+                eps,
+            )?,
+            layers,
+            heads: config.num_attention_heads,
+            head_size: hidden / config.num_attention_heads,
+            act: config.hidden_act,
+            dtype: weights.dtype(),
+        })
+    }
+
+    /// F32 hidden states for a padded batch; `mask` is 1 for real tokens and 0 for padding.
+    pub fn forward(
+        &self,
+        ids: &Tensor,
+        type_ids: &Tensor,
+        mask: &Tensor,
+    ) -> candle_core::Result<Tensor> {
+        let (_, len) = ids.dims2()?;
+        let positions = Tensor::arange(0u32, len as u32, ids.device())?;
+        let hidden = (self.lookup(&self.word_embeddings, ids)?
+            + self.lookup(&self.token_type_embeddings, type_ids)?)?
+        .broadcast_add(&self.lookup(&self.position_embeddings, &positions)?)?;
+        let mut hidden = self.embeddings_norm.forward(&hidden)?;
+
+        // Added to the attention scores: 0 for real tokens, -10000 for padding.
+        let bias = mask
+            .to_dtype(DType::F32)?
+            .affine(10000.0, -10000.0)?
+            .to_dtype(self.dtype)?
+            .unsqueeze(1)?
+            .unsqueeze(1)?;
+        for layer in &self.layers {
+            hidden = self.layer(layer, &hidden, &bias)?;
+        }
+        hidden.to_dtype(DType::F32)
+    }
+
+    fn lookup(&self, table: &Tensor, ids: &Tensor) -> candle_core::Result<Tensor> {
+        let mut shape = ids.dims().to_vec();
+        shape.push(table.dim(1)?);
+        table
+            .index_select(&ids.flatten_all()?, 0)?
+            .reshape(shape)?
+            .to_dtype(self.dtype)
+    }
+
+    fn layer(&self, layer: &Layer, xs: &Tensor, bias: &Tensor) -> candle_core::Result<Tensor> {
+        let (batch, len, _) = xs.dims3()?;
+        let heads = |t: Tensor| {
+            t.reshape((batch, len, self.heads, self.head_size))?
+                .transpose(1, 2)?
+                .contiguous()
+        };
+        let query = heads(layer.query.forward(xs)?)?;
+        let key = heads(layer.key.forward(xs)?)?;
+        let value = heads(layer.value.forward(xs)?)?;
+
+        let scores = (query.matmul(&key.t()?)? / (self.head_size as f64).sqrt())?;
+        let probs = candle_nn::ops::softmax(&scores.broadcast_add(bias)?, D::Minus1)?;
+        let context = probs
+            .matmul(&value)?
+            .transpose(1, 2)?
+            .contiguous()?
+            .reshape((batch, len, ()))?;
+        let attention = layer
+            .attention_norm
+            .forward(&(layer.attention_output.forward(&context)? + xs)?)?;
+
+        let intermediate = layer.intermediate.forward(&attention)?;
+        let intermediate = match self.act {
+            HiddenAct::Gelu => intermediate.gelu_erf()?,
+            HiddenAct::GeluApproximate => intermediate.gelu()?,
+            HiddenAct::Relu => intermediate.relu()?,
+        };
+        layer
+            .output_norm
+            .forward(&(layer.output.forward(&intermediate)? + attention)?)
+    }
+}
+
+/// A BERT encoder: candle's reference model for F32 weights, [`CompactBert`] for the
+/// other precisions.
+pub enum Bert {
+    Reference(BertModel),
+    Compact(CompactBert),
+}
+
+impl Bert {
+    pub fn load(
+        weights: &Path,
+        config: &Config,
+        precision: Precision,
+        device: &Device,
+    ) -> Result<(Self, u64)> {
+        if precision == Precision::F32 {
+            let vb = unsafe { VarBuilder::from_mmaped_safetensors(&[weights], DType::F32, device) }
+                .map_err(model_err)?;
+            let model = BertModel::load(vb, config).map_err(model_err)?;
+            // The reference model keeps every tensor of the checkpoint in F32.
+            let bytes = std::fs::metadata(weights)?.len();
+            return Ok((Self::Reference(model), bytes));
+        }
+        let weights = Weights::open(weights, precision, device)?;
+        let model = CompactBert::load(&weights, config, config.model_type.as_deref())?;
+        Ok((Self::Compact(model), weights.bytes()))
+    }
+
+    /// F32 hidden states; like candle's `BertModel::forward`.
+    pub fn forward(
+        &self,
+        ids: &Tensor,
+        type_ids: &Tensor,
+        mask: Option<&Tensor>,
+    ) -> candle_core::Result<Tensor> {
+        match self {
+            Self::Reference(model) => model.forward(ids, type_ids, mask),
+            Self::Compact(model) => match mask {
+                Some(mask) => model.forward(ids, type_ids, mask),
+                None => model.forward(ids, type_ids, &ids.ones_like()?),
+            },
+        }
+    }
+}
+
+/// Writes a safetensors checkpoint as GGUF for [`Precision::Gguf`]. Linear layer weights
+/// are quantized to `precision` (`q8_0` or `q4_0`), embedding tables stored as F16 and
+/// everything else as F32; tensor names are kept. Returns the size of the file.
+pub fn write_gguf(safetensors: &Path, precision: Precision, output: &Path) -> Result<u64> {
+    let dtype = ggml_dtype(precision).ok_or_else(|| {
+        UdoError::Config(format!(
+            "Cannot quantize to {}; use q8_0 or q4_0",
+            precision
+        ))
+    })?;
+    let device = Device::Cpu;
+    let checkpoint = unsafe { MmapedSafetensors::new(safetensors) }.map_err(model_err)?;
+
+    let mut tensors = Vec::new();
+    for (name, _) in checkpoint.tensors() {
+        let tensor = checkpoint.load(&name, &device).map_err(model_err)?;
+        // Integer buffers such as `position_ids` are not weights.
+        if !tensor.dtype().is_float() {
+            continue;
+        }
+        let tensor = tensor.to_dtype(DType::F32).map_err(model_err)?;
+        let target = match tensor.dims() {
+            [_, _] if name.contains("embeddings") => GgmlDType::F16,
+            [_, cols] if cols.is_multiple_of(dtype.block_size()) => dtype,
+            _ => GgmlDType::F32,
+        };
+        tensors.push((name, QTensor::quantize(&tensor, target).map_err(model_err)?));
+    }
+    tensors.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let architecture = gguf_file::Value::String("bert".to_string());
+    let quantization = gguf_file::Value::String(precision.to_string());
+    let refs: Vec<(&str, &QTensor)> = tensors.iter().map(|(n, t)| (n.as_str(), t)).collect();
+    let mut file = std::io::BufWriter::new(std::fs::File::create(output)?);
+    gguf_file::write(
+        &mut file,
+        &[
+            ("general.architecture", &architecture),
+            ("udo.quantization", &quantization),
+        ],
+        &refs,
+    )
+    .map_err(model_err)?;
+    drop(file);
+    Ok(std::fs::metadata(output)?.len())
+}
@@ -1,21 +1,145 @@
+use crate::core::error::{Result, UdoError};
 use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
 use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PipelineConfig {
+    /// Name recorded in DLQ envelopes and metrics.
+    #[serde(default)]
+    pub name: Option<String>,
     pub source: SourceConfig,
-    pub processors: Vec<ProcessorConfig>,
+    pub processors: Vec<ProcessorEntry>,
     pub sink: SinkConfig,
     #[serde(default)]
     pub dlq: Option<SinkConfig>,
     #[serde(default = "default_batch_size")]
     pub batch_size: usize,
+    /// Abort the job when more than this fraction (0.0-1.0) of records is dead-lettered.
+    #[serde(default)]
+    pub max_error_rate: Option<f64>,
+    /// Number of records to see before `max_error_rate` is enforced mid-run.
+    #[serde(default = "default_error_rate_min_records")]
+    pub error_rate_min_records: u64,
+    /// Where models are cached and which exact files the processors may load.
+    #[serde(default)]
+    pub models: ModelsConfig,
+}
+
+/// Model download and verification settings shared by every model-backed processor.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelsConfig {
+    /// Hub download cache, instead of the Hugging Face default (`HF_HOME`).
+    #[serde(default)]
+    pub cache_dir: Option<PathBuf>,
+    /// Never download; load only models already in the cache (see `models pull`).
+    #[serde(default)]
+    pub offline: bool,
+    /// Pinned revision and checksum per Hugging Face model ID.
+    #[serde(default)]
+    pub pins: HashMap<String, ModelPin>,
+    /// Forward passes run at once, off the async runtime; default: number of CPUs.
+    #[serde(default)]
+    pub threads: Option<usize>,
+    /// How long a request waits for concurrent ones to share its forward pass.
+    #[serde(default)]
+    pub batch_wait_ms: Option<u64>,
+    /// Format model weights are kept in, unless a pin says otherwise.
+    #[serde(default)]
+    pub precision: Precision,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelPin {
+    /// Branch, tag or commit to download; a commit hash pins the exact files.
+    #[serde(default)]
+    pub revision: Option<String>,
+    /// Expected hex SHA-256 of the weights file; loading fails on a mismatch.
+    #[serde(default)]
+    pub sha256: Option<String>,
+    /// Format this model's weights are kept in, overriding `models.precision`.
+    #[serde(default)]
+    pub precision: Option<Precision>,
+}
+
+/// How model weights are held in memory. Everything but `f32` trades some accuracy
+/// for memory and, on most CPUs, speed; only BERT models support the other modes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Precision {
+    #[default]
+    #[serde(rename = "f32")]
+    F32,
+    #[serde(rename = "f16")]
+    F16,
+    #[serde(rename = "bf16")]
+    Bf16,
+    /// 8-bit blocks, quantized from `model.safetensors` while loading.
+    #[serde(rename = "q8_0")]
+    Q8_0,
+    /// 4-bit blocks, quantized from `model.safetensors` while loading.
+    #[serde(rename = "q4_0")]
+    Q4_0,
+    /// Pre-quantized `model.gguf`, e.g. written by `udo-cli models quantize`.
+    #[serde(rename = "gguf")]
+    Gguf,
+}
+
+impl Precision {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Precision::F32 => "f32",
+            Precision::F16 => "f16",
+            Precision::Bf16 => "bf16",
+            Precision::Q8_0 => "q8_0",
+            Precision::Q4_0 => "q4_0",
+            Precision::Gguf => "gguf",
+        }
+    }
+
+    /// The file the weights are read from.
+    pub fn weights_file(&self) -> &'static str {
+        match self {
+            Precision::Gguf => "model.gguf",
+            _ => "model.safetensors",
+        }
+    }
+}
+
+impl std::str::FromStr for Precision {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        [
+            Precision::F32,
+            Precision::F16,
+            Precision::Bf16,
+            Precision::Q8_0,
+            Precision::Q4_0,
+            Precision::Gguf,
+        ]
+        .into_iter()
+        .find(|p| p.as_str() == s)
+        .ok_or_else(|| format!("unknown precision '{}'", s))
+    }
+}
+
+impl std::fmt::Display for Precision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 fn default_batch_size() -> usize {
     10000
 }
 
+fn default_error_rate_min_records() -> u64 {
+    1000
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum SourceConfig {
@@ -24,6 +148,13 @@ pub enum SourceConfig {
     },
     Csv {
         path: PathBuf,
+        #[serde(flatten)]
+        options: CsvOptions,
+        /// Explicit column list. When omitted the schema is inferred from the file.
+        #[serde(default)]
+        schema: Option<Vec<CsvColumn>>,
+        #[serde(default = "default_csv_infer_rows")]
+        infer_schema_rows: usize,
     },
     Avro {
         path: PathBuf,
@@ -36,6 +167,182 @@ pub enum SourceConfig {
     },
 }
 
+fn default_csv_infer_rows() -> usize {
+    1000
+}
+
+/// Dialect settings shared by the CSV source and sink.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CsvOptions {
+    #[serde(default = "default_csv_delimiter")]
+    pub delimiter: char,
+    #[serde(default)]
+    pub quote: Option<char>,
+    #[serde(default)]
+    pub escape: Option<char>,
+    #[serde(default = "default_true")]
+    pub has_header: bool,
+    /// String that represents a missing value, e.g. `NA` or `\N`.
+    #[serde(default)]
+    pub null_value: Option<String>,
+    /// chrono format string for `date` columns, e.g. `%d/%m/%Y`.
+    #[serde(default)]
+    pub date_format: Option<String>,
+    /// chrono format string for `timestamp` columns, e.g. `%Y-%m-%d %H:%M:%S`.
+    #[serde(default)]
+    pub timestamp_format: Option<String>,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: default_csv_delimiter(),
+            quote: None,
+            escape: None,
+            has_header: true,
+            null_value: None,
+            date_format: None,
+            timestamp_format: None,
+        }
+    }
+}
+
+fn default_csv_delimiter() -> char {
+    ','
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Converts a single-character dialect setting into the byte the Arrow CSV codec expects.
+pub fn ascii_byte(c: char, setting: &str) -> Result<u8> {
+    if c.is_ascii() {
+        Ok(c as u8)
+    } else {
+        Err(UdoError::Config(format!(
+            "CSV {} must be a single ASCII character, got '{}'",
+            setting, c
+        )))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CsvColumn {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub data_type: CsvColumnType,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CsvColumnType {
+    #[serde(alias = "int", alias = "integer")]
+    Int64,
+    #[serde(alias = "float", alias = "double")]
+    Float64,
+    #[serde(alias = "string", alias = "str")]
+    Utf8,
+    #[serde(alias = "bool")]
+    Boolean,
+    Date,
+    Timestamp,
+}
+
+/// A processor plus the settings the runner applies around it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProcessorEntry {
+    #[serde(flatten)]
+    pub processor: ProcessorConfig,
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    /// Run the processor only on records a `language_detector` earlier in the pipeline
+    /// tagged with one of these ISO 639-1 codes (`und`: not detected); all when empty.
+    #[serde(default)]
+    pub languages: Vec<String>,
+}
+
+/// Exponential backoff applied when a processor fails on a record. With the default
+/// `max_attempts: 1` the record goes straight to the DLQ.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    #[serde(default = "default_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    #[serde(default = "default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+    #[serde(default = "default_backoff_multiplier")]
+    pub multiplier: f64,
+    /// Fraction (0.0-1.0) by which each delay is randomly shortened or lengthened.
+    #[serde(default = "default_jitter")]
+    pub jitter: f64,
+    /// `UdoError::kind()` values worth retrying, e.g. `ai_model`, `io`.
+    #[serde(default = "default_retryable_kinds")]
+    pub retryable: Vec<String>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: default_max_attempts(),
+            initial_backoff_ms: default_initial_backoff_ms(),
+            max_backoff_ms: default_max_backoff_ms(),
+            multiplier: default_backoff_multiplier(),
+            jitter: default_jitter(),
+            retryable: default_retryable_kinds(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn is_retryable(&self, error: &UdoError) -> bool {
+        self.retryable.iter().any(|kind| kind == error.kind())
+    }
+
+    /// Delay before retry number `attempt` (1-based).
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(63) as i32;
+        let base = (self.initial_backoff_ms as f64 * self.multiplier.powi(exponent))
+            .min(self.max_backoff_ms as f64);
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        let factor = 1.0 + jitter * (2.0 * random_unit() - 1.0);
+        Duration::from_millis((base * factor).max(0.0) as u64)
+    }
+}
+
+/// Uniform value in [0, 1) from std's randomly seeded hasher; good enough for jitter.
+fn random_unit() -> f64 {
+    let bits = RandomState::new().build_hasher().finish();
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+fn default_max_attempts() -> u32 {
+    1
+}
+
+fn default_initial_backoff_ms() -> u64 {
+    100
+}
+
+fn default_max_backoff_ms() -> u64 {
+    10_000
+}
+
+fn default_backoff_multiplier() -> f64 {
+    2.0
+}
+
+fn default_jitter() -> f64 {
+    0.2
+}
+
+fn default_retryable_kinds() -> Vec<String> {
+    vec!["ai_model".to_string(), "io".to_string()]
+}
+
+// Parsed once at startup, so the size of the PII masker variant does not matter.
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ProcessorConfig {
@@ -45,20 +352,465 @@ pub enum ProcessorConfig {
         use_ner: bool,
         #[serde(default)]
         model_path: Option<PathBuf>,
+        /// Regex detector IDs to run (see `processors::pii::detectors`); all when omitted.
+        #[serde(default)]
+        detectors: Option<Vec<String>>,
+        #[serde(default)]
+        disabled_detectors: Vec<String>,
+        /// Span replacement per detector ID or NER label, e.g. `PER: "<PERSON_{n}>"`.
+        #[serde(default)]
+        templates: HashMap<String, String>,
+        /// Secret for `hash` and `tokenize` modes, required when any field or entity
+        /// type uses them.
+        #[serde(default)]
+        key: Option<KeySource>,
+        /// Encrypted token vault for re-identifying `tokenize` output. Requires `key`.
+        #[serde(default)]
+        vault: Option<PathBuf>,
+        /// Which fields are scanned and what happens to them.
+        #[serde(default)]
+        policy: PiiPolicy,
+        /// Batching and long-text windowing for the NER model.
+        #[serde(default)]
+        ner: NerOptions,
+    },
+    SecretScanner {
+        /// Rule IDs to run (see `processors::secrets`); all built-ins when omitted.
+        #[serde(default)]
+        rules: Option<Vec<String>>,
+        #[serde(default)]
+        disabled_rules: Vec<String>,
+        #[serde(default)]
+        redaction: SecretRedaction,
+        /// Per-rule redaction overrides, keyed by rule ID.
+        #[serde(default)]
+        rule_redaction: HashMap<String, SecretRedaction>,
+        /// Minimum Shannon entropy (bits per char) for the `high_entropy` rule.
+        #[serde(default = "default_entropy_threshold")]
+        entropy_threshold: f64,
+    },
+    /// Keeps records within an LLM token budget by trimming fields by priority.
+    TokenFirewall {
+        #[serde(default)]
+        tokenizer: TokenizerConfig,
+        #[serde(default)]
+        max_tokens_per_record: Option<usize>,
+        /// Budget for all rows of a batch; rows that do not fit are dropped.
+        #[serde(default)]
+        max_tokens_per_batch: Option<usize>,
+        /// Top-level fields, most important first; unlisted fields are trimmed first.
+        #[serde(default)]
+        priority: Vec<String>,
+        #[serde(default)]
+        overflow: TokenOverflow,
     },
     #[cfg(feature = "semantic")]
     SemanticPruner {
         query: String,
-        #[serde(default = "default_threshold")]
-        threshold: f32,
+        /// Minimum column score; defaults to 0.85 unless `top_k` or
+        /// `relative_threshold` select the columns.
+        #[serde(default)]
+        threshold: Option<f32>,
+        /// Minimum score as a fraction of the best column's score.
+        #[serde(default)]
+        relative_threshold: Option<f32>,
+        /// Keep at most this many of the best scoring columns.
+        #[serde(default)]
+        top_k: Option<usize>,
+        /// Columns kept whatever their score, e.g. join keys and IDs.
+        #[serde(default)]
+        always_keep: Vec<String>,
+        /// Columns dropped whatever their score.
+        #[serde(default)]
+        never_keep: Vec<String>,
+        /// Fail instead of keeping every column when none is selected.
+        #[serde(default)]
+        fail_if_empty: bool,
         #[serde(default)]
         model_path: Option<PathBuf>,
+        /// Directory for embeddings reused across runs, keyed by model and text.
+        #[serde(default)]
+        cache_dir: Option<PathBuf>,
+        /// Texts per embedding forward pass.
+        #[serde(default = "default_embedding_batch_size")]
+        batch_size: usize,
+        /// Column descriptions, keyed by column name or dotted nested path.
+        #[serde(default)]
+        descriptions: HashMap<String, String>,
+        /// YAML or JSON data dictionary file with more descriptions; inline
+        /// `descriptions` take precedence.
+        #[serde(default)]
+        dictionary: Option<PathBuf>,
+        /// Distinct warm-up values added to each column's text.
+        #[serde(default = "default_sample_values")]
+        sample_values: usize,
+        /// Share of the score from BM25 keyword matching, in `[0, 1]`.
+        #[serde(default = "default_lexical_weight")]
+        lexical_weight: f32,
     },
+    /// Adds an embedding column per text field, e.g. for loading into a vector store.
+    #[cfg(feature = "semantic")]
+    Embedding {
+        /// Text fields (dotted paths for nested values) to embed.
+        fields: Vec<String>,
+        /// Output columns are named `<field><suffix>`.
+        #[serde(default = "default_embedding_suffix")]
+        suffix: String,
+        #[serde(default)]
+        model_path: Option<PathBuf>,
+        /// Directory for embeddings reused across runs, keyed by model and text.
+        #[serde(default)]
+        cache_dir: Option<PathBuf>,
+        #[serde(default = "default_embedding_batch_size")]
+        batch_size: usize,
+    },
+    /// Annotates records with the ISO 639-1 code of their text's language and the
+    /// detection's confidence.
+    LanguageDetector {
+        /// Fields (dotted paths for nested values) whose text is examined.
+        fields: Vec<String>,
+        /// Detections less confident than this leave the language null.
+        #[serde(default)]
+        min_confidence: f32,
+        #[serde(default = "default_language_field")]
+        language_field: String,
+        #[serde(default = "default_language_confidence_field")]
+        confidence_field: String,
+    },
+    /// Tags records with the best matching of `labels`, by keyword rules or by
+    /// embedding similarity to the labels' descriptions.
+    Classifier {
+        /// Fields (dotted paths for nested values) whose text is classified.
+        fields: Vec<String>,
+        /// Candidate labels; earlier labels win ties.
+        labels: Vec<ClassLabel>,
+        #[serde(default)]
+        method: ClassifyMethod,
+        /// Minimum score of the best label; records below it get `fallback`.
+        #[serde(default)]
+        threshold: Option<f32>,
+        /// Label for records no label matches; null when unset.
+        #[serde(default)]
+        fallback: Option<String>,
+        #[serde(default = "default_label_field")]
+        label_field: String,
+        #[serde(default = "default_label_score_field")]
+        score_field: String,
+        #[cfg(feature = "semantic")]
+        #[serde(default)]
+        model_path: Option<PathBuf>,
+        #[cfg(feature = "semantic")]
+        #[serde(default)]
+        cache_dir: Option<PathBuf>,
+        #[cfg(feature = "semantic")]
+        #[serde(default = "default_embedding_batch_size")]
+        batch_size: usize,
+    },
+    /// Scores rows against `query` by the text of `fields` and drops irrelevant ones.
+    #[cfg(feature = "semantic")]
+    SemanticRowFilter {
+        query: String,
+        /// Fields (dotted paths for nested values) whose text is embedded per row.
+        fields: Vec<String>,
+        /// Drop rows scoring below this similarity.
+        #[serde(default)]
+        threshold: Option<f32>,
+        /// Keep the N most relevant rows of each batch of `batch_size` records.
+        #[serde(default)]
+        top_n: Option<usize>,
+        /// Column the relevance score is written to.
+        #[serde(default = "default_score_field")]
+        score_field: String,
+        #[serde(default)]
+        model_path: Option<PathBuf>,
+        #[serde(default)]
+        cache_dir: Option<PathBuf>,
+        #[serde(default = "default_embedding_batch_size")]
+        batch_size: usize,
+    },
+}
+
+/// A category a `classifier` can tag records with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassLabel {
+    pub name: String,
+    /// What records with this label are about; embedded for `method: embedding`
+    /// instead of the name.
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Words or phrases that indicate the label, for `method: keywords`.
+    #[serde(default)]
+    pub keywords: Vec<String>,
+}
+
+/// How a `classifier` scores its labels.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClassifyMethod {
+    /// Share of the keyword matches in a record that belong to the label.
+    #[default]
+    Keywords,
+    /// Cosine similarity of the record's embedding to the label's.
+    Embedding,
+}
+
+/// What a `pii_masker` does with a field. `mask`, `hash` and `tokenize` rewrite the
+/// PII detected within the field; `drop` removes the field, `allow` leaves it unscanned,
+/// `detect_only` counts findings without modifying the field and `tag` lists them in
+/// the record's `_entities` array, leaving the field as is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PiiAction {
+    Mask,
+    Hash,
+    Tokenize,
+    Drop,
+    Allow,
+    DetectOnly,
+    Tag,
+}
+
+impl PiiAction {
+    /// Action for a masker `mode` string; unknown modes mask, as they always have.
+    pub fn from_mode(mode: &str) -> Self {
+        match mode {
+            "hash" => Self::Hash,
+            "tokenize" => Self::Tokenize,
+            "detect_only" => Self::DetectOnly,
+            "tag" => Self::Tag,
+            _ => Self::Mask,
+        }
+    }
+}
+
+/// Per-dataset PII policy: JSONPath-style selectors (`$.user.email`, `$..ssn`,
+/// `$.items[*].note`) mapped to actions.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PiiPolicy {
+    /// Action for fields no rule covers; the masker's `mode` when unset.
+    #[serde(default)]
+    pub default: Option<PiiAction>,
+    /// Only fields under these selectors are scanned (all fields when empty).
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Fields under these selectors are never scanned or modified.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Field rules. A rule covers the matched field and its children; the deepest
+    /// matching rule wins, and the first listed on ties.
+    #[serde(default)]
+    pub fields: Vec<FieldRule>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldRule {
+    pub path: String,
+    pub action: PiiAction,
+}
+
+/// NER model and inference settings. Texts longer than `max_tokens` are split into
+/// windows that overlap by `stride` tokens.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NerOptions {
+    /// Hugging Face token-classification model ID; the CoNLL-03 BERT when unset.
+    /// Ignored when the masker's `model_path` is set.
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Hub branch, tag or commit of `model`.
+    #[serde(default)]
+    pub revision: Option<String>,
+    /// Windows per forward pass.
+    #[serde(default = "default_ner_batch_size")]
+    pub batch_size: usize,
+    /// Window length in tokens, special tokens included; the longest the model
+    /// allows (at most 512) when unset.
+    #[serde(default)]
+    pub max_tokens: Option<usize>,
+    /// Window overlap in tokens; a quarter of the window, at most 128, when unset.
+    #[serde(default)]
+    pub stride: Option<usize>,
+    /// Entity types to act on, keyed by label without the BIO prefix (`PER`, `ORG`,
+    /// ...). Every type the model predicts is acted on when empty.
+    #[serde(default)]
+    pub entities: HashMap<String, EntityRule>,
+    /// Minimum confidence for entity types without their own `min_score`.
+    #[serde(default)]
+    pub min_score: f32,
+}
+
+/// How a `pii_masker` treats one NER entity type.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EntityRule {
+    /// Minimum softmax confidence for the entity to count.
+    #[serde(default)]
+    pub min_score: Option<f32>,
+    /// Replaces the field's action for this type (`mask`, `hash`, `tokenize`,
+    /// `detect_only` or `tag`). Fields whose action is `detect_only` or `tag` are never
+    /// modified.
+    #[serde(default)]
+    pub action: Option<PiiAction>,
+}
+
+impl Default for NerOptions {
+    fn default() -> Self {
+        Self {
+            model: None,
+            revision: None,
+            batch_size: default_ner_batch_size(),
+            max_tokens: None,
+            stride: None,
+            entities: HashMap::new(),
+            min_score: 0.0,
+        }
+    }
+}
+
+fn default_ner_batch_size() -> usize {
+    16
+}
+
+/// Where to read a secret key from: the raw bytes of an environment variable or of a
+/// file (trailing whitespace trimmed).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct KeySource {
+    #[serde(default)]
+    pub env: Option<String>,
+    #[serde(default)]
+    pub file: Option<PathBuf>,
+}
+
+impl KeySource {
+    pub fn load(&self) -> Result<Vec<u8>> {
+        let key = match (&self.env, &self.file) {
+            (Some(var), None) => std::env::var(var)
+                .map_err(|_| UdoError::Config(format!("Key variable {} is not set", var)))?
+                .into_bytes(),
+            (None, Some(path)) => {
+                let mut bytes = std::fs::read(path)?;
+                while bytes.last().is_some_and(|b| b.is_ascii_whitespace()) {
+                    bytes.pop();
+                }
+                bytes
+            }
+            _ => {
+                return Err(UdoError::Config(
+                    "Key source needs exactly one of 'env' or 'file'".to_string(),
+                ))
+            }
+        };
+        if key.len() < 16 {
+            return Err(UdoError::Config(
+                "Key must be at least 16 bytes long".to_string(),
+            ));
+        }
+        Ok(key)
+    }
+}
+
+/// How a detected secret is rewritten.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretRedaction {
+    /// Replace the secret with `[REDACTED:<rule>]`.
+    #[default]
+    Mask,
+    /// Replace the secret with its SHA-256 hex digest.
+    Hash,
+    /// Keep the first four characters and mask the rest with `*`.
+    Partial,
+    /// Replace the whole field value with null.
+    Drop,
+}
+
+/// Tokenizer a `token_firewall` counts tokens with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TokenizerConfig {
+    /// About `chars_per_token` characters per token; needs no vocabulary.
+    Approximate {
+        #[serde(default = "default_chars_per_token")]
+        chars_per_token: f64,
+    },
+    /// A built-in OpenAI encoding, e.g. `cl100k_base` or `o200k_base`.
+    Tiktoken { encoding: String },
+    /// A tiktoken-format BPE rank file; `pattern` defaults to the cl100k pre-tokenizer.
+    TiktokenFile {
+        path: PathBuf,
+        #[serde(default)]
+        pattern: Option<String>,
+    },
+    /// A HuggingFace `tokenizer.json`, e.g. of the model the context is sent to.
+    #[cfg(feature = "ai")]
+    #[serde(rename = "huggingface")]
+    HuggingFace { path: PathBuf },
+}
+
+impl Default for TokenizerConfig {
+    fn default() -> Self {
+        Self::Approximate {
+            chars_per_token: default_chars_per_token(),
+        }
+    }
+}
+
+/// What a `token_firewall` does with a field when a record is over budget.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenOverflow {
+    /// Shorten string fields to fit; fields that cannot be shortened are dropped.
+    #[default]
+    Truncate,
+    /// Remove whole fields.
+    DropFields,
+}
+
+fn default_chars_per_token() -> f64 {
+    4.0
+}
+
+fn default_language_field() -> String {
+    "_lang".to_string()
+}
+
+fn default_language_confidence_field() -> String {
+    "_lang_confidence".to_string()
+}
+
+fn default_label_field() -> String {
+    "_label".to_string()
+}
+
+fn default_label_score_field() -> String {
+    "_label_score".to_string()
+}
+
+fn default_entropy_threshold() -> f64 {
+    4.0
+}
+
+#[cfg(feature = "semantic")]
+fn default_embedding_batch_size() -> usize {
+    32
+}
+
+#[cfg(feature = "semantic")]
+fn default_embedding_suffix() -> String {
+    "_embedding".to_string()
+}
+
+#[cfg(feature = "semantic")]
+fn default_score_field() -> String {
+    "_relevance".to_string()
 }
 
 #[cfg(feature = "semantic")]
-fn default_threshold() -> f32 {
-    0.85
+fn default_sample_values() -> usize {
+    3
+}
+
+#[cfg(feature = "semantic")]
+fn default_lexical_weight() -> f32 {
+    0.3
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -69,6 +821,8 @@ pub enum SinkConfig {
     },
     Csv {
         path: PathBuf,
+        #[serde(flatten)]
+        options: CsvOptions,
     },
     Avro {
         path: PathBuf,
@@ -76,5 +830,43 @@ pub enum SinkConfig {
     #[cfg(feature = "cloud")]
     Cloud {
         url: String,
+        /// Only used when this sink is the dead letter queue.
+        #[serde(flatten, default)]
+        rotation: DlqRotation,
     },
 }
+
+/// Part-file rotation for object-store dead letter queues.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DlqRotation {
+    #[serde(default = "default_dlq_part_bytes")]
+    pub max_part_bytes: usize,
+    #[serde(default = "default_dlq_part_age_secs")]
+    pub max_part_age_secs: u64,
+    /// Bytes of sealed parts kept for retry while uploads fail; the oldest part is
+    /// dropped past this.
+    #[serde(default = "default_dlq_pending_bytes")]
+    pub max_pending_bytes: usize,
+}
+
+impl Default for DlqRotation {
+    fn default() -> Self {
+        Self {
+            max_part_bytes: default_dlq_part_bytes(),
+            max_part_age_secs: default_dlq_part_age_secs(),
+            max_pending_bytes: default_dlq_pending_bytes(),
+        }
+    }
+}
+
+fn default_dlq_part_bytes() -> usize {
+    8 * 1024 * 1024
+}
+
+fn default_dlq_part_age_secs() -> u64 {
+    60
+}
+
+fn default_dlq_pending_bytes() -> usize {
+    64 * 1024 * 1024
+}
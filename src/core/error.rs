@@ -20,6 +20,17 @@ pub enum UdoError {
     #[error("Pipeline Error: {0}")]
     Pipeline(String),
 
+    /// Input that the source could not decode; `raw` holds the original bytes.
+    #[error("Source Decode Error at position {position:?}: {reason}")]
+    SourceDecode {
+        position: Option<u64>,
+        raw: Vec<u8>,
+        reason: String,
+    },
+
+    #[error("Schema Mismatch: {0}")]
+    SchemaMismatch(String),
+
     #[cfg(feature = "kafka")]
     #[error("Kafka Error: {0}")]
     Kafka(#[from] rdkafka::error::KafkaError),
@@ -35,8 +46,37 @@ pub enum UdoError {
     #[error("AI Model Error: {0}")]
     AiModel(String),
 
+    /// A processor panicked while handling the record.
+    #[error("Processor Panic: {0}")]
+    Panic(String),
+
     #[error("Unknown Error: {0}")]
     Unknown(String),
 }
 
+impl UdoError {
+    /// Stable, machine-readable name of the error variant (used in DLQ envelopes).
+    pub fn kind(&self) -> &'static str {
+        match self {
+            UdoError::Io(_) => "io",
+            UdoError::Arrow(_) => "arrow",
+            UdoError::Parquet(_) => "parquet",
+            UdoError::JsonParse(_) => "json_parse",
+            UdoError::Config(_) => "config",
+            UdoError::Pipeline(_) => "pipeline",
+            UdoError::SourceDecode { .. } => "source_decode",
+            UdoError::SchemaMismatch(_) => "schema_mismatch",
+            #[cfg(feature = "kafka")]
+            UdoError::Kafka(_) => "kafka",
+            #[cfg(feature = "cloud")]
+            UdoError::ObjectStore(_) => "object_store",
+            #[cfg(feature = "cloud")]
+            UdoError::UrlParse(_) => "url_parse",
+            UdoError::AiModel(_) => "ai_model",
+            UdoError::Panic(_) => "panic",
+            UdoError::Unknown(_) => "unknown",
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, UdoError>;
@@ -0,0 +1,157 @@
+use crate::core::error::{Result, UdoError};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot, Semaphore};
+use tracing::debug;
+
+/// How long a request waits for concurrent ones when nothing else is configured.
+pub const DEFAULT_BATCH_WAIT_MS: u64 = 5;
+
+/// Runs model inference on tokio's blocking threads, at most `threads` at a time, so
+/// CPU-bound forward passes never occupy the async workers that drive I/O.
+pub struct InferencePool {
+    permits: Arc<Semaphore>,
+    threads: usize,
+}
+
+impl InferencePool {
+    pub fn new(threads: usize) -> Self {
+        let threads = threads.max(1);
+        Self {
+            permits: Arc::new(Semaphore::new(threads)),
+            threads,
+        }
+    }
+
+    /// Maximum number of inference calls running at once.
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Runs `f` on a blocking thread once one of the pool's slots is free.
+    pub async fn run<R, F>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce() -> Result<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        let _permit = self
+            .permits
+            .acquire()
+            .await
+            .map_err(|e| UdoError::AiModel(e.to_string()))?;
+        tokio::task::spawn_blocking(f)
+            .await
+            .map_err(|e| UdoError::AiModel(format!("Inference task failed: {}", e)))?
+    }
+}
+
+impl Default for InferencePool {
+    fn default() -> Self {
+        Self::new(num_cpus::get())
+    }
+}
+
+type BatchFn<I, O> = Arc<dyn Fn(Vec<I>) -> Result<Vec<O>> + Send + Sync>;
+
+struct Request<I, O> {
+    inputs: Vec<I>,
+    reply: oneshot::Sender<Result<Vec<O>>>,
+}
+
+/// Merges concurrent requests into one model call. The first request of a batch waits
+/// up to `max_wait` for others until `max_batch` inputs are queued; the batch then runs
+/// on the [`InferencePool`] and every caller gets its own slice of the outputs.
+pub struct MicroBatcher<I, O> {
+    requests: mpsc::UnboundedSender<Request<I, O>>,
+}
+
+impl<I: Send + 'static, O: Send + 'static> MicroBatcher<I, O> {
+    /// Starts the batching task; must be called from within a tokio runtime.
+    pub fn new(
+        pool: Arc<InferencePool>,
+        max_batch: usize,
+        max_wait: Duration,
+        run: impl Fn(Vec<I>) -> Result<Vec<O>> + Send + Sync + 'static,
+    ) -> Self {
+        let (requests, mut queue) = mpsc::unbounded_channel::<Request<I, O>>();
+        let run: BatchFn<I, O> = Arc::new(run);
+        tokio::spawn(async move {
+            while let Some(first) = queue.recv().await {
+                let mut size = first.inputs.len();
+                let mut batch = vec![first];
+                let deadline = tokio::time::Instant::now() + max_wait;
+                while size < max_batch {
+                    match tokio::time::timeout_at(deadline, queue.recv()).await {
+                        Ok(Some(request)) => {
+                            size += request.inputs.len();
+                            batch.push(request);
+                        }
+                        _ => break,
+                    }
+                }
+                tokio::spawn(dispatch(pool.clone(), run.clone(), batch));
+            }
+        });
+        Self { requests }
+    }
+
+    /// Outputs for `inputs`, in order, computed together with concurrent requests.
+    pub async fn call(&self, inputs: Vec<I>) -> Result<Vec<O>> {
+        if inputs.is_empty() {
+            return Ok(Vec::new());
+        }
+        let (reply, response) = oneshot::channel();
+        self.requests
+            .send(Request { inputs, reply })
+            .map_err(|_| UdoError::AiModel("Micro-batcher stopped".to_string()))?;
+        response
+            .await
+            .map_err(|_| UdoError::AiModel("Micro-batcher dropped a request".to_string()))?
+    }
+}
+
+async fn dispatch<I: Send + 'static, O: Send + 'static>(
+    pool: Arc<InferencePool>,
+    run: BatchFn<I, O>,
+    batch: Vec<Request<I, O>>,
+) {
+    let mut sizes = Vec::with_capacity(batch.len());
+    let mut replies = Vec::with_capacity(batch.len());
+    let mut inputs = Vec::new();
+    for request in batch {
+        sizes.push(request.inputs.len());
+        inputs.extend(request.inputs);
+        replies.push(request.reply);
+    }
+    let total = inputs.len();
+    debug!(requests = %replies.len(), inputs = %total, "Running micro-batch");
+
+    match pool.run(move || run(inputs)).await {
+        Ok(outputs) if outputs.len() == total => {
+            let mut outputs = outputs.into_iter();
+            for (reply, size) in replies.into_iter().zip(sizes) {
+                let _ = reply.send(Ok(outputs.by_ref().take(size).collect()));
+            }
+        }
+        Ok(outputs) => {
+            let message = format!(
+                "Model returned {} outputs for {} inputs",
+                outputs.len(),
+                total
+            );
+            for reply in replies {
+                let _ = reply.send(Err(UdoError::AiModel(message.clone())));
+            }
+        }
+        // Errors are not `Clone`; every caller gets the message.
+        Err(e) => {
+            let message = match e {
+                UdoError::AiModel(message) => message,
+                other => other.to_string(),
+            };
+            for reply in replies {
+                let _ = reply.send(Err(UdoError::AiModel(message.clone())));
+            }
+        }
+    }
+}
@@ -1,5 +1,9 @@
+#[cfg(any(feature = "semantic", feature = "ner"))]
+pub mod bert;
 pub mod config;
 pub mod error;
+#[cfg(any(feature = "semantic", feature = "ner"))]
+pub mod inference;
 pub mod model;
 pub mod pipeline;
 pub mod schema;
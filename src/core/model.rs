@@ -1,73 +1,586 @@
 #[cfg(any(feature = "semantic", feature = "ner"))]
+use crate::core::bert::Bert;
+#[cfg(any(feature = "semantic", feature = "ner"))]
+use crate::core::config::{ModelsConfig, Precision};
+#[cfg(any(feature = "semantic", feature = "ner"))]
 use crate::core::error::{Result, UdoError};
 #[cfg(any(feature = "semantic", feature = "ner"))]
+use crate::core::inference::{InferencePool, DEFAULT_BATCH_WAIT_MS};
+#[cfg(any(feature = "semantic", feature = "ner"))]
 use anyhow::Error;
 #[cfg(any(feature = "semantic", feature = "ner"))]
-use candle_core::{DType, Device};
+use candle_core::{Device, Tensor};
+#[cfg(any(feature = "semantic", feature = "ner"))]
+use candle_transformers::models::bert::Config;
+#[cfg(any(feature = "semantic", feature = "ner"))]
+use hf_hub::{api::sync::ApiBuilder, Cache, Repo, RepoType};
 #[cfg(any(feature = "semantic", feature = "ner"))]
-use candle_nn::VarBuilder;
+use serde::Serialize;
 #[cfg(any(feature = "semantic", feature = "ner"))]
-use candle_transformers::models::bert::{BertModel, Config};
+use std::any::{Any, TypeId};
 #[cfg(any(feature = "semantic", feature = "ner"))]
-use hf_hub::{api::sync::ApiBuilder, Repo, RepoType};
+use std::collections::HashMap;
 #[cfg(any(feature = "semantic", feature = "ner"))]
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+#[cfg(any(feature = "semantic", feature = "ner"))]
+use std::sync::{Arc, Mutex};
+#[cfg(any(feature = "semantic", feature = "ner"))]
+use std::time::{Duration, Instant};
 #[cfg(any(feature = "semantic", feature = "ner"))]
 use tokenizers::Tokenizer;
+#[cfg(any(feature = "semantic", feature = "ner"))]
+use tracing::{debug, info};
 
 #[cfg(any(feature = "semantic", feature = "ner"))]
 pub struct BertModelContainer {
-    pub model: BertModel,
+    pub model: Bert,
     pub tokenizer: Tokenizer,
     pub device: Device,
+    /// The safetensors file the weights were loaded from.
+    pub weights: PathBuf,
+    /// Width of the hidden states, i.e. the embedding dimension.
+    pub hidden_size: usize,
+    pub report: LoadReport,
 }
 
 #[cfg(any(feature = "semantic", feature = "ner"))]
 impl BertModelContainer {
     pub fn load(model_id: &str, model_path: Option<PathBuf>) -> Result<Self> {
+        Self::from_spec(&ModelSpec::new(model_id).with_path(model_path))
+    }
+
+    pub fn from_spec(spec: &ModelSpec) -> Result<Self> {
         let device = Device::Cpu;
+        let files = ModelFiles::fetch(spec)?;
+        let loading = LoadReport::start(spec.precision());
 
-        let (config_filename, tokenizer_filename, weights_filename) = if let Some(path) = model_path
-        {
-            (
-                path.join("config.json"),
-                path.join("tokenizer.json"),
-                path.join("model.safetensors"),
-            )
+        let config_json: serde_json::Value = serde_json::from_str(&files.read_config()?)
+            .map_err(|e: serde_json::Error| UdoError::Config(e.to_string()))?;
+        let hidden_size = config_json["hidden_size"]
+            .as_u64()
+            .ok_or_else(|| UdoError::Config("Model config has no hidden_size".to_string()))?
+            as usize;
+        let config: Config = serde_json::from_value(config_json)
+            .map_err(|e: serde_json::Error| UdoError::Config(e.to_string()))?;
+        let tokenizer = files.load_tokenizer()?;
+
+        let (model, weights_bytes) =
+            Bert::load(&files.weights, &config, spec.precision(), &device)?;
+        let report = loading.finish(weights_bytes, || {
+            let (ids, mask) = sample_batch(&tokenizer, &device)?;
+            model.forward(&ids, &ids.zeros_like()?, Some(&mask))?;
+            Ok(())
+        })?;
+        report.log(spec, "Model load cost");
+
+        Ok(Self {
+            model,
+            tokenizer,
+            device,
+            weights: files.weights,
+            hidden_size,
+            report,
+        })
+    }
+
+    /// Hex SHA-256 of the weights, identifying exactly which model produced an output.
+    /// Weights converted while loading get the precision appended, as their outputs differ.
+    pub fn fingerprint(&self) -> Result<String> {
+        let sha256 = sha256_file(&self.weights)?;
+        Ok(match self.report.precision {
+            Precision::F32 | Precision::Gguf => sha256,
+            precision => format!("{}-{}", sha256, precision),
+        })
+    }
+}
+
+/// Text of the forward pass timed after a model loads.
+#[cfg(any(feature = "semantic", feature = "ner"))]
+const SAMPLE_TEXT: &str = "Jane Doe from Acme Corp emailed the Berlin office about invoice 4711 \
+    on Monday, asking for the quarterly revenue report before the board meeting.";
+
+/// Token IDs and attention mask of [`SAMPLE_TEXT`] as a batch of one.
+#[cfg(any(feature = "semantic", feature = "ner"))]
+pub fn sample_batch(
+    tokenizer: &Tokenizer,
+    device: &Device,
+) -> candle_core::Result<(Tensor, Tensor)> {
+    let encoding = tokenizer
+        .encode(SAMPLE_TEXT, true)
+        .map_err(|e| candle_core::Error::Msg(e.to_string()))?;
+    let ids = Tensor::new(encoding.get_ids(), device)?.unsqueeze(0)?;
+    let mask = Tensor::new(encoding.get_attention_mask(), device)?.unsqueeze(0)?;
+    Ok((ids, mask))
+}
+
+/// What loading a model cost: logged at load time so precisions can be compared on
+/// the machine that will run them.
+#[cfg(any(feature = "semantic", feature = "ner"))]
+#[derive(Debug, Clone, Serialize)]
+pub struct LoadReport {
+    pub precision: Precision,
+    /// Bytes of the weights the model holds in memory.
+    pub weights_bytes: u64,
+    /// Growth of the process's resident memory while loading, where the OS reports it.
+    pub resident_bytes: Option<u64>,
+    pub load_ms: f64,
+    /// One forward pass over a sample sentence right after loading.
+    pub forward_ms: f64,
+}
+
+/// A load in progress; see [`LoadReport::start`].
+#[cfg(any(feature = "semantic", feature = "ner"))]
+pub struct Loading {
+    precision: Precision,
+    started: Instant,
+    resident: Option<u64>,
+}
+
+#[cfg(any(feature = "semantic", feature = "ner"))]
+impl LoadReport {
+    /// Call before reading any weights.
+    pub fn start(precision: Precision) -> Loading {
+        Loading {
+            precision,
+            started: Instant::now(),
+            resident: resident_bytes(),
+        }
+    }
+
+    /// Logs the report at info level.
+    pub fn log(&self, spec: &ModelSpec, message: &str) {
+        const MB: f64 = 1024.0 * 1024.0;
+        info!(
+            model = %spec,
+            precision = %self.precision,
+            weights_mb = format!("{:.1}", self.weights_bytes as f64 / MB),
+            resident_mb = self
+                .resident_bytes
+                .map(|b| format!("{:.1}", b as f64 / MB))
+                .unwrap_or_else(|| "unknown".to_string()),
+            load_ms = format!("{:.0}", self.load_ms),
+            forward_ms = format!("{:.1}", self.forward_ms),
+            "{}",
+            message
+        );
+    }
+}
+
+#[cfg(any(feature = "semantic", feature = "ner"))]
+impl Loading {
+    /// Ends the load and times `forward`, a sample forward pass of the loaded model.
+    pub fn finish(
+        self,
+        weights_bytes: u64,
+        forward: impl FnOnce() -> candle_core::Result<()>,
+    ) -> Result<LoadReport> {
+        let load_ms = self.started.elapsed().as_secs_f64() * 1000.0;
+        let resident = match (self.resident, resident_bytes()) {
+            (Some(before), Some(after)) => Some(after.saturating_sub(before)),
+            _ => None,
+        };
+        let started = Instant::now();
+        forward().map_err(|e| UdoError::AiModel(e.to_string()))?;
+        Ok(LoadReport {
+            precision: self.precision,
+            weights_bytes,
+            resident_bytes: resident,
+            load_ms,
+            forward_ms: started.elapsed().as_secs_f64() * 1000.0,
+        })
+    }
+}
+
+/// Resident memory of this process, from `/proc` on Linux.
+#[cfg(any(feature = "semantic", feature = "ner"))]
+fn resident_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+/// Hex SHA-256 of a file, read in chunks so large weights are not loaded at once.
+#[cfg(any(feature = "semantic", feature = "ner"))]
+pub fn sha256_file(path: &std::path::Path) -> Result<String> {
+    use sha2::{Digest, Sha256};
+    use std::io::Read;
+
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1 << 20];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Where a model comes from and how it is checked before it is loaded.
+#[cfg(any(feature = "semantic", feature = "ner"))]
+#[derive(Debug, Clone, Default)]
+pub struct ModelSpec {
+    /// Hugging Face hub model ID.
+    pub id: String,
+    /// Branch, tag or commit to download; the hub's default branch when unset.
+    pub revision: Option<String>,
+    /// Local directory with the model files; the hub is not used.
+    pub path: Option<PathBuf>,
+    /// Expected hex SHA-256 of the weights file.
+    pub sha256: Option<String>,
+    /// Hub download cache, instead of the Hugging Face default.
+    pub cache_dir: Option<PathBuf>,
+    /// Only use files already in the cache.
+    pub offline: bool,
+    /// Format the weights are kept in. When unset, [`ModelSpec::with_settings`] takes
+    /// it from the model's pin or `models.precision`; `f32` without settings.
+    pub precision: Option<Precision>,
+}
+
+#[cfg(any(feature = "semantic", feature = "ner"))]
+impl ModelSpec {
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_revision(mut self, revision: Option<String>) -> Self {
+        self.revision = revision;
+        self
+    }
+
+    pub fn with_path(mut self, path: Option<PathBuf>) -> Self {
+        self.path = path;
+        self
+    }
+
+    pub fn with_precision(mut self, precision: Precision) -> Self {
+        self.precision = Some(precision);
+        self
+    }
+
+    pub fn precision(&self) -> Precision {
+        self.precision.unwrap_or_default()
+    }
+
+    /// Applies a pipeline's `models` settings: the cache, offline mode, precision and
+    /// this model's pin. A pinned revision must agree with one set on the processor; a
+    /// precision set on the spec wins over the pin's, which wins over the pipeline's.
+    pub fn with_settings(mut self, settings: &ModelsConfig) -> Result<Self> {
+        self.cache_dir = self.cache_dir.or_else(|| settings.cache_dir.clone());
+        self.offline |= settings.offline;
+        if let Some(pin) = settings.pins.get(&self.id) {
+            match (&self.revision, &pin.revision) {
+                (Some(own), Some(pinned)) if own != pinned => {
+                    return Err(UdoError::Config(format!(
+                        "Model {} is configured at revision {} but pinned to {}",
+                        self.id, own, pinned
+                    )));
+                }
+                (None, Some(pinned)) => self.revision = Some(pinned.clone()),
+                _ => {}
+            }
+            self.sha256 = pin.sha256.clone().or(self.sha256);
+        }
+        self.precision = self
+            .precision
+            .or_else(|| settings.pins.get(&self.id).and_then(|pin| pin.precision))
+            .or(Some(settings.precision));
+        Ok(self)
+    }
+
+    fn repo(&self) -> Repo {
+        match &self.revision {
+            Some(revision) => {
+                Repo::with_revision(self.id.clone(), RepoType::Model, revision.clone())
+            }
+            None => Repo::new(self.id.clone(), RepoType::Model),
+        }
+    }
+}
+
+#[cfg(any(feature = "semantic", feature = "ner"))]
+impl std::fmt::Display for ModelSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.path, &self.revision) {
+            (Some(path), _) => write!(f, "{} ({})", self.id, path.display())?,
+            (None, Some(revision)) => write!(f, "{}@{}", self.id, revision)?,
+            (None, None) => write!(f, "{}", self.id)?,
+        }
+        if self.precision() != Precision::F32 {
+            write!(f, " [{}]", self.precision())?;
+        }
+        Ok(())
+    }
+}
+
+/// The files of a Hugging Face model: from a local directory when `model_path` is
+/// given, otherwise downloaded from the hub at `revision` (a branch, tag or commit).
+#[cfg(any(feature = "semantic", feature = "ner"))]
+pub struct ModelFiles {
+    pub config: PathBuf,
+    pub tokenizer: PathBuf,
+    pub weights: PathBuf,
+}
+
+#[cfg(any(feature = "semantic", feature = "ner"))]
+impl ModelFiles {
+    pub fn resolve(
+        model_id: &str,
+        revision: Option<&str>,
+        model_path: Option<PathBuf>,
+    ) -> Result<Self> {
+        Self::fetch(
+            &ModelSpec::new(model_id)
+                .with_revision(revision.map(str::to_string))
+                .with_path(model_path),
+        )
+    }
+
+    /// Locates the files of `spec`, downloading them unless it is offline or local,
+    /// and checks the weights against `spec.sha256`.
+    pub fn fetch(spec: &ModelSpec) -> Result<Self> {
+        let files = if let Some(path) = &spec.path {
+            Self::in_dir(path, spec.precision())
+        } else if spec.offline {
+            let cache = cache_dir(spec.cache_dir.as_deref());
+            let repo = spec.repo();
+            let folder = cache.join(repo.folder_name());
+            // A branch or tag resolves through `refs`; a commit names its snapshot directly.
+            let revision = repo.revision();
+            let commit = std::fs::read_to_string(folder.join("refs").join(revision))
+                .map(|c| c.trim().to_string())
+                .unwrap_or_else(|_| revision.to_string());
+            let snapshot = folder.join("snapshots").join(commit);
+            let files = Self::in_dir(&snapshot, spec.precision());
+            for file in [&files.config, &files.tokenizer, &files.weights] {
+                if !file.exists() {
+                    return Err(UdoError::AiModel(format!(
+                        "Model {} is not in the cache at {} (missing {}); run `udo-cli models pull` first",
+                        spec,
+                        cache.display(),
+                        file.display()
+                    )));
+                }
+            }
+            files
         } else {
-            let api = ApiBuilder::new()
+            let mut builder = ApiBuilder::new();
+            if let Some(dir) = &spec.cache_dir {
+                builder = builder.with_cache_dir(dir.clone());
+            }
+            let api = builder
                 .build()
                 .map_err(|e: hf_hub::api::sync::ApiError| UdoError::AiModel(e.to_string()))?;
-            let repo = api.repo(Repo::new(model_id.to_string(), RepoType::Model));
-
-            (
-                repo.get("config.json")
-                    .map_err(|e: hf_hub::api::sync::ApiError| UdoError::AiModel(e.to_string()))?,
-                repo.get("tokenizer.json")
-                    .map_err(|e: hf_hub::api::sync::ApiError| UdoError::AiModel(e.to_string()))?,
-                repo.get("model.safetensors")
-                    .map_err(|e: hf_hub::api::sync::ApiError| UdoError::AiModel(e.to_string()))?,
-            )
+            let repo = api.repo(spec.repo());
+            let get = |file: &str| {
+                repo.get(file)
+                    .map_err(|e: hf_hub::api::sync::ApiError| UdoError::AiModel(e.to_string()))
+            };
+            Self {
+                config: get("config.json")?,
+                tokenizer: get("tokenizer.json")?,
+                weights: get(spec.precision().weights_file())?,
+            }
         };
 
-        let config: Config =
-            serde_json::from_str(&std::fs::read_to_string(config_filename).map_err(UdoError::Io)?)
-                .map_err(|e: serde_json::Error| UdoError::Config(e.to_string()))?;
-        let tokenizer = Tokenizer::from_file(tokenizer_filename)
+        if let Some(expected) = &spec.sha256 {
+            files.verify(expected)?;
+        }
+        Ok(files)
+    }
+
+    fn in_dir(path: &std::path::Path, precision: Precision) -> Self {
+        Self {
+            config: path.join("config.json"),
+            tokenizer: path.join("tokenizer.json"),
+            weights: path.join(precision.weights_file()),
+        }
+    }
+
+    /// Checks the weights against an expected hex SHA-256 and returns the actual one.
+    pub fn verify(&self, expected: &str) -> Result<String> {
+        let actual = sha256_file(&self.weights)?;
+        if !actual.eq_ignore_ascii_case(expected.trim()) {
+            return Err(UdoError::AiModel(format!(
+                "Checksum mismatch for {}: expected {}, got {}",
+                self.weights.display(),
+                expected.trim(),
+                actual
+            )));
+        }
+        Ok(actual)
+    }
+
+    /// Commit hash of the hub snapshot the files come from, when they are cached ones.
+    pub fn snapshot(&self) -> Option<String> {
+        let dir = self.weights.parent()?;
+        let parent = dir.parent()?;
+        (parent.file_name()? == "snapshots")
+            .then(|| dir.file_name()?.to_str().map(str::to_string))
+            .flatten()
+    }
+
+    pub fn read_config(&self) -> Result<String> {
+        std::fs::read_to_string(&self.config).map_err(UdoError::Io)
+    }
+
+    pub fn load_tokenizer(&self) -> Result<Tokenizer> {
+        Tokenizer::from_file(&self.tokenizer)
             .map_err(Error::msg)
-            .map_err(|e: anyhow::Error| UdoError::AiModel(e.to_string()))?;
+            .map_err(|e: anyhow::Error| UdoError::AiModel(e.to_string()))
+    }
+}
+
+/// Loaded models shared by every processor of a pipeline that asks for the same one,
+/// so its weights are in memory once, and the threads their forward passes run on.
+#[cfg(any(feature = "semantic", feature = "ner"))]
+pub struct ModelPool {
+    settings: ModelsConfig,
+    models: Mutex<HashMap<(TypeId, String), Arc<dyn Any + Send + Sync>>>,
+    inference: Arc<InferencePool>,
+}
 
-        let vb = unsafe {
-            VarBuilder::from_mmaped_safetensors(&[weights_filename], DType::F32, &device)
-                .map_err(|e: candle_core::Error| UdoError::AiModel(e.to_string()))?
+#[cfg(any(feature = "semantic", feature = "ner"))]
+impl ModelPool {
+    pub fn new(settings: &ModelsConfig) -> Self {
+        let inference = match settings.threads {
+            Some(threads) => InferencePool::new(threads),
+            None => InferencePool::default(),
         };
-        let model = BertModel::load(vb, &config)
-            .map_err(|e: candle_core::Error| UdoError::AiModel(e.to_string()))?;
+        Self {
+            settings: settings.clone(),
+            models: Mutex::new(HashMap::new()),
+            inference: Arc::new(inference),
+        }
+    }
 
-        Ok(Self {
-            model,
-            tokenizer,
-            device,
-        })
+    /// The settings models of this pool are fetched with.
+    pub fn settings(&self) -> &ModelsConfig {
+        &self.settings
+    }
+
+    pub fn inference(&self) -> &Arc<InferencePool> {
+        &self.inference
+    }
+
+    /// How long a request waits for others to share its forward pass.
+    pub fn batch_wait(&self) -> Duration {
+        Duration::from_millis(self.settings.batch_wait_ms.unwrap_or(DEFAULT_BATCH_WAIT_MS))
+    }
+
+    /// The `T` loaded for `spec`, loading it on first use. Loads are serialized so
+    /// processors asking for the same model at once do not both load it.
+    pub fn get_or_load<T: Any + Send + Sync>(
+        &self,
+        spec: &ModelSpec,
+        load: impl FnOnce() -> Result<T>,
+    ) -> Result<Arc<T>> {
+        let key = (TypeId::of::<T>(), spec.to_string());
+        let mut models = self
+            .models
+            .lock()
+            .map_err(|_| UdoError::AiModel("Model pool lock poisoned".to_string()))?;
+        if let Some(model) = models.get(&key)
+            && let Ok(model) = model.clone().downcast::<T>()
+        {
+            debug!(model = %spec, "Sharing loaded model");
+            return Ok(model);
+        }
+        let model = Arc::new(load()?);
+        models.insert(key, model.clone());
+        Ok(model)
+    }
+
+    /// Number of distinct models loaded so far.
+    pub fn len(&self) -> usize {
+        self.models.lock().map(|m| m.len()).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The hub download cache: `configured`, or the Hugging Face default (`HF_HOME`).
+#[cfg(any(feature = "semantic", feature = "ner"))]
+pub fn cache_dir(configured: Option<&Path>) -> PathBuf {
+    match configured {
+        Some(dir) => dir.to_path_buf(),
+        None => Cache::from_env().path().clone(),
+    }
+}
+
+/// One downloaded snapshot of a model in the hub cache.
+#[cfg(any(feature = "semantic", feature = "ner"))]
+#[derive(Debug, Clone, Serialize)]
+pub struct CachedModel {
+    pub id: String,
+    pub commit: String,
+    /// Branches and tags that point at this commit.
+    pub refs: Vec<String>,
+    pub files: Vec<String>,
+    /// Total size of the snapshot's files in bytes.
+    pub size: u64,
+}
+
+/// Every model snapshot in the hub cache at `dir`, sorted by ID and commit.
+#[cfg(any(feature = "semantic", feature = "ner"))]
+pub fn cached_models(dir: &Path) -> Result<Vec<CachedModel>> {
+    let read_dir = |path: &Path| -> Result<Vec<std::fs::DirEntry>> {
+        match std::fs::read_dir(path) {
+            Ok(entries) => Ok(entries.collect::<std::io::Result<_>>()?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e.into()),
+        }
+    };
+
+    let mut models = Vec::new();
+    for repo in read_dir(dir)? {
+        let name = repo.file_name().to_string_lossy().to_string();
+        let Some(id) = name.strip_prefix("models--") else {
+            continue;
+        };
+        let id = id.replace("--", "/");
+
+        let mut refs: HashMap<String, Vec<String>> = HashMap::new();
+        for r in read_dir(&repo.path().join("refs"))? {
+            if r.path().is_file() {
+                let commit = std::fs::read_to_string(r.path())?.trim().to_string();
+                refs.entry(commit)
+                    .or_default()
+                    .push(r.file_name().to_string_lossy().to_string());
+            }
+        }
+
+        for snapshot in read_dir(&repo.path().join("snapshots"))? {
+            let commit = snapshot.file_name().to_string_lossy().to_string();
+            let mut files = Vec::new();
+            let mut size = 0;
+            for file in read_dir(&snapshot.path())? {
+                // Snapshot entries are symlinks into `blobs`; follow them for the size.
+                if let Ok(meta) = std::fs::metadata(file.path()) {
+                    size += meta.len();
+                }
+                files.push(file.file_name().to_string_lossy().to_string());
+            }
+            files.sort();
+            let mut refs = refs.get(&commit).cloned().unwrap_or_default();
+            refs.sort();
+            models.push(CachedModel {
+                id: id.clone(),
+                commit,
+                refs,
+                files,
+                size,
+            });
+        }
     }
+    models.sort_by(|a, b| (&a.id, &a.commit).cmp(&(&b.id, &b.commit)));
+    Ok(models)
 }
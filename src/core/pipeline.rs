@@ -1,17 +1,33 @@
+use crate::core::config::RetryPolicy;
 use crate::core::error::{Result, UdoError};
 use crate::core::schema::infer_schema;
-use crate::utils::json::json_rows_to_batch;
+use crate::utils::json::{check_row, json_rows_to_batch};
 use arrow::datatypes::Schema;
 use arrow::record_batch::RecordBatch;
 use async_trait::async_trait;
-use futures::StreamExt;
+use futures::{FutureExt, StreamExt};
+use serde::{Deserialize, Serialize};
 use simd_json::OwnedValue;
+use std::any::Any;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
 use std::sync::Arc;
-use tracing::{debug, error, info};
+use std::time::Duration;
+use tokio::time::{Interval, MissedTickBehavior};
+use tracing::{debug, error, info, warn};
+
+/// How often the runner lets the DLQ push letters that are due while it waits.
+const DLQ_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Batches going through the stages at once.
+const BATCHES_IN_FLIGHT: usize = 4;
 
 #[async_trait]
 pub trait InputSource: Send + Sync {
     async fn next_record(&mut self) -> Result<Option<OwnedValue>>;
+    /// Position of the record last returned by `next_record` (line number, offset, ...).
+    fn position(&self) -> Option<u64> {
+        None
+    }
 }
 
 #[async_trait]
@@ -20,6 +36,30 @@ pub trait DataProcessor: Send + Sync {
     fn update_schema(&self, _schema: &Arc<Schema>) -> Result<Arc<Schema>> {
         Ok(_schema.clone())
     }
+    /// Called with the warm-up records, as read from the source, before `update_schema`;
+    /// also when the schema was supplied to `run`.
+    fn observe_samples(&self, _records: &[OwnedValue]) {}
+    /// Called with each batch of rows once they have all passed this processor's
+    /// `process`, before the next processor sees them, so a processor can work across
+    /// rows of the same batch, e.g. keep the most relevant ones or run one batched
+    /// inference. Returns one slot per row, in order; `None` drops the row. On error
+    /// every row of the batch is dead-lettered.
+    async fn process_batch(&self, rows: Vec<OwnedValue>) -> Result<Vec<Option<OwnedValue>>> {
+        Ok(rows.into_iter().map(Some).collect())
+    }
+    /// Short processor name reported in DLQ envelopes.
+    fn name(&self) -> &str {
+        let full = std::any::type_name::<Self>();
+        full.rsplit("::").next().unwrap_or(full)
+    }
+    /// Counters reported once the run completes, e.g. findings per detection rule.
+    fn metrics(&self) -> Vec<(String, u64)> {
+        Vec::new()
+    }
+    /// Structured summary collected once the run completes, e.g. a PII detection report.
+    fn report(&self) -> Option<serde_json::Value> {
+        None
+    }
 }
 
 #[async_trait]
@@ -28,458 +68,707 @@ pub trait OutputSink: Send + Sync {
     async fn close(&mut self) -> Result<()>;
 }
 
+/// Envelope written to the dead letter queue for every record that failed processing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub timestamp: String,
+    #[serde(default)]
+    pub pipeline: Option<String>,
+    /// Index of the failing processor in the pipeline.
+    #[serde(default)]
+    pub stage: Option<usize>,
+    #[serde(default)]
+    pub processor: Option<String>,
+    pub error_kind: String,
+    pub error: String,
+    /// Ordinal of the record in the source stream.
+    #[serde(default)]
+    pub record_index: Option<u64>,
+    /// Source-specific position, e.g. line number or Kafka offset.
+    #[serde(default)]
+    pub source_position: Option<u64>,
+    #[serde(default = "default_attempts")]
+    pub attempts: u32,
+    pub record: OwnedValue,
+}
+
+fn default_attempts() -> u32 {
+    1
+}
+
+impl DeadLetter {
+    pub fn new(record: OwnedValue, error: &UdoError) -> Self {
+        Self {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            pipeline: None,
+            stage: None,
+            processor: None,
+            error_kind: error.kind().to_string(),
+            error: error.to_string(),
+            record_index: None,
+            source_position: None,
+            attempts: 1,
+            record,
+        }
+    }
+}
+
 #[async_trait]
 pub trait DlqSink: Send + Sync {
-    async fn write_dead_letter(&mut self, record: OwnedValue, reason: String) -> Result<()>;
+    async fn write_dead_letter(&mut self, letter: DeadLetter) -> Result<()>;
+    /// Pushes any buffered dead letters to the underlying storage.
+    async fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+    /// Called periodically by the runner, so buffered dead letters can be pushed on a
+    /// timer even while no new ones arrive.
+    async fn flush_due(&mut self) -> Result<()> {
+        Ok(())
+    }
+    /// Called once by the runner at shutdown; must not lose buffered records.
+    async fn close(&mut self) -> Result<()> {
+        self.flush().await
+    }
 }
 
-pub type SinkFactory =
+/// A processor's end-of-run report, tagged with where it ran in the pipeline.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessorReport {
+    pub processor: String,
+    pub stage: usize,
+    pub report: serde_json::Value,
+}
 
+pub type SinkFactory =
     Box<dyn Fn(Arc<Schema>) -> Result<Box<dyn OutputSink>> + Send + Sync + 'static>;
 
-
-
 pub struct PipelineRunner {
-
     source: Box<dyn InputSource>,
-
-    processors: Vec<Box<dyn DataProcessor>>,
-
+    processors: Vec<Stage>,
     sink_factory: Option<SinkFactory>,
-
     sink: Option<Box<dyn OutputSink>>,
-
     dlq: Option<Box<dyn DlqSink>>,
-
     batch_size: usize,
-
     warmup_rows: usize,
-
+    name: Option<String>,
+    budget: ErrorBudget,
+    processor_metrics: Vec<(String, u64)>,
+    processor_reports: Vec<ProcessorReport>,
 }
 
-
-
 impl PipelineRunner {
-
     pub fn new(source: Box<dyn InputSource>, batch_size: usize) -> Self {
-
         Self {
-
             source,
-
             processors: Vec::new(),
-
             sink_factory: None,
-
             sink: None,
-
             dlq: None,
-
             batch_size,
-
             warmup_rows: 100,
-
+            name: None,
+            budget: ErrorBudget::default(),
+            processor_metrics: Vec::new(),
+            processor_reports: Vec::new(),
         }
-
     }
 
-
-
     pub fn set_dlq(&mut self, dlq: Box<dyn DlqSink>) {
-
         self.dlq = Some(dlq);
-
     }
 
-
+    pub fn set_name(&mut self, name: impl Into<String>) {
+        self.name = Some(name.into());
+    }
 
     pub fn set_warmup_rows(&mut self, rows: usize) {
-
         self.warmup_rows = rows;
-
     }
 
-
+    /// Aborts the job once more than `max_rate` (0.0-1.0) of the records seen so far
+    /// have been dead-lettered. The rate is only enforced after `min_records` records.
+    pub fn set_max_error_rate(&mut self, max_rate: f64, min_records: u64) {
+        self.budget.max_rate = Some(max_rate);
+        self.budget.min_records = min_records;
+    }
 
     pub fn add_processor(&mut self, processor: Box<dyn DataProcessor>) {
+        self.add_processor_with_retry(processor, RetryPolicy::default());
+    }
 
-        self.processors.push(processor);
-
+    pub fn add_processor_with_retry(
+        &mut self,
+        processor: Box<dyn DataProcessor>,
+        retry: RetryPolicy,
+    ) {
+        self.processors.push(Stage { processor, retry });
     }
 
+    /// Processor counters collected at the end of the last run, keyed `<processor>.<counter>`.
+    pub fn processor_metrics(&self) -> &[(String, u64)] {
+        &self.processor_metrics
+    }
 
+    /// Processor reports collected at the end of the last run, in pipeline order.
+    pub fn processor_reports(&self) -> &[ProcessorReport] {
+        &self.processor_reports
+    }
 
     pub fn set_sink_factory<F>(&mut self, factory: F)
-
     where
-
         F: Fn(Arc<Schema>) -> Result<Box<dyn OutputSink>> + Send + Sync + 'static,
-
     {
-
         self.sink_factory = Some(Box::new(factory));
-
     }
 
+    /// Runs the pipeline to completion. The sink and the DLQ are closed on every exit
+    /// path, so dead letters buffered before an abort still reach storage.
+    pub async fn run(&mut self, initial_schema: Option<Arc<Schema>>) -> Result<()> {
+        let result = self.execute(initial_schema).await;
+        let closed = self.close_outputs().await;
+        let total_rows = match (result, closed) {
+            (Err(e), Err(close_error)) => {
+                error!(error = %close_error, "Failed to close outputs after pipeline error");
+                return Err(e);
+            }
+            (result, closed) => result.and_then(|rows| closed.map(|_| rows))?,
+        };
+        self.budget.check_final()?;
+
+        info!(
+            total_rows = %total_rows,
+            dead_letters = %self.budget.failed,
+            "Pipeline execution completed successfully"
+        );
+        Ok(())
+    }
 
+    /// Closes the sink and the DLQ, if they were opened; both are attempted.
+    async fn close_outputs(&mut self) -> Result<()> {
+        let sink = match self.sink.take() {
+            Some(mut s) => s.close().await,
+            None => Ok(()),
+        };
+        let dlq = match self.dlq.take() {
+            Some(mut d) => d.close().await,
+            None => Ok(()),
+        };
+        sink.and(dlq)
+    }
 
-            pub async fn run(&mut self, initial_schema: Option<Arc<Schema>>) -> Result<()> {
-
-
-
-                let (mut current_schema, total_rows) = if let Some(schema) = &initial_schema {
-
-
-
-                    (schema.clone(), 0)
-
-
-
-                } else {
-
-
-
-                    info!(
-
-
-
-                        warmup_limit = %self.warmup_rows,
-
-
-
-                        "Starting adaptive warm-up phase"
-
-
-
-                    );
-
-
-
-                    let mut warmup_records = Vec::new();
-
-
-
-                    while warmup_records.len() < self.warmup_rows {
-
-
-
-                        match self.source.next_record().await {
-
-
-
-                            Ok(Some(record)) => warmup_records.push(record),
-
-
-
-                            Ok(None) => break,
-
-
-
-                            Err(e) => {
-
-
-
-                                error!(error = %e, "Source error during warm-up");
-
-
-
-                                return Err(e);
-
-
-
-                            }
-
-
-
-                        }
-
-
-
-                    }
-
-
-
-        
-
-
-
-                    if warmup_records.is_empty() {
-
-
-
-                        return Err(UdoError::Pipeline(
-
-
-
-                            "Source yielded no records during warm-up".to_string(),
-
-
-
-                        ));
-
-
-
-                    }
-
-
-
-        
-
-
-
-                    let schema_array = OwnedValue::Array(warmup_records.clone());
-
-
-
-                    let mut schema = Arc::new(infer_schema(&schema_array, None)?);
-
-
-
-        
-
-
-
-                    for proc in &self.processors {
-
-
-
-                        schema = proc.update_schema(&schema)?;
-
-
-
-                    }
-
-
-
-        
-
-
-
-                    if let Some(factory) = &self.sink_factory {
-
-
-
-                        self.sink = Some(factory(schema.clone())?);
-
-
-
-                    }
-
-
-
-        
-
-
-
-                    let mut processed_warmup = Vec::new();
-
-
-
-                    for record in warmup_records {
-
-
-
-                        if let Some(rec) = self.process_record_sequential(record).await? {
-
-
-
-                            processed_warmup.push(rec);
-
-
-
-                        }
-
-
-
-                    }
-
-
-
-        
-
-
-
-                    let mut rows = 0;
-
-
-
-                    if !processed_warmup.is_empty() {
-
-
-
-                        self.flush_to_sink(&processed_warmup, &schema).await?;
-
-
+    /// Reads, processes and writes every record; returns the number of rows written.
+    async fn execute(&mut self, initial_schema: Option<Arc<Schema>>) -> Result<usize> {
+        let mut index = 0u64;
+        let mut pending = Vec::new();
+        let mut dlq_poll = dlq_poll_interval();
+        // Processors see the first records even when the schema is supplied, e.g. to
+        // sample column values.
+        info!(
+            warmup_limit = %self.warmup_rows,
+            "Starting adaptive warm-up phase"
+        );
+        while pending.len() < self.warmup_rows {
+            match self.next_source_record(&mut index, &mut dlq_poll).await? {
+                Some(record) => pending.push(record),
+                None => break,
+            }
+        }
+        let samples: Vec<OwnedValue> = pending.iter().map(|r| r.record.clone()).collect();
+        for stage in &self.processors {
+            stage.processor.observe_samples(&samples);
+        }
 
-                        rows = processed_warmup.len();
+        let mut schema = match initial_schema {
+            Some(schema) => schema,
+            None if samples.is_empty() => {
+                return Err(UdoError::Pipeline(
+                    "Source yielded no records during warm-up".to_string(),
+                ));
+            }
+            None => Arc::new(infer_schema(&OwnedValue::Array(samples), None)?),
+        };
 
+        for stage in &self.processors {
+            schema = stage.processor.update_schema(&schema)?;
+        }
+        if let Some(factory) = &self.sink_factory {
+            self.sink = Some(factory(schema.clone())?);
+        }
 
+        // The warm-up records start the first batch, so every batch but the last is
+        // made of `batch_size` records.
+        let processors = Arc::new(std::mem::take(&mut self.processors));
+        let source = std::mem::replace(&mut self.source, Box::new(EmptySource));
 
+        // Undecodable input is surfaced as an item so it can be dead-lettered in order;
+        // any other source error ends the stream and fails the job.
+        let stream = futures::stream::unfold(
+            Some((source, index)),
+            |state: Option<(Box<dyn InputSource>, u64)>| async move {
+                let (mut source, index) = state?;
+                match source.next_record().await {
+                    Ok(Some(record)) => {
+                        let record = SourceRecord {
+                            record,
+                            index,
+                            position: source.position(),
+                        };
+                        Some((SourceItem::Record(record), Some((source, index + 1))))
                     }
-
-
-
-        
-
-
-
-                    (schema, rows)
-
-
-
-                };
-
-
-
-        
-
-
-
-                if initial_schema.is_some() {
-
-
-
-                    for proc in &self.processors {
-
-
-
-                        current_schema = proc.update_schema(&current_schema)?;
-
-
-
+                    Ok(None) => None,
+                    Err(e @ UdoError::SourceDecode { .. }) => {
+                        Some((SourceItem::Rejected(e, index), Some((source, index + 1))))
                     }
+                    Err(e) => Some((SourceItem::Fatal(e), None)),
+                }
+            },
+        );
+
+        // Batches run through the stages in their own tasks, so the source keeps being
+        // read and finished batches keep being written while others are in flight.
+        let pipeline_name = self.name.clone();
+        let batches = futures::stream::iter(pending.into_iter().map(SourceItem::Record))
+            .chain(stream)
+            .chunks(self.batch_size.max(1))
+            .map(|items| {
+                let procs = processors.clone();
+                let schema = schema.clone();
+                let pipeline_name = pipeline_name.clone();
+                tokio::spawn(run_batch(procs, items, schema, pipeline_name))
+            })
+            .buffered(BATCHES_IN_FLIGHT);
 
+        tokio::pin!(batches);
 
+        let mut total_rows = 0;
+        while let Some(joined) = polling_dlq(batches.next(), &mut self.dlq, &mut dlq_poll).await {
+            let outcome =
+                joined.map_err(|e| UdoError::Pipeline(format!("Batch task failed: {}", e)))?;
+            for _ in 0..outcome.read - outcome.letters.len() {
+                self.budget.record_ok();
+            }
+            for letter in outcome.letters {
+                route_dead_letter(&mut self.dlq, &mut self.budget, Box::new(letter)).await?;
+            }
+            if let Some(e) = outcome.fatal {
+                error!(error = %e, "Source error, aborting pipeline");
+                return Err(e);
+            }
+            if let Some(s) = self.sink.as_mut()
+                && !outcome.rows.is_empty()
+            {
+                let batch = json_rows_to_batch(&outcome.rows, schema.clone())?;
+                s.write_batch(batch).await?;
+            }
+            total_rows += outcome.rows.len();
+            debug!(total = %total_rows, "Batch flushed to sink");
+        }
 
-                    if let Some(factory) = &self.sink_factory {
-
-
-
-                        self.sink = Some(factory(current_schema.clone())?);
-
-
-
-                    }
-
+        for (idx, stage) in processors.iter().enumerate() {
+            for (counter, value) in stage.processor.metrics() {
+                let key = format!("{}.{}", stage.processor.name(), counter);
+                info!(metric = %key, value = %value, "Processor metric");
+                self.processor_metrics.push((key, value));
+            }
+            if let Some(report) = stage.processor.report() {
+                self.processor_reports.push(ProcessorReport {
+                    processor: stage.processor.name().to_string(),
+                    stage: idx,
+                    report,
+                });
+            }
+        }
 
+        Ok(total_rows)
+    }
 
+    /// Next record of the source. Undecodable input is dead-lettered in order; any
+    /// other source error fails the job.
+    async fn next_source_record(
+        &mut self,
+        index: &mut u64,
+        dlq_poll: &mut Interval,
+    ) -> Result<Option<SourceRecord>> {
+        loop {
+            let next = self.source.next_record();
+            match polling_dlq(next, &mut self.dlq, dlq_poll).await {
+                Ok(Some(record)) => {
+                    let record = SourceRecord {
+                        record,
+                        index: *index,
+                        position: self.source.position(),
+                    };
+                    *index += 1;
+                    return Ok(Some(record));
                 }
+                Ok(None) => return Ok(None),
+                Err(e @ UdoError::SourceDecode { .. }) => {
+                    let letter = decode_dead_letter(e, *index, self.name.clone());
+                    *index += 1;
+                    route_dead_letter(&mut self.dlq, &mut self.budget, letter).await?;
+                }
+                Err(e) => {
+                    error!(error = %e, "Source error, aborting pipeline");
+                    return Err(e);
+                }
+            }
+        }
+    }
+}
 
+/// A record read from the source, with where it came from.
+struct SourceRecord {
+    record: OwnedValue,
+    index: u64,
+    position: Option<u64>,
+}
 
+enum SourceItem {
+    Record(SourceRecord),
+    Rejected(UdoError, u64),
+    Fatal(UdoError),
+}
 
-        
-
+/// What became of a batch: the rows to write and the dead letters to route.
+struct BatchOutcome {
+    rows: Vec<OwnedValue>,
+    letters: Vec<DeadLetter>,
+    /// Records and rejected input in the batch.
+    read: usize,
+    /// Source error that ended the stream after this batch.
+    fatal: Option<UdoError>,
+}
 
+struct Stage {
+    processor: Box<dyn DataProcessor>,
+    retry: RetryPolicy,
+}
 
-                self.run_main_loop(current_schema, total_rows).await
+/// Runs a batch through the stages in order and checks what is left against the sink
+/// schema.
+async fn run_batch(
+    stages: Arc<Vec<Stage>>,
+    items: Vec<SourceItem>,
+    schema: Arc<Schema>,
+    pipeline_name: Option<String>,
+) -> BatchOutcome {
+    let mut rows = Vec::with_capacity(items.len());
+    let mut letters = Vec::new();
+    let mut fatal = None;
+    let mut read = 0;
+    for item in items {
+        match item {
+            SourceItem::Record(row) => {
+                read += 1;
+                rows.push(row);
+            }
+            SourceItem::Rejected(e, index) => {
+                read += 1;
+                letters.push(decode_dead_letter(e, index, None));
+            }
+            SourceItem::Fatal(e) => fatal = Some(e),
+        }
+    }
 
+    for i in 0..stages.len() {
+        if rows.is_empty() {
+            break;
+        }
+        let (kept, failed) = run_stage(stages.clone(), i, rows).await;
+        rows = kept;
+        letters.extend(failed);
+    }
 
+    let mut written = Vec::with_capacity(rows.len());
+    for row in rows {
+        match check_sink_row(Some(row.record), &schema) {
+            Ok(record) => written.extend(record),
+            Err(mut letter) => {
+                letter.record_index = Some(row.index);
+                letter.source_position = row.position;
+                letters.push(letter);
+            }
+        }
+    }
+    let letters = letters
+        .into_iter()
+        .map(|mut letter| {
+            letter.pipeline = pipeline_name.clone();
+            *letter
+        })
+        .collect();
+    BatchOutcome {
+        rows: written,
+        letters,
+        read,
+        fatal,
+    }
+}
 
+/// Runs stage `i` over a batch: its `process` on every row, concurrently but keeping
+/// their order, then its `process_batch` on the rows left. Returns the rows that
+/// passed and the dead letters of those that failed; a failed `process_batch` fails
+/// every row it was given.
+async fn run_stage(
+    stages: Arc<Vec<Stage>>,
+    i: usize,
+    rows: Vec<SourceRecord>,
+) -> (Vec<SourceRecord>, Vec<Box<DeadLetter>>) {
+    let mut kept = Vec::with_capacity(rows.len());
+    let mut letters = Vec::new();
+    let mut processed = futures::stream::iter(rows)
+        .map(|row| {
+            // Kept outside the task, so a panicking processor still yields a dead letter.
+            let original = SourceRecord {
+                record: row.record.clone(),
+                index: row.index,
+                position: row.position,
+            };
+            let stages = stages.clone();
+            let task = tokio::spawn(async move { process_row(&stages[i], i, row).await });
+            async move { (original, task.await) }
+        })
+        .buffered(num_cpus::get() * 2);
+    while let Some((original, joined)) = processed.next().await {
+        match joined {
+            Ok(Ok(Some(row))) => kept.push(row),
+            Ok(Ok(None)) => {} // Record filtered out
+            Ok(Err(letter)) => letters.push(letter),
+            Err(e) => {
+                error!(processor = %stages[i].processor.name(), error = %e, "Processor panicked");
+                let error = UdoError::Panic(e.to_string());
+                letters.push(stage_dead_letter(original, &error, i, &stages[i], 1));
             }
+        }
+    }
+    if kept.is_empty() {
+        return (kept, letters);
+    }
 
-    async fn process_record_sequential(
-        &self,
-        mut record: OwnedValue,
-    ) -> Result<Option<OwnedValue>> {
-        for proc in &self.processors {
-            if let Some(processed) = proc.process(record).await? {
-                record = processed;
+    let stage = &stages[i];
+    let records = kept.iter().map(|row| row.record.clone()).collect();
+    let slots = AssertUnwindSafe(stage.processor.process_batch(records))
+        .catch_unwind()
+        .await
+        .unwrap_or_else(|panic| Err(UdoError::Panic(panic_message(panic))))
+        .and_then(|slots| {
+            if slots.len() == kept.len() {
+                Ok(slots)
             } else {
-                return Ok(None);
+                Err(UdoError::Pipeline(format!(
+                    "process_batch returned {} rows for a batch of {}",
+                    slots.len(),
+                    kept.len()
+                )))
             }
+        });
+    match slots {
+        Ok(slots) => {
+            let kept = kept
+                .into_iter()
+                .zip(slots)
+                .filter_map(|(row, slot)| {
+                    Some(SourceRecord {
+                        record: slot?,
+                        ..row
+                    })
+                })
+                .collect();
+            (kept, letters)
+        }
+        Err(e) => {
+            warn!(
+                processor = %stage.processor.name(),
+                rows = %kept.len(),
+                error = %e,
+                "Batch processing failed"
+            );
+            letters.extend(
+                kept.into_iter()
+                    .map(|row| stage_dead_letter(row, &e, i, stage, 1)),
+            );
+            (Vec::new(), letters)
         }
-        Ok(Some(record))
     }
+}
 
-    async fn run_main_loop(&mut self, schema: Arc<Schema>, mut total_rows: usize) -> Result<()> {
-        let mut row_buffer = Vec::with_capacity(self.batch_size);
-        let processors = Arc::new(self.processors.drain(..).collect::<Vec<_>>());
+/// Runs a record through stage `i`'s `process`, retrying per the stage's policy and
+/// wrapping a final failure in a DLQ envelope.
+async fn process_row(
+    stage: &Stage,
+    i: usize,
+    mut row: SourceRecord,
+) -> std::result::Result<Option<SourceRecord>, Box<DeadLetter>> {
+    let mut attempts = 1;
+    let result = loop {
+        match stage.processor.process(row.record.clone()).await {
+            Err(e) if attempts < stage.retry.max_attempts && stage.retry.is_retryable(&e) => {
+                let delay = stage.retry.backoff(attempts);
+                warn!(
+                    processor = %stage.processor.name(),
+                    attempt = %attempts,
+                    delay_ms = %delay.as_millis(),
+                    error = %e,
+                    "Processor failed, retrying"
+                );
+                tokio::time::sleep(delay).await;
+                attempts += 1;
+            }
+            result => break result,
+        }
+    };
+    match result {
+        Ok(Some(processed)) => {
+            row.record = processed;
+            Ok(Some(row))
+        }
+        Ok(None) => Ok(None),
+        Err(e) => Err(stage_dead_letter(row, &e, i, stage, attempts)),
+    }
+}
 
-        let source = std::mem::replace(&mut self.source, Box::new(EmptySource));
-        let mut sink = self.sink.take();
-        let mut dlq = self.dlq.take();
+/// The message a panic was raised with, if it was a string.
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    match panic.downcast::<String>() {
+        Ok(message) => *message,
+        Err(panic) => panic
+            .downcast_ref::<&str>()
+            .map_or_else(|| "unknown panic".to_string(), |m| m.to_string()),
+    }
+}
 
-        let stream =
-            futures::stream::unfold(source, |mut source: Box<dyn InputSource>| async move {
-                match source.next_record().await {
-                    Ok(Some(record)) => Some((record, source)),
-                    _ => None,
-                }
-            });
+fn stage_dead_letter(
+    row: SourceRecord,
+    error: &UdoError,
+    i: usize,
+    stage: &Stage,
+    attempts: u32,
+) -> Box<DeadLetter> {
+    let mut letter = DeadLetter::new(row.record, error);
+    letter.stage = Some(i);
+    letter.processor = Some(stage.processor.name().to_string());
+    letter.record_index = Some(row.index);
+    letter.source_position = row.position;
+    letter.attempts = attempts;
+    Box::new(letter)
+}
 
-        let processed_stream = stream
-            .map(|record| {
-                let procs = processors.clone();
-                tokio::spawn(async move {
-                    let mut current_record = Some(record);
-                    let num_procs = procs.len();
-                    for i in 0..num_procs {
-                        if let Some(rec) = current_record.take() {
-                            let rec_clone = rec.clone();
-                            match procs[i].process(rec).await {
-                                Ok(res) => current_record = res,
-                                Err(e) => {
-                                    return Err((rec_clone, e.to_string()));
-                                }
-                            }
-                        } else {
-                            break;
-                        }
-                    }
-                    Ok(current_record)
-                })
-            })
-            .buffer_unordered(num_cpus::get() * 2);
-
-        tokio::pin!(processed_stream);
-
-        while let Some(join_result) = processed_stream.next().await {
-            match join_result {
-                Ok(Ok(Some(record))) => {
-                    row_buffer.push(record);
-                    if row_buffer.len() >= self.batch_size {
-                        if let Some(s) = sink.as_mut() {
-                            let batch = json_rows_to_batch(&row_buffer, schema.clone())?;
-                            s.write_batch(batch).await?;
-                        }
-                        total_rows += row_buffer.len();
-                        row_buffer.clear();
-                        debug!(total = %total_rows, "Batch flushed to sink");
-                    }
-                }
-                Ok(Ok(None)) => {} // Record filtered out
-                Ok(Err((failed_record, reason))) => {
-                    error!(reason = %reason, "Processing failed, sending to DLQ");
-                    if let Some(d) = dlq.as_mut() {
-                        d.write_dead_letter(failed_record, reason).await?;
-                    }
-                }
-                Err(e) => {
-                    error!(error = %e, "Task join error");
-                }
+/// Rejects rows whose values cannot be represented in the sink schema instead of
+/// silently writing nulls for them.
+fn check_sink_row(
+    record: Option<OwnedValue>,
+    schema: &Schema,
+) -> std::result::Result<Option<OwnedValue>, Box<DeadLetter>> {
+    match record {
+        Some(record) => match check_row(&record, schema) {
+            Ok(()) => Ok(Some(record)),
+            Err(e) => {
+                let mut letter = DeadLetter::new(record, &e);
+                letter.processor = Some("sink".to_string());
+                Err(Box::new(letter))
             }
+        },
+        None => Ok(None),
+    }
+}
+
+fn decode_dead_letter(
+    error: UdoError,
+    index: u64,
+    pipeline_name: Option<String>,
+) -> Box<DeadLetter> {
+    let (raw, position) = match &error {
+        UdoError::SourceDecode { raw, position, .. } => {
+            (String::from_utf8_lossy(raw).into_owned(), *position)
         }
+        _ => (String::new(), None),
+    };
+    let mut letter = DeadLetter::new(OwnedValue::from(raw), &error);
+    letter.pipeline = pipeline_name;
+    letter.record_index = Some(index);
+    letter.source_position = position;
+    Box::new(letter)
+}
 
-        if !row_buffer.is_empty() {
-            if let Some(s) = sink.as_mut() {
-                let batch = json_rows_to_batch(&row_buffer, schema.clone())?;
-                s.write_batch(batch).await?;
+fn dlq_poll_interval() -> Interval {
+    let mut interval = tokio::time::interval(DLQ_POLL_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    interval
+}
+
+/// Awaits `future`, meanwhile calling the DLQ's `flush_due` on every tick. A failed
+/// flush is only logged: the DLQ keeps the letters and `close` pushes them again.
+async fn polling_dlq<T>(
+    future: impl Future<Output = T>,
+    dlq: &mut Option<Box<dyn DlqSink>>,
+    interval: &mut Interval,
+) -> T {
+    tokio::pin!(future);
+    loop {
+        tokio::select! {
+            output = &mut future => return output,
+            _ = interval.tick() => {
+                if let Some(d) = dlq.as_mut()
+                    && let Err(e) = d.flush_due().await
+                {
+                    warn!(error = %e, "Failed to flush due dead letters");
+                }
             }
-            total_rows += row_buffer.len();
         }
+    }
+}
 
-        if let Some(s) = sink.as_mut() {
-            s.close().await?;
-        }
+async fn route_dead_letter(
+    dlq: &mut Option<Box<dyn DlqSink>>,
+    budget: &mut ErrorBudget,
+    letter: Box<DeadLetter>,
+) -> Result<()> {
+    error!(
+        reason = %letter.error,
+        kind = %letter.error_kind,
+        stage = ?letter.stage,
+        "Record failed, sending to DLQ"
+    );
+    if let Some(d) = dlq.as_mut() {
+        d.write_dead_letter(*letter).await?;
+    }
+    budget.record_failure()
+}
 
-        info!(total_rows = %total_rows, "Pipeline execution completed successfully");
-        Ok(())
+#[derive(Default)]
+struct ErrorBudget {
+    max_rate: Option<f64>,
+    min_records: u64,
+    seen: u64,
+    failed: u64,
+}
+
+impl ErrorBudget {
+    fn record_ok(&mut self) {
+        self.seen += 1;
     }
 
-    async fn flush_to_sink(&mut self, records: &[OwnedValue], schema: &Arc<Schema>) -> Result<()> {
-        if let Some(sink) = self.sink.as_mut() {
-            let batch = json_rows_to_batch(records, schema.clone())?;
-            sink.write_batch(batch).await?;
+    fn record_failure(&mut self) -> Result<()> {
+        self.seen += 1;
+        self.failed += 1;
+        if self.seen >= self.min_records {
+            self.check_final()
+        } else {
+            Ok(())
+        }
+    }
+
+    fn check_final(&self) -> Result<()> {
+        if let Some(max_rate) = self.max_rate
+            && self.seen > 0
+        {
+            let rate = self.failed as f64 / self.seen as f64;
+            if rate > max_rate {
+                return Err(UdoError::Pipeline(format!(
+                    "Error rate {:.4} ({} of {} records) exceeds max_error_rate {}",
+                    rate, self.failed, self.seen, max_rate
+                )));
+            }
         }
         Ok(())
     }
@@ -1,18 +1,28 @@
 use crate::core::error::{Result, UdoError};
-use crate::core::pipeline::DlqSink;
+use crate::core::pipeline::{DeadLetter, DlqSink, InputSource};
 use async_trait::async_trait;
 use simd_json::OwnedValue;
 use std::fs::File;
 use std::io::{BufWriter, Write};
 use std::path::PathBuf;
+use tokio::io::AsyncBufReadExt;
+use tracing::warn;
 
+#[cfg(feature = "cloud")]
+use crate::core::config::{DlqRotation, RetryPolicy};
 #[cfg(feature = "cloud")]
 use object_store::path::Path as ObjectPath;
 #[cfg(feature = "cloud")]
-use object_store::{parse_url, ObjectStore, ObjectStoreExt};
+use object_store::{parse_url, ObjectStore, ObjectStoreExt, PutPayload};
+#[cfg(feature = "cloud")]
+use std::collections::VecDeque;
 #[cfg(feature = "cloud")]
 use std::sync::Arc;
 #[cfg(feature = "cloud")]
+use std::time::{Duration, Instant};
+#[cfg(feature = "cloud")]
+use tracing::error;
+#[cfg(feature = "cloud")]
 use url::Url;
 
 pub struct FileDlq {
@@ -30,49 +40,253 @@ impl FileDlq {
 
 #[async_trait]
 impl DlqSink for FileDlq {
-    async fn write_dead_letter(&mut self, record: OwnedValue, reason: String) -> Result<()> {
-        let json_str = simd_json::to_string(&record).map_err(UdoError::JsonParse)?;
-        let log_line = format!(r#"{{"error": "{}", "record": {}}}"#, reason, json_str);
+    async fn write_dead_letter(&mut self, letter: DeadLetter) -> Result<()> {
+        let log_line =
+            serde_json::to_string(&letter).map_err(|e| UdoError::Unknown(e.to_string()))?;
         writeln!(self.writer, "{}", log_line).map_err(UdoError::Io)?;
         Ok(())
     }
+
+    async fn flush(&mut self) -> Result<()> {
+        self.writer.flush().map_err(UdoError::Io)
+    }
+}
+
+/// Reads a `FileDlq` output file and yields the original records, so they can be
+/// replayed through a pipeline once the underlying issue is fixed.
+///
+/// Letters for input the source could not decode only hold the raw text, which would
+/// fail again as a schema mismatch; they are skipped with a warning and counted in
+/// `skipped`.
+pub struct DlqReplaySource {
+    reader: tokio::io::BufReader<tokio::fs::File>,
+    line_buffer: String,
+    line_number: u64,
+    skipped: u64,
+}
+
+impl DlqReplaySource {
+    pub async fn new(path: PathBuf) -> Result<Self> {
+        let file = tokio::fs::File::open(path).await.map_err(UdoError::Io)?;
+        Ok(Self {
+            reader: tokio::io::BufReader::new(file),
+            line_buffer: String::new(),
+            line_number: 0,
+            skipped: 0,
+        })
+    }
+
+    /// Number of `source_decode` letters skipped so far.
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+}
+
+#[async_trait]
+impl InputSource for DlqReplaySource {
+    async fn next_record(&mut self) -> Result<Option<OwnedValue>> {
+        loop {
+            self.line_buffer.clear();
+            let bytes_read = self
+                .reader
+                .read_line(&mut self.line_buffer)
+                .await
+                .map_err(UdoError::Io)?;
+            if bytes_read == 0 {
+                return Ok(None);
+            }
+            self.line_number += 1;
+            if self.line_buffer.trim().is_empty() {
+                continue;
+            }
+
+            match serde_json::from_str::<DeadLetter>(&self.line_buffer) {
+                Ok(letter) if letter.error_kind == "source_decode" => {
+                    self.skipped += 1;
+                    warn!(
+                        line = %self.line_number,
+                        source_position = ?letter.source_position,
+                        "Skipping undecodable source input; fix and re-ingest it from the source"
+                    );
+                }
+                Ok(letter) => return Ok(Some(letter.record)),
+                Err(e) => {
+                    warn!(line = %self.line_number, error = %e, "Skipping malformed DLQ envelope");
+                }
+            }
+        }
+    }
+
+    fn position(&self) -> Option<u64> {
+        Some(self.line_number)
+    }
 }
 
+/// Dead letters are buffered into NDJSON part files; a part is sealed once it
+/// reaches `max_part_bytes` or has been open for `max_part_age`. The age is checked on
+/// every write and on the runner's `flush_due` timer, so a part does not wait for the
+/// next letter.
+///
+/// Sealed parts wait in a queue until their upload succeeds. After a failed put the
+/// queue is retried with backoff rather than on every letter, and a failed upload
+/// never fails `write_dead_letter`; past `max_pending_bytes` the oldest parts are
+/// dropped.
 #[cfg(feature = "cloud")]
 pub struct CloudDlq {
     store: Arc<dyn ObjectStore>,
     base_path: ObjectPath,
+    run_id: String,
+    part: u64,
+    buffer: Vec<u8>,
+    part_opened: Option<Instant>,
+    max_part_bytes: usize,
+    max_part_age: Duration,
+    pending: VecDeque<(ObjectPath, PutPayload)>,
+    pending_bytes: usize,
+    max_pending_bytes: usize,
+    retry: RetryPolicy,
+    failures: u32,
+    retry_at: Option<Instant>,
 }
 
 #[cfg(feature = "cloud")]
 impl CloudDlq {
     pub fn new(url_str: &str) -> Result<Self> {
+        Self::with_rotation(url_str, &DlqRotation::default())
+    }
+
+    pub fn with_rotation(url_str: &str, rotation: &DlqRotation) -> Result<Self> {
         let url = Url::parse(url_str)?;
         let (store, path) = parse_url(&url)?;
+
+        // Timestamp + pid keeps part names from concurrent or restarted jobs apart.
+        let started = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_err(|e| UdoError::Unknown(e.to_string()))?
+            .as_micros();
         Ok(Self {
             store: Arc::from(store),
             base_path: path,
+            run_id: format!("{}_{}", started, std::process::id()),
+            part: 0,
+            buffer: Vec::new(),
+            part_opened: None,
+            max_part_bytes: rotation.max_part_bytes,
+            max_part_age: Duration::from_secs(rotation.max_part_age_secs),
+            pending: VecDeque::new(),
+            pending_bytes: 0,
+            max_pending_bytes: rotation.max_pending_bytes,
+            retry: RetryPolicy {
+                max_attempts: 5,
+                initial_backoff_ms: 500,
+                max_backoff_ms: 30_000,
+                ..RetryPolicy::default()
+            },
+            failures: 0,
+            retry_at: None,
         })
     }
+
+    fn should_rotate(&self) -> bool {
+        self.buffer.len() >= self.max_part_bytes
+            || self
+                .part_opened
+                .is_some_and(|opened| opened.elapsed() >= self.max_part_age)
+    }
+
+    /// Moves the open part to the upload queue, dropping the oldest queued parts
+    /// while the queue is over its byte limit.
+    fn seal(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+        let path = ObjectPath::from(format!(
+            "{}/dlq_{}_{:06}.ndjson",
+            self.base_path, self.run_id, self.part
+        ));
+        let payload = PutPayload::from(std::mem::take(&mut self.buffer));
+        self.pending_bytes += payload.content_length();
+        self.pending.push_back((path, payload));
+        self.part_opened = None;
+        self.part += 1;
+
+        while self.pending_bytes > self.max_pending_bytes && self.pending.len() > 1 {
+            if let Some((path, payload)) = self.pending.pop_front() {
+                self.pending_bytes -= payload.content_length();
+                error!(part = %path, bytes = payload.content_length(), "Dropped dead letter part that could not be uploaded");
+            }
+        }
+    }
+
+    fn retry_due(&self) -> bool {
+        !self.pending.is_empty() && self.retry_at.is_none_or(|at| Instant::now() >= at)
+    }
+
+    /// Uploads queued parts oldest first; on failure the rest stay queued and the next
+    /// attempt is pushed back.
+    async fn upload_pending(&mut self) -> Result<()> {
+        while let Some((path, payload)) = self.pending.front() {
+            if let Err(e) = self.store.put(path, payload.clone()).await {
+                self.failures += 1;
+                self.retry_at = Some(Instant::now() + self.retry.backoff(self.failures));
+                return Err(e.into());
+            }
+            self.pending_bytes -= payload.content_length();
+            self.pending.pop_front();
+            self.failures = 0;
+            self.retry_at = None;
+        }
+        Ok(())
+    }
 }
 
 #[cfg(feature = "cloud")]
 #[async_trait]
 impl DlqSink for CloudDlq {
-    async fn write_dead_letter(&mut self, record: OwnedValue, reason: String) -> Result<()> {
-        let json_str = simd_json::to_string(&record).map_err(UdoError::JsonParse)?;
-        let content = format!(r#"{{"error": "{}", "record": {}}}"#, reason, json_str);
+    async fn write_dead_letter(&mut self, letter: DeadLetter) -> Result<()> {
+        let content =
+            serde_json::to_string(&letter).map_err(|e| UdoError::Unknown(e.to_string()))?;
 
-        // Simple strategy: One file per error (not efficient for high error rates, but safe for DLQ)
-        // A production version would buffer or rotate logs.
-        let timestamp = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_micros();
-        let filename = format!("{}/dlq_{}.json", self.base_path, timestamp);
-        let path = ObjectPath::from(filename);
+        if self.buffer.is_empty() {
+            self.part_opened = Some(Instant::now());
+        }
+        self.buffer.extend_from_slice(content.as_bytes());
+        self.buffer.push(b'\n');
+
+        if self.should_rotate() {
+            self.seal();
+        }
+        if self.retry_due()
+            && let Err(e) = self.upload_pending().await
+        {
+            warn!(error = %e, queued = self.pending.len(), "Failed to upload dead letter part, will retry");
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        self.seal();
+        let mut attempts = 1;
+        loop {
+            match self.upload_pending().await {
+                Ok(()) => return Ok(()),
+                Err(e) if attempts < self.retry.max_attempts => {
+                    warn!(error = %e, attempt = attempts, "Failed to upload dead letter part, retrying");
+                    tokio::time::sleep(self.retry.backoff(attempts)).await;
+                    attempts += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
 
-        self.store.put(&path, content.into()).await?;
+    async fn flush_due(&mut self) -> Result<()> {
+        if self.should_rotate() {
+            self.seal();
+        }
+        if self.retry_due() {
+            self.upload_pending().await?;
+        }
         Ok(())
     }
 }
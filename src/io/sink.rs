@@ -1,3 +1,4 @@
+use crate::core::config::{ascii_byte, CsvOptions};
 use crate::core::error::{Result, UdoError};
 use crate::core::pipeline::OutputSink;
 use arrow::datatypes::Schema;
@@ -66,9 +67,31 @@ pub struct CsvSink {
 }
 
 impl CsvSink {
-    pub fn new(path: PathBuf) -> Result<Self> {
+    pub fn new(path: PathBuf, options: &CsvOptions) -> Result<Self> {
         let file = File::create(path).map_err(UdoError::Io)?;
-        let writer = csv::Writer::new(file);
+        let mut builder = csv::WriterBuilder::new()
+            .with_header(options.has_header)
+            .with_delimiter(ascii_byte(options.delimiter, "delimiter")?);
+        if let Some(q) = options.quote {
+            builder = builder.with_quote(ascii_byte(q, "quote")?);
+        }
+        if let Some(e) = options.escape {
+            builder = builder
+                .with_escape(ascii_byte(e, "escape")?)
+                .with_double_quote(false);
+        }
+        if let Some(null) = &options.null_value {
+            builder = builder.with_null(null.clone());
+        }
+        if let Some(fmt) = &options.date_format {
+            builder = builder.with_date_format(fmt.clone());
+        }
+        if let Some(fmt) = &options.timestamp_format {
+            builder = builder
+                .with_datetime_format(fmt.clone())
+                .with_timestamp_format(fmt.clone());
+        }
+        let writer = builder.build(file);
         Ok(Self {
             writer: Arc::new(Mutex::new(Some(writer))),
         })
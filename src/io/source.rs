@@ -1,14 +1,20 @@
+use crate::core::config::{ascii_byte, CsvColumn, CsvColumnType, CsvOptions};
 use crate::core::error::{Result, UdoError};
 use crate::core::pipeline::InputSource;
 use crate::utils::json::parse_json;
 use apache_avro::Reader as AvroReader;
-use arrow::csv;
+use arrow::csv as arrow_csv;
+use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
 use arrow::json::LineDelimitedWriter;
 use async_trait::async_trait;
-use simd_json::OwnedValue;
+use chrono::{NaiveDate, NaiveDateTime};
+use simd_json::{prelude::*, OwnedValue};
+use std::borrow::Cow;
 use std::collections::VecDeque;
 use std::fs::File as StdFile;
+use std::io::{Seek, SeekFrom};
 use std::path::PathBuf;
+use std::sync::Arc;
 use tokio::fs::File;
 use tokio::io::{AsyncBufReadExt, BufReader};
 
@@ -21,7 +27,8 @@ use rdkafka::message::Message;
 
 pub struct FileSource {
     reader: BufReader<File>,
-    line_buffer: String,
+    line_buffer: Vec<u8>,
+    line_number: u64,
 }
 
 impl FileSource {
@@ -29,82 +36,342 @@ impl FileSource {
         let file = File::open(path).await.map_err(UdoError::Io)?;
         Ok(Self {
             reader: BufReader::new(file),
-            line_buffer: String::new(),
+            line_buffer: Vec::new(),
+            line_number: 0,
         })
     }
 }
 
 #[async_trait]
 impl InputSource for FileSource {
+    /// Lines that are not valid JSON are returned as `UdoError::SourceDecode` so the
+    /// runner can dead-letter them and keep reading.
     async fn next_record(&mut self) -> Result<Option<OwnedValue>> {
-        self.line_buffer.clear();
-        let bytes_read = self
-            .reader
-            .read_line(&mut self.line_buffer)
-            .await
-            .map_err(UdoError::Io)?;
-        if bytes_read == 0 {
-            return Ok(None);
-        }
-
-        match parse_json(self.line_buffer.as_bytes()) {
-            Ok(val) => Ok(Some(val)),
-            Err(_) => {
-                eprintln!("Warning: Skipping corrupted JSON record");
-                Box::pin(self.next_record()).await
+        loop {
+            self.line_buffer.clear();
+            let bytes_read = self
+                .reader
+                .read_until(b'\n', &mut self.line_buffer)
+                .await
+                .map_err(UdoError::Io)?;
+            if bytes_read == 0 {
+                return Ok(None);
+            }
+            self.line_number += 1;
+            if self.line_buffer.trim_ascii().is_empty() {
+                continue;
             }
+
+            return parse_json(&self.line_buffer).map(Some).map_err(|e| {
+                let line = self
+                    .line_buffer
+                    .strip_suffix(b"\n")
+                    .unwrap_or(&self.line_buffer);
+                UdoError::SourceDecode {
+                    position: Some(self.line_number),
+                    raw: line.strip_suffix(b"\r").unwrap_or(line).to_vec(),
+                    reason: e.to_string(),
+                }
+            });
         }
     }
+
+    fn position(&self) -> Option<u64> {
+        Some(self.line_number)
+    }
 }
 
+/// Rows read, and converted through Arrow, at a time.
+const CSV_BATCH_ROWS: usize = 1024;
+
+/// Reads CSV rows and converts them to records with the configured or inferred
+/// schema. A row that cannot be read (e.g. has the wrong number of fields) or
+/// converted (e.g. a value that does not parse as its column's type) is returned as
+/// `UdoError::SourceDecode` at the line it starts on, so the runner can dead-letter it
+/// and keep reading.
 pub struct CsvSource {
     reader: csv::Reader<StdFile>,
-    buffer: VecDeque<OwnedValue>,
+    schema: Arc<Schema>,
+    /// Dialect of the rows as re-encoded for the Arrow decoder.
+    row_format: arrow_csv::reader::Format,
+    delimiter: u8,
+    quote: u8,
+    buffer: VecDeque<(Result<OwnedValue>, Option<u64>)>,
+    line: Option<u64>,
+    temporal_columns: Vec<(String, String, CsvColumnType)>,
+}
+
+/// A row as read from the file; `range` locates it re-encoded in the decoder's dialect.
+struct CsvRow {
+    range: std::ops::Range<usize>,
+    line: Option<u64>,
+    rejected: Option<String>,
 }
 
 impl CsvSource {
-    pub fn new(path: PathBuf) -> Result<Self> {
-        let file = StdFile::open(path).map_err(UdoError::Io)?;
-        let reader = csv::ReaderBuilder::new(std::sync::Arc::new(arrow::datatypes::Schema::empty())) // Schema will be inferred
-            .with_header(true)
-            .build(file)
-            .map_err(UdoError::Arrow)?;
-        
+    pub fn new(
+        path: PathBuf,
+        options: &CsvOptions,
+        columns: Option<&[CsvColumn]>,
+        infer_schema_rows: usize,
+    ) -> Result<Self> {
+        let mut file = StdFile::open(path).map_err(UdoError::Io)?;
+        let delimiter = ascii_byte(options.delimiter, "delimiter")?;
+        let quote = options
+            .quote
+            .map(|q| ascii_byte(q, "quote"))
+            .transpose()?
+            .unwrap_or(b'"');
+        let escape = options
+            .escape
+            .map(|e| ascii_byte(e, "escape"))
+            .transpose()?;
+
+        let mut format = arrow_csv::reader::Format::default()
+            .with_header(options.has_header)
+            .with_delimiter(delimiter)
+            .with_quote(quote);
+        if let Some(e) = escape {
+            format = format.with_escape(e);
+        }
+        // Rows reach the decoder re-encoded by `csv::Writer`: no header, quotes doubled.
+        let mut row_format = arrow_csv::reader::Format::default()
+            .with_header(false)
+            .with_delimiter(delimiter)
+            .with_quote(quote);
+        if let Some(null) = &options.null_value {
+            let null_regex = regex::Regex::new(&format!("^{}$", regex::escape(null)))
+                .map_err(|e| UdoError::Config(format!("Invalid CSV null value: {}", e)))?;
+            format = format.with_null_regex(null_regex.clone());
+            row_format = row_format.with_null_regex(null_regex);
+        }
+
+        // Columns with a custom date/timestamp format are read as text and normalised
+        // to ISO 8601 afterwards, since the Arrow CSV reader only understands ISO input.
+        let mut temporal_columns = Vec::new();
+        let schema = match columns {
+            Some(columns) => {
+                let mut fields = Vec::with_capacity(columns.len());
+                for col in columns {
+                    let custom_format = match col.data_type {
+                        CsvColumnType::Date => options.date_format.clone(),
+                        CsvColumnType::Timestamp => options.timestamp_format.clone(),
+                        _ => None,
+                    };
+                    let data_type = if let Some(input_format) = custom_format {
+                        temporal_columns.push((col.name.clone(), input_format, col.data_type));
+                        DataType::Utf8
+                    } else {
+                        csv_column_type(col.data_type)
+                    };
+                    fields.push(Field::new(&col.name, data_type, true));
+                }
+                Schema::new(fields)
+            }
+            None => {
+                let limit = (infer_schema_rows > 0).then_some(infer_schema_rows);
+                let (schema, _) = format
+                    .infer_schema(&mut file, limit)
+                    .map_err(UdoError::Arrow)?;
+                file.seek(SeekFrom::Start(0)).map_err(UdoError::Io)?;
+                schema
+            }
+        };
+
+        let reader = csv::ReaderBuilder::new()
+            .has_headers(options.has_header)
+            .delimiter(delimiter)
+            .quote(quote)
+            .escape(escape)
+            .from_reader(file);
+
         Ok(Self {
             reader,
+            schema: Arc::new(schema),
+            row_format,
+            delimiter,
+            quote,
             buffer: VecDeque::new(),
+            line: None,
+            temporal_columns,
         })
     }
+
+    /// Reads up to `CSV_BATCH_ROWS` rows into the buffer, in file order.
+    fn read_rows(&mut self) -> Result<()> {
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .flexible(true)
+            .from_writer(Vec::new());
+        let mut rows = Vec::new();
+        let mut record = csv::ByteRecord::new();
+        let mut start = 0;
+        while rows.len() < CSV_BATCH_ROWS {
+            let (line, rejected) = match self.reader.read_byte_record(&mut record) {
+                Ok(true) => (record.position().map(|p| p.line()), None),
+                Ok(false) => break,
+                Err(e) => {
+                    let line = e.position().map(|p| p.line());
+                    let reason = e.to_string();
+                    match e.into_kind() {
+                        csv::ErrorKind::Io(e) => return Err(UdoError::Io(e)),
+                        _ => (line, Some(reason)),
+                    }
+                }
+            };
+            writer
+                .write_byte_record(&record)
+                .map_err(|e| UdoError::Unknown(e.to_string()))?;
+            writer.flush().map_err(UdoError::Io)?;
+            let end = writer.get_ref().len();
+            rows.push(CsvRow {
+                range: start..end,
+                line,
+                rejected,
+            });
+            start = end;
+        }
+        let encoded = writer
+            .into_inner()
+            .map_err(|e| UdoError::Unknown(e.to_string()))?;
+
+        // Convert the readable rows at once, or one by one to single out the bad ones.
+        let readable: Vec<&CsvRow> = rows.iter().filter(|r| r.rejected.is_none()).collect();
+        let data: Cow<[u8]> = if readable.len() == rows.len() {
+            Cow::Borrowed(&encoded)
+        } else {
+            readable
+                .iter()
+                .flat_map(|r| encoded[r.range.clone()].iter().copied())
+                .collect()
+        };
+        let mut converted: VecDeque<Result<OwnedValue>> = match self.decode(&data) {
+            Ok(records) if records.len() == readable.len() => records.into_iter().map(Ok).collect(),
+            _ => readable
+                .iter()
+                .map(|row| {
+                    self.decode(&encoded[row.range.clone()])?
+                        .pop()
+                        .ok_or_else(|| UdoError::Pipeline("Row decoded to no record".to_string()))
+                })
+                .collect(),
+        };
+
+        for row in rows {
+            let decoded = match row.rejected {
+                Some(reason) => Err(reason),
+                None => match converted.pop_front() {
+                    Some(Ok(mut record)) => self.normalize_temporal(&mut record).map(|_| record),
+                    Some(Err(e)) => Err(e.to_string()),
+                    None => Err("Row decoded to no record".to_string()),
+                },
+            };
+            let item = decoded.map_err(|reason| {
+                let raw = &encoded[row.range];
+                UdoError::SourceDecode {
+                    position: row.line,
+                    raw: raw.strip_suffix(b"\n").unwrap_or(raw).to_vec(),
+                    reason,
+                }
+            });
+            self.buffer.push_back((item, row.line));
+        }
+        Ok(())
+    }
+
+    /// Converts encoded rows to records through an Arrow batch.
+    fn decode(&self, data: &[u8]) -> Result<Vec<OwnedValue>> {
+        if data.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut decoder = arrow_csv::ReaderBuilder::new(self.schema.clone())
+            .with_format(self.row_format.clone())
+            .with_batch_size(CSV_BATCH_ROWS)
+            .build_decoder();
+        let mut offset = 0;
+        while offset < data.len() {
+            let read = decoder.decode(&data[offset..]).map_err(UdoError::Arrow)?;
+            if read == 0 {
+                break;
+            }
+            offset += read;
+        }
+        let Some(batch) = decoder.flush().map_err(UdoError::Arrow)? else {
+            return Ok(Vec::new());
+        };
+
+        let mut buf = Vec::new();
+        let mut writer = LineDelimitedWriter::new(&mut buf);
+        writer.write(&batch).map_err(UdoError::Arrow)?;
+        writer.finish().map_err(UdoError::Arrow)?;
+        drop(writer);
+
+        let mut records = Vec::with_capacity(batch.num_rows());
+        for line in buf.split_mut(|b| *b == b'\n') {
+            if !line.is_empty() {
+                records.push(simd_json::to_owned_value(line).map_err(UdoError::JsonParse)?);
+            }
+        }
+        Ok(records)
+    }
+
+    fn normalize_temporal(&self, record: &mut OwnedValue) -> std::result::Result<(), String> {
+        let Some(obj) = record.as_object_mut() else {
+            return Ok(());
+        };
+        for (name, input_format, column_type) in &self.temporal_columns {
+            let Some(raw) = obj.get(name.as_str()).and_then(|v| v.as_str()) else {
+                continue;
+            };
+            let normalized = if *column_type == CsvColumnType::Date {
+                NaiveDate::parse_from_str(raw, input_format)
+                    .map(|d| d.format("%Y-%m-%d").to_string())
+            } else {
+                NaiveDateTime::parse_from_str(raw, input_format)
+                    .map(|d| d.format("%Y-%m-%dT%H:%M:%S%.f").to_string())
+            }
+            .map_err(|e| {
+                format!(
+                    "Column '{}': cannot parse '{}' with format '{}': {}",
+                    name, raw, input_format, e
+                )
+            })?;
+            obj.insert(name.clone(), OwnedValue::from(normalized));
+        }
+        Ok(())
+    }
+}
+
+fn csv_column_type(column_type: CsvColumnType) -> DataType {
+    match column_type {
+        CsvColumnType::Int64 => DataType::Int64,
+        CsvColumnType::Float64 => DataType::Float64,
+        CsvColumnType::Utf8 => DataType::Utf8,
+        CsvColumnType::Boolean => DataType::Boolean,
+        CsvColumnType::Date => DataType::Date32,
+        CsvColumnType::Timestamp => DataType::Timestamp(TimeUnit::Microsecond, None),
+    }
 }
 
 #[async_trait]
 impl InputSource for CsvSource {
     async fn next_record(&mut self) -> Result<Option<OwnedValue>> {
-        if let Some(record) = self.buffer.pop_front() {
-            return Ok(Some(record));
+        if self.buffer.is_empty() {
+            self.read_rows()?;
         }
-
-        match self.reader.next() {
-            Some(Ok(batch)) => {
-                let mut buf = Vec::new();
-                let mut writer = LineDelimitedWriter::new(&mut buf);
-                writer.write(&batch).map_err(UdoError::Arrow)?;
-                writer.finish().map_err(UdoError::Arrow)?;
-                drop(writer);
-
-                let s = String::from_utf8(buf).map_err(|e| UdoError::Unknown(e.to_string()))?;
-                for line in s.lines() {
-                    let mut line_bytes = line.as_bytes().to_vec();
-                    let owned = simd_json::to_owned_value(&mut line_bytes).map_err(UdoError::JsonParse)?;
-                    self.buffer.push_back(owned);
-                }
-                
-                self.buffer.pop_front().map(|r| Ok(Some(r))).unwrap_or(Ok(None))
+        match self.buffer.pop_front() {
+            Some((record, line)) => {
+                self.line = line;
+                record.map(Some)
             }
-            Some(Err(e)) => Err(UdoError::Arrow(e)),
             None => Ok(None),
         }
     }
+
+    fn position(&self) -> Option<u64> {
+        self.line
+    }
 }
 
 pub struct AvroSource {
@@ -139,6 +406,7 @@ impl InputSource for AvroSource {
 #[cfg(feature = "kafka")]
 pub struct KafkaSource {
     consumer: StreamConsumer,
+    last_offset: Option<i64>,
 }
 
 #[cfg(feature = "kafka")]
@@ -151,7 +419,10 @@ impl KafkaSource {
             .create()?;
 
         consumer.subscribe(&[topic])?;
-        Ok(Self { consumer })
+        Ok(Self {
+            consumer,
+            last_offset: None,
+        })
     }
 }
 
@@ -163,13 +434,15 @@ impl InputSource for KafkaSource {
             match self.consumer.recv().await {
                 Ok(msg) => {
                     let payload = msg.payload().unwrap_or_default();
-                    match parse_json(payload) {
-                        Ok(val) => return Ok(Some(val)),
-                        Err(e) => {
-                            eprintln!("Warning: Skipping corrupted JSON record from Kafka: {}", e);
-                            continue;
-                        }
-                    }
+                    self.last_offset = Some(msg.offset());
+                    return match parse_json(payload) {
+                        Ok(val) => Ok(Some(val)),
+                        Err(e) => Err(UdoError::SourceDecode {
+                            position: self.position(),
+                            raw: payload.to_vec(),
+                            reason: e.to_string(),
+                        }),
+                    };
                 }
                 Err(e) => {
                     eprintln!("Error receiving from Kafka: {}", e);
@@ -179,4 +452,8 @@ impl InputSource for KafkaSource {
             }
         }
     }
+
+    fn position(&self) -> Option<u64> {
+        self.last_offset.and_then(|o| u64::try_from(o).ok())
+    }
 }
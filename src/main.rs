@@ -1,14 +1,27 @@
 use anyhow::{bail, Context, Result};
 use clap::Parser;
 use simd_json::OwnedValue;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Instant;
 #[cfg(feature = "db")]
 use tracing::error;
 use tracing::info;
 
-use udo::core::pipeline::{InputSource, SinkFactory};
+#[cfg(any(feature = "semantic", feature = "ner"))]
+use udo::core::config::Precision;
+use udo::core::config::{
+    ClassifyMethod, KeySource, ModelsConfig, PiiAction, PipelineConfig, ProcessorConfig,
+    ProcessorEntry, RetryPolicy, SinkConfig, SourceConfig,
+};
+#[cfg(any(feature = "semantic", feature = "ner"))]
+use udo::core::model::{cache_dir, cached_models, ModelFiles, ModelPool, ModelSpec};
+use udo::core::pipeline::{DataProcessor, DlqSink, InputSource, SinkFactory};
+use udo::processors::classify::RecordClassifier;
+use udo::processors::firewall::counter::TokenCounter;
+use udo::processors::firewall::TokenFirewall;
+use udo::processors::language::{LanguageDetector, LanguageGate};
+use udo::processors::pii::tokenize::{Pseudonymizer, TokenVault};
 
 use clap::Subcommand;
 #[cfg(feature = "db")]
@@ -16,7 +29,10 @@ use udo::api::metrics::MetricsDb;
 #[cfg(feature = "server")]
 use udo::api::server::start_server;
 #[cfg(feature = "semantic")]
-use udo::processors::semantic::{IntentAnalyzer, SemanticProcessor};
+use udo::processors::semantic::{
+    load_dictionary, EmbeddingProcessor, IntentAnalyzer, SemanticProcessor, SemanticRowFilter,
+    DEFAULT_EMBEDDING_MODEL, DEFAULT_THRESHOLD,
+};
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -33,6 +49,128 @@ enum Commands {
     /// Start the Metrics API Server
     #[cfg(feature = "server")]
     Server,
+    /// Inspect and replay dead letter queues
+    Dlq {
+        #[command(subcommand)]
+        action: DlqCommands,
+    },
+    /// Work with pseudonymized PII
+    Pii {
+        #[command(subcommand)]
+        action: PiiCommands,
+    },
+    /// Download, verify and list the models processors load
+    #[cfg(any(feature = "semantic", feature = "ner"))]
+    Models {
+        #[command(subcommand)]
+        action: ModelCommands,
+    },
+}
+
+#[cfg(any(feature = "semantic", feature = "ner"))]
+#[derive(Subcommand, Debug)]
+enum ModelCommands {
+    /// Download the models of a pipeline config (or given IDs) into the cache
+    Pull {
+        /// Pipeline configuration (YAML) whose processors' models and pins to pull
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+
+        /// Extra model to pull, as `org/name` or `org/name@revision`
+        #[arg(short, long)]
+        model: Vec<String>,
+
+        /// Cache directory, overriding the config's `models.cache_dir`
+        #[arg(long)]
+        cache_dir: Option<PathBuf>,
+    },
+    /// Check cached models against the pinned checksums, without downloading
+    Verify {
+        /// Pipeline configuration (YAML) whose processors' models and pins to check
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+
+        /// Extra model to check, as `org/name` or `org/name@revision`
+        #[arg(short, long)]
+        model: Vec<String>,
+
+        /// Expected SHA-256 of `model.safetensors` for the `--model` entries
+        #[arg(long)]
+        sha256: Option<String>,
+
+        /// Cache directory, overriding the config's `models.cache_dir`
+        #[arg(long)]
+        cache_dir: Option<PathBuf>,
+    },
+    /// Write a model as GGUF with quantized linear layers, for `precision: gguf`
+    Quantize {
+        /// Model to quantize, as `org/name` or `org/name@revision`
+        #[arg(short, long)]
+        model: String,
+
+        /// Read the model from this directory instead of the hub
+        #[arg(long)]
+        model_path: Option<PathBuf>,
+
+        /// Block format of the linear layers: q8_0 or q4_0
+        #[arg(long, default_value = "q8_0")]
+        precision: Precision,
+
+        /// Directory to write config.json, tokenizer.json and model.gguf to
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Cache directory (default: the Hugging Face cache)
+        #[arg(long)]
+        cache_dir: Option<PathBuf>,
+    },
+    /// List the model snapshots in the cache
+    List {
+        /// Cache directory (default: the Hugging Face cache)
+        #[arg(long)]
+        cache_dir: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum PiiCommands {
+    /// Print the original values behind tokens recorded in a token vault
+    Reveal {
+        /// Token vault file written by a `pii_masker` with `vault` set
+        #[arg(long)]
+        vault: PathBuf,
+
+        /// Environment variable holding the pipeline key
+        #[arg(long, conflicts_with = "key_file")]
+        key_env: Option<String>,
+
+        /// File holding the pipeline key
+        #[arg(long)]
+        key_file: Option<PathBuf>,
+
+        /// Tokens to re-identify
+        #[arg(required = true)]
+        tokens: Vec<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum DlqCommands {
+    /// Feed the records of a DLQ file back through a pipeline config
+    Replay {
+        /// DLQ file written by a `file` dead letter queue
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Pipeline configuration (YAML); its source is replaced by the DLQ file
+        #[arg(short, long)]
+        config: PathBuf,
+
+        /// Output path or URL; defaults to the config's sink with `.replay` before the
+        /// extension, so the original job's output is kept
+        #[arg(short, long)]
+        output: Option<String>,
+    },
 }
 
 #[derive(Parser, Debug)]
@@ -65,10 +203,22 @@ struct RunArgs {
     #[arg(long, default_value_t = 0.85)]
     sim_threshold: f32,
 
-    /// PII masking mode (none, mask, hash)
+    /// Abort when more than this fraction (0.0-1.0) of records ends up in the DLQ
+    #[arg(long)]
+    max_error_rate: Option<f64>,
+
+    /// PII masking mode (none, mask, hash, tokenize, detect_only, tag)
     #[arg(long, default_value = "none")]
     pii_mode: String,
 
+    /// Environment variable holding the key for `hash` and `tokenize` PII modes
+    #[arg(long)]
+    pii_key_env: Option<String>,
+
+    /// File holding the key for `hash` and `tokenize` PII modes
+    #[arg(long)]
+    pii_key_file: Option<PathBuf>,
+
     /// Use NER for advanced PII detection (Name, Loc, Org)
     #[arg(long, default_value_t = false)]
     pii_ner: bool,
@@ -89,239 +239,198 @@ async fn main() -> Result<()> {
 
     let cli = Cli::parse();
 
-    #[cfg(feature = "server")]
-    if let Some(Commands::Server) = cli.command {
-        start_server("udo_metrics.duckdb")
-            .await
-            .context("Failed to start server")?;
-        return Ok(());
+    match cli.command {
+        #[cfg(feature = "server")]
+        Some(Commands::Server) => {
+            start_server("udo_metrics.duckdb")
+                .await
+                .context("Failed to start server")?;
+            return Ok(());
+        }
+        Some(Commands::Dlq {
+            action:
+                DlqCommands::Replay {
+                    input,
+                    config,
+                    output,
+                },
+        }) => return replay_dlq(input, config, output).await,
+        Some(Commands::Pii {
+            action:
+                PiiCommands::Reveal {
+                    vault,
+                    key_env,
+                    key_file,
+                    tokens,
+                },
+        }) => {
+            let key = KeySource {
+                env: key_env,
+                file: key_file,
+            };
+            return reveal_tokens(&vault, &key, &tokens);
+        }
+        #[cfg(any(feature = "semantic", feature = "ner"))]
+        Some(Commands::Models { action }) => return run_models(action),
+        None => {}
     }
 
     let args = cli.run_args;
 
     // Load config if provided, otherwise build from CLI args
-    let (source, processors, sink_factory, dlq, batch_size, input_for_schema) =
-        if let Some(config_path) = args.config {
-            info!(path = ?config_path, "Loading pipeline configuration from YAML");
-            let config_str =
-                std::fs::read_to_string(&config_path).context("Failed to read config file")?;
-            let config: udo::core::config::PipelineConfig =
-                serde_yaml::from_str(&config_str).context("Failed to parse YAML config")?;
-
-            let source: Box<dyn InputSource> = match config.source {
-                udo::core::config::SourceConfig::File { path } => {
-                    Box::new(udo::io::source::FileSource::new(path).await?)
-                }
-                udo::core::config::SourceConfig::Csv { path } => {
-                    Box::new(udo::io::source::CsvSource::new(path)?)
-                }
-                udo::core::config::SourceConfig::Avro { path } => {
-                    Box::new(udo::io::source::AvroSource::new(path)?)
-                }
-                #[cfg(feature = "kafka")]
-                udo::core::config::SourceConfig::Kafka {
-                    brokers,
-                    group_id,
-                    topic,
-                } => Box::new(udo::io::source::KafkaSource::new(
-                    &brokers, &group_id, &topic,
-                )?),
-            };
+    let (
+        name,
+        source,
+        processors,
+        sink_factory,
+        dlq,
+        batch_size,
+        error_rate,
+        input_for_schema,
+        report_path,
+    ) = if let Some(config_path) = args.config {
+        let config = load_config(&config_path)?;
+        let source = build_source(config.source).await?;
+        let procs = build_processors(config.processors, &config.models)?;
+        let report_path = report_path(&config.sink);
+        let sink_factory = build_sink_factory(config.sink);
+        let dlq = build_dlq(config.dlq)?;
 
-            let mut procs: Vec<Box<dyn udo::core::pipeline::DataProcessor>> = Vec::new();
-            for p_cfg in config.processors {
-                match p_cfg {
-                    udo::core::config::ProcessorConfig::PiiMasker {
-                        mode,
-                        use_ner: _use_ner,
-                        model_path: _model_path,
-                    } => {
-                        procs.push(Box::new(
-                            udo::processors::pii::PiiMasker::new(&mode)
-                                .map_err(|e| anyhow::anyhow!(e))?,
-                        ));
-                        #[cfg(feature = "ner")]
-                        if _use_ner {
-                            let ner_analyzer = udo::processors::ner::NerAnalyzer::new(_model_path)
-                                .map_err(|e| anyhow::anyhow!(e))?;
-                            procs.push(Box::new(udo::processors::pii::NerPiiMasker::new(
-                                ner_analyzer,
-                                &mode,
-                            )));
-                        }
-                    }
-                    #[cfg(feature = "semantic")]
-                    udo::core::config::ProcessorConfig::SemanticPruner {
-                        query,
-                        threshold,
-                        model_path,
-                    } => {
-                        let analyzer =
-                            IntentAnalyzer::new(model_path).map_err(|e| anyhow::anyhow!(e))?;
-                        procs.push(Box::new(SemanticProcessor::new(analyzer, query, threshold)));
-                    }
-                }
-            }
+        // For YAML, we skip Pass 1 schema inference for now or implement it based on source type
+        (
+            config.name,
+            source,
+            procs,
+            sink_factory,
+            dlq,
+            config.batch_size,
+            config
+                .max_error_rate
+                .map(|rate| (rate, config.error_rate_min_records)),
+            None,
+            report_path,
+        )
+    } else {
+        // Legacy CLI behavior
+        let input_path_str = args
+            .input
+            .context("Input is required if no config file provided")?;
+        let output_path = args
+            .output
+            .context("Output is required if no config file provided")?;
 
-            let sink_factory: SinkFactory = match config.sink {
-                udo::core::config::SinkConfig::File { path } => Box::new(move |s| {
-                    Ok(Box::new(
-                        udo::io::sink::ParquetSink::new(path.clone(), s)
-                            .map_err(|e| udo::UdoError::Pipeline(e.to_string()))?,
-                    ))
-                }),
-                udo::core::config::SinkConfig::Csv { path } => Box::new(move |_s| {
-                    Ok(Box::new(
-                        udo::io::sink::CsvSink::new(path.clone())
-                            .map_err(|e| udo::UdoError::Pipeline(e.to_string()))?,
-                    ))
-                }),
-                udo::core::config::SinkConfig::Avro { path } => Box::new(move |s| {
-                    Ok(Box::new(
-                        udo::io::sink::AvroSink::new(path.clone(), s)
-                            .map_err(|e| udo::UdoError::Pipeline(e.to_string()))?,
-                    ))
-                }),
-                #[cfg(feature = "cloud")]
-                udo::core::config::SinkConfig::Cloud { url } => {
-                    Box::new(move |s| {
-                        let url = url.clone();
-                        // We need to block here because sink_factory is synchronous in signature,
-                        // but CloudSink::new is async.
-                        // In a real generic pipeline, we might make the factory async or use a handle.
-                        // For CLI context, blocking is acceptable or we need to refactor factory trait.
-                        tokio::task::block_in_place(|| {
-                            tokio::runtime::Handle::current().block_on(async move {
-                                Ok(Box::new(
-                                    udo::io::sink::CloudSink::new(&url, s)
-                                        .await
-                                        .map_err(|e| udo::UdoError::Pipeline(e.to_string()))?,
-                                )
-                                    as Box<dyn udo::core::pipeline::OutputSink>)
-                            })
-                        })
-                    })
+        let source: Box<dyn InputSource> = if input_path_str.starts_with("kafka://") {
+            #[cfg(feature = "kafka")]
+            {
+                let parts: Vec<&str> = input_path_str
+                    .trim_start_matches("kafka://")
+                    .split('/')
+                    .collect();
+                if parts.len() < 3 {
+                    bail!("Invalid Kafka URL");
                 }
-            };
-
-            let dlq: Option<Box<dyn udo::core::pipeline::DlqSink>> =
-                if let Some(dlq_cfg) = config.dlq {
-                    match dlq_cfg {
-                        udo::core::config::SinkConfig::File { path } => Some(Box::new(
-                            udo::io::dlq::FileDlq::new(path).map_err(|e| anyhow::anyhow!(e))?,
-                        )),
-                        #[cfg(feature = "cloud")]
-                        udo::core::config::SinkConfig::Cloud { url } => Some(Box::new(
-                            udo::io::dlq::CloudDlq::new(&url).map_err(|e| anyhow::anyhow!(e))?,
-                        )),
-                        _ => None, // CSV/Avro DLQ not supported yet
-                    }
-                } else {
-                    None
-                };
-
-            // For YAML, we skip Pass 1 schema inference for now or implement it based on source type
-            (source, procs, sink_factory, dlq, config.batch_size, None)
+                Box::new(udo::io::source::KafkaSource::new(
+                    parts[0], parts[1], parts[2],
+                )?)
+            }
+            #[cfg(not(feature = "kafka"))]
+            bail!("Kafka feature not enabled")
         } else {
-            // Legacy CLI behavior
-            let input_path_str = args
-                .input
-                .context("Input is required if no config file provided")?;
-            let output_path = args
-                .output
-                .context("Output is required if no config file provided")?;
-
-            let source: Box<dyn InputSource> = if input_path_str.starts_with("kafka://") {
-                #[cfg(feature = "kafka")]
-                {
-                    let parts: Vec<&str> = input_path_str
-                        .trim_start_matches("kafka://")
-                        .split('/')
-                        .collect();
-                    if parts.len() < 3 {
-                        bail!("Invalid Kafka URL");
-                    }
-                    Box::new(udo::io::source::KafkaSource::new(
-                        parts[0], parts[1], parts[2],
-                    )?)
-                }
-                #[cfg(not(feature = "kafka"))]
-                bail!("Kafka feature not enabled")
-            } else {
-                Box::new(udo::io::source::FileSource::new(PathBuf::from(&input_path_str)).await?)
-            };
+            Box::new(udo::io::source::FileSource::new(PathBuf::from(&input_path_str)).await?)
+        };
 
-            let mut procs: Vec<Box<dyn udo::core::pipeline::DataProcessor>> = Vec::new();
-            if args.pii_mode != "none" {
-                procs.push(Box::new(
-                    udo::processors::pii::PiiMasker::new(&args.pii_mode)
-                        .map_err(|e| anyhow::anyhow!(e))?,
-                ));
-                #[cfg(feature = "ner")]
-                if args.pii_ner {
-                    let ner_analyzer =
-                        udo::processors::ner::NerAnalyzer::new(args.ner_model_path.clone())
-                            .map_err(|e| anyhow::anyhow!(e))?;
-                    procs.push(Box::new(udo::processors::pii::NerPiiMasker::new(
-                        ner_analyzer,
-                        &args.pii_mode,
-                    )));
+        let mut procs: Vec<Box<dyn DataProcessor>> = Vec::new();
+        if args.pii_mode != "none" {
+            let key =
+                (args.pii_key_env.is_some() || args.pii_key_file.is_some()).then(|| KeySource {
+                    env: args.pii_key_env.clone(),
+                    file: args.pii_key_file.clone(),
+                });
+            let needs_key = matches!(
+                PiiAction::from_mode(&args.pii_mode),
+                PiiAction::Hash | PiiAction::Tokenize
+            );
+            let pseudonymizer = build_pseudonymizer(key.as_ref(), None, needs_key)?;
+            let mut masker = udo::processors::pii::PiiMasker::new(&args.pii_mode)
+                .map_err(|e| anyhow::anyhow!(e))?;
+            if let Some(p) = &pseudonymizer {
+                masker = masker.with_pseudonymizer(p.clone());
+            }
+            procs.push(Box::new(masker));
+            #[cfg(feature = "ner")]
+            if args.pii_ner {
+                let ner_analyzer =
+                    udo::processors::ner::NerAnalyzer::new(args.ner_model_path.clone())
+                        .map_err(|e| anyhow::anyhow!(e))?;
+                let mut masker =
+                    udo::processors::pii::NerPiiMasker::new(ner_analyzer, &args.pii_mode);
+                if let Some(p) = pseudonymizer {
+                    masker = masker.with_pseudonymizer(p);
                 }
+                procs.push(Box::new(masker));
             }
+        }
 
-            #[cfg(feature = "semantic")]
-            if let Some(query) = &args.query {
-                let analyzer = IntentAnalyzer::new(args.semantic_model_path.clone())
-                    .map_err(|e| anyhow::anyhow!(e))?;
-                procs.push(Box::new(SemanticProcessor::new(
-                    analyzer,
-                    query.clone(),
-                    args.sim_threshold,
-                )));
-            }
+        #[cfg(feature = "semantic")]
+        if let Some(query) = &args.query {
+            let analyzer = IntentAnalyzer::new(args.semantic_model_path.clone())
+                .map_err(|e| anyhow::anyhow!(e))?;
+            procs.push(Box::new(SemanticProcessor::new(
+                analyzer,
+                query.clone(),
+                args.sim_threshold,
+            )));
+        }
 
-            let out_path_str = output_path.to_string_lossy().to_string();
-            let out_path_clone = out_path_str.clone();
-            let sink_factory: SinkFactory = Box::new(move |s| {
-                #[cfg(feature = "cloud")]
+        let out_path_str = output_path.to_string_lossy().to_string();
+        let out_path_clone = out_path_str.clone();
+        let report_path = report_path_for(&out_path_str);
+        let sink_factory: SinkFactory = Box::new(move |s| {
+            #[cfg(feature = "cloud")]
+            {
+                if out_path_clone.starts_with("s3://")
+                    || out_path_clone.starts_with("gs://")
+                    || out_path_clone.starts_with("az://")
                 {
-                    if out_path_clone.starts_with("s3://")
-                        || out_path_clone.starts_with("gs://")
-                        || out_path_clone.starts_with("az://")
-                    {
-                        let url = out_path_clone.clone();
-                        return tokio::task::block_in_place(|| {
-                            tokio::runtime::Handle::current().block_on(async move {
-                                Ok(Box::new(
-                                    udo::io::sink::CloudSink::new(&url, s)
-                                        .await
-                                        .map_err(|e| udo::UdoError::Pipeline(e.to_string()))?,
-                                )
-                                    as Box<dyn udo::core::pipeline::OutputSink>)
-                            })
-                        });
-                    }
+                    let url = out_path_clone.clone();
+                    return tokio::task::block_in_place(|| {
+                        tokio::runtime::Handle::current().block_on(async move {
+                            Ok(Box::new(
+                                udo::io::sink::CloudSink::new(&url, s)
+                                    .await
+                                    .map_err(|e| udo::UdoError::Pipeline(e.to_string()))?,
+                            )
+                                as Box<dyn udo::core::pipeline::OutputSink>)
+                        })
+                    });
                 }
-                Ok(Box::new(
-                    udo::io::sink::ParquetSink::new(PathBuf::from(&out_path_clone), s)
-                        .map_err(|e| udo::UdoError::Pipeline(e.to_string()))?,
-                ))
-            });
-
-            let schema_input = if !input_path_str.starts_with("kafka://") {
-                Some(input_path_str)
-            } else {
-                None
-            };
-            (
-                source,
-                procs,
-                sink_factory,
-                None,
-                args.batch_size,
-                schema_input,
-            )
+            }
+            Ok(Box::new(
+                udo::io::sink::ParquetSink::new(PathBuf::from(&out_path_clone), s)
+                    .map_err(|e| udo::UdoError::Pipeline(e.to_string()))?,
+            ))
+        });
+
+        let schema_input = if !input_path_str.starts_with("kafka://") {
+            Some(input_path_str)
+        } else {
+            None
         };
+        (
+            None,
+            source,
+            procs
+                .into_iter()
+                .map(|p| (p, RetryPolicy::default()))
+                .collect(),
+            sink_factory,
+            None,
+            args.batch_size,
+            args.max_error_rate.map(|rate| (rate, 1000)),
+            schema_input,
+            report_path,
+        )
+    };
 
     let start_time = Instant::now();
 
@@ -343,10 +452,12 @@ async fn main() -> Result<()> {
         let mut infer_source = udo::io::source::FileSource::new(PathBuf::from(&inp)).await?;
         let mut schema_rows = Vec::new();
         while schema_rows.len() < args.scan_rows {
-            if let Some(record) = infer_source.next_record().await? {
-                schema_rows.push(record);
-            } else {
-                break;
+            match infer_source.next_record().await {
+                Ok(Some(record)) => schema_rows.push(record),
+                Ok(None) => break,
+                // Undecodable lines are dead-lettered by the runner in pass 2.
+                Err(udo::UdoError::SourceDecode { .. }) => continue,
+                Err(e) => return Err(e.into()),
             }
         }
         if !schema_rows.is_empty() {
@@ -359,15 +470,21 @@ async fn main() -> Result<()> {
     }
 
     let mut runner = udo::PipelineRunner::new(source, batch_size);
+    if let Some(name) = name {
+        runner.set_name(name);
+    }
     if let Some(d) = dlq {
         runner.set_dlq(d);
     }
+    if let Some((rate, min_records)) = error_rate {
+        runner.set_max_error_rate(rate, min_records);
+    }
     if schema.is_none() {
         runner.set_warmup_rows(100);
     }
 
-    for p in processors {
-        runner.add_processor(p);
+    for (p, retry) in processors {
+        runner.add_processor_with_retry(p, retry);
     }
 
     runner.set_sink_factory(move |s| {
@@ -379,13 +496,860 @@ async fn main() -> Result<()> {
 
     let elapsed = start_time.elapsed();
     info!(duration = ?elapsed, "Job completed");
+    write_reports(&runner, &report_path)?;
 
     #[cfg(feature = "db")]
-    if let Some(db) = metrics_db
-        && let Err(e) = db.record_metric(0, elapsed.as_millis() as f64, 0, "pipeline")
+    if let Some(db) = metrics_db {
+        let usage = token_usage(runner.processor_metrics());
+        let tokens_saved: u64 = usage
+            .iter()
+            .map(|u| u.tokens_before.saturating_sub(u.tokens_after))
+            .sum();
+        if let Err(e) = db.record_metric(
+            0,
+            elapsed.as_millis() as f64,
+            tokens_saved as usize,
+            "pipeline",
+        ) {
+            error!(error = %e, "Failed to record metrics");
+        }
+        if let Err(e) = db.record_processor_metrics(runner.processor_metrics()) {
+            error!(error = %e, "Failed to record processor metrics");
+        }
+        for u in &usage {
+            if let Err(e) =
+                db.record_token_usage(&u.processor, u.records, u.tokens_before, u.tokens_after)
+            {
+                error!(error = %e, "Failed to record token usage");
+            }
+        }
+        for report in runner.processor_reports() {
+            if let Err(e) = db.record_report(&report.processor, &report.report.to_string()) {
+                error!(error = %e, "Failed to record processor report");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Token counts reported by one processor, e.g. a token firewall.
+#[cfg(feature = "db")]
+struct TokenUsage {
+    processor: String,
+    records: u64,
+    tokens_before: u64,
+    tokens_after: u64,
+}
+
+/// Collects processors reporting both `tokens_before` and `tokens_after` counters.
+#[cfg(feature = "db")]
+fn token_usage(metrics: &[(String, u64)]) -> Vec<TokenUsage> {
+    let counter = |key: String| metrics.iter().find(|(k, _)| *k == key).map(|(_, v)| *v);
+    metrics
+        .iter()
+        .filter_map(|(key, before)| {
+            let processor = key.strip_suffix(".tokens_before")?;
+            Some(TokenUsage {
+                processor: processor.to_string(),
+                records: counter(format!("{}.records", processor)).unwrap_or(0),
+                tokens_before: *before,
+                tokens_after: counter(format!("{}.tokens_after", processor))?,
+            })
+        })
+        .collect()
+}
+
+fn load_config(config_path: &Path) -> Result<PipelineConfig> {
+    info!(path = ?config_path, "Loading pipeline configuration from YAML");
+    let config_str = std::fs::read_to_string(config_path).context("Failed to read config file")?;
+    serde_yaml::from_str(&config_str).context("Failed to parse YAML config")
+}
+
+async fn build_source(source: SourceConfig) -> Result<Box<dyn InputSource>> {
+    Ok(match source {
+        SourceConfig::File { path } => Box::new(udo::io::source::FileSource::new(path).await?),
+        SourceConfig::Csv {
+            path,
+            options,
+            schema,
+            infer_schema_rows,
+        } => Box::new(udo::io::source::CsvSource::new(
+            path,
+            &options,
+            schema.as_deref(),
+            infer_schema_rows,
+        )?),
+        SourceConfig::Avro { path } => Box::new(udo::io::source::AvroSource::new(path)?),
+        #[cfg(feature = "kafka")]
+        SourceConfig::Kafka {
+            brokers,
+            group_id,
+            topic,
+        } => Box::new(udo::io::source::KafkaSource::new(
+            &brokers, &group_id, &topic,
+        )?),
+    })
+}
+
+fn build_processors(
+    entries: Vec<ProcessorEntry>,
+    _models: &ModelsConfig,
+) -> Result<Vec<(Box<dyn DataProcessor>, RetryPolicy)>> {
+    // Processors asking for the same model share one loaded copy.
+    #[cfg(any(feature = "semantic", feature = "ner"))]
+    let pool = ModelPool::new(_models);
+    let mut staged = Vec::new();
+    // Where the latest language detector writes, for entries restricted to languages.
+    let mut language_field: Option<String> = None;
+    for entry in entries {
+        let mut procs: Vec<Box<dyn DataProcessor>> = Vec::new();
+        match entry.processor {
+            ProcessorConfig::PiiMasker {
+                mode,
+                use_ner: _use_ner,
+                model_path: _model_path,
+                detectors,
+                disabled_detectors,
+                templates,
+                key,
+                vault,
+                policy,
+                ner: _ner,
+            } => {
+                let templates = udo::processors::pii::redact::RedactionTemplates::new(templates);
+                let policy = udo::processors::pii::policy::FieldPolicy::compile(&policy, &mode)
+                    .map_err(|e| anyhow::anyhow!(e))?;
+                let entity_actions = udo::processors::pii::entities::EntityRules::new(
+                    _ner.entities.clone(),
+                    _ner.min_score,
+                )
+                .map_err(|e| anyhow::anyhow!(e))?;
+                let needs_key = [PiiAction::Hash, PiiAction::Tokenize]
+                    .into_iter()
+                    .any(|a| policy.uses(a) || (_use_ner && entity_actions.uses(a)));
+                let pseudonymizer = build_pseudonymizer(key.as_ref(), vault, needs_key)?;
+                let registry = udo::processors::pii::detectors::DetectorRegistry::select(
+                    detectors.as_deref(),
+                    &disabled_detectors,
+                )
+                .map_err(|e| anyhow::anyhow!(e))?;
+                let mut masker = udo::processors::pii::PiiMasker::with_detectors(&mode, registry)
+                    .with_templates(templates.clone())
+                    .with_policy(policy.clone());
+                if let Some(p) = &pseudonymizer {
+                    masker = masker.with_pseudonymizer(p.clone());
+                }
+                procs.push(Box::new(masker));
+                #[cfg(feature = "ner")]
+                if _use_ner {
+                    let spec = ModelSpec::new(
+                        _ner.model
+                            .as_deref()
+                            .unwrap_or(udo::processors::ner::DEFAULT_NER_MODEL),
+                    )
+                    .with_revision(_ner.revision)
+                    .with_path(_model_path)
+                    .with_settings(pool.settings())
+                    .map_err(|e| anyhow::anyhow!(e))?;
+                    let ner_analyzer = udo::processors::ner::NerAnalyzer::from_pool(&pool, &spec)
+                        .and_then(|a| {
+                            let max_tokens = _ner.max_tokens.unwrap_or(a.max_tokens());
+                            let stride = _ner
+                                .stride
+                                .unwrap_or(udo::processors::ner::default_stride(max_tokens));
+                            a.with_window(max_tokens, stride)
+                        })
+                        .map_err(|e| anyhow::anyhow!(e))?
+                        .with_batch_size(_ner.batch_size);
+                    let mut masker = udo::processors::pii::NerPiiMasker::new(ner_analyzer, &mode)
+                        .with_templates(templates)
+                        .with_policy(policy)
+                        .with_entities(entity_actions);
+                    if let Some(p) = pseudonymizer {
+                        masker = masker.with_pseudonymizer(p);
+                    }
+                    procs.push(Box::new(masker));
+                }
+            }
+            ProcessorConfig::SecretScanner {
+                rules,
+                disabled_rules,
+                redaction,
+                rule_redaction,
+                entropy_threshold,
+            } => {
+                procs.push(Box::new(
+                    udo::processors::secrets::SecretScanner::select(
+                        rules.as_deref(),
+                        &disabled_rules,
+                        redaction,
+                        &rule_redaction,
+                        entropy_threshold,
+                    )
+                    .map_err(|e| anyhow::anyhow!(e))?,
+                ));
+            }
+            ProcessorConfig::TokenFirewall {
+                tokenizer,
+                max_tokens_per_record,
+                max_tokens_per_batch,
+                priority,
+                overflow,
+            } => {
+                let counter =
+                    TokenCounter::from_config(&tokenizer).map_err(|e| anyhow::anyhow!(e))?;
+                let mut firewall = TokenFirewall::new(counter)
+                    .with_priority(priority)
+                    .with_overflow(overflow);
+                if let Some(max) = max_tokens_per_record {
+                    firewall = firewall.with_record_budget(max);
+                }
+                if let Some(max) = max_tokens_per_batch {
+                    firewall = firewall.with_batch_budget(max);
+                }
+                procs.push(Box::new(firewall));
+            }
+            #[cfg(feature = "semantic")]
+            ProcessorConfig::SemanticPruner {
+                query,
+                threshold,
+                relative_threshold,
+                top_k,
+                always_keep,
+                never_keep,
+                fail_if_empty,
+                model_path,
+                cache_dir,
+                batch_size,
+                descriptions,
+                dictionary,
+                sample_values,
+                lexical_weight,
+            } => {
+                let analyzer = build_embedder(model_path, cache_dir, batch_size, &pool)?;
+                let mut entries = match dictionary {
+                    Some(path) => load_dictionary(path).map_err(|e| anyhow::anyhow!(e))?,
+                    None => std::collections::HashMap::new(),
+                };
+                entries.extend(descriptions);
+                if let Some(column) = always_keep.iter().find(|c| never_keep.contains(c)) {
+                    bail!(
+                        "semantic_pruner column '{}' is in both always_keep and never_keep",
+                        column
+                    );
+                }
+                let threshold = match threshold {
+                    None if top_k.is_none() && relative_threshold.is_none() => {
+                        Some(DEFAULT_THRESHOLD)
+                    }
+                    threshold => threshold,
+                };
+                let mut processor = SemanticProcessor::new(analyzer, query, threshold)
+                    .with_descriptions(entries)
+                    .with_sample_values(sample_values)
+                    .with_lexical_weight(lexical_weight)
+                    .with_always_keep(always_keep)
+                    .with_never_keep(never_keep)
+                    .with_fail_if_empty(fail_if_empty);
+                if let Some(fraction) = relative_threshold {
+                    processor = processor.with_relative_threshold(fraction);
+                }
+                if let Some(k) = top_k {
+                    processor = processor.with_top_k(k);
+                }
+                procs.push(Box::new(processor));
+            }
+            #[cfg(feature = "semantic")]
+            ProcessorConfig::Embedding {
+                fields,
+                suffix,
+                model_path,
+                cache_dir,
+                batch_size,
+            } => {
+                if fields.is_empty() {
+                    bail!("embedding processor requires at least one field");
+                }
+                let analyzer = build_embedder(model_path, cache_dir, batch_size, &pool)?;
+                procs.push(Box::new(
+                    EmbeddingProcessor::new(analyzer, fields).with_suffix(&suffix),
+                ));
+            }
+            ProcessorConfig::LanguageDetector {
+                fields,
+                min_confidence,
+                language_field: field,
+                confidence_field,
+            } => {
+                if fields.is_empty() {
+                    bail!("language_detector requires at least one field");
+                }
+                let detector = LanguageDetector::new(fields)
+                    .with_min_confidence(min_confidence)
+                    .with_language_field(field)
+                    .with_confidence_field(confidence_field);
+                language_field = Some(detector.language_field().to_string());
+                procs.push(Box::new(detector));
+            }
+            ProcessorConfig::Classifier {
+                fields,
+                labels,
+                method,
+                threshold,
+                fallback,
+                label_field,
+                score_field,
+                #[cfg(feature = "semantic")]
+                model_path,
+                #[cfg(feature = "semantic")]
+                cache_dir,
+                #[cfg(feature = "semantic")]
+                batch_size,
+            } => {
+                let classifier = match method {
+                    ClassifyMethod::Keywords => RecordClassifier::from_keywords(labels, fields),
+                    #[cfg(feature = "semantic")]
+                    ClassifyMethod::Embedding => {
+                        let analyzer = build_embedder(model_path, cache_dir, batch_size, &pool)?;
+                        RecordClassifier::from_embeddings(analyzer, labels, fields)
+                    }
+                    #[cfg(not(feature = "semantic"))]
+                    ClassifyMethod::Embedding => {
+                        bail!("classifier method `embedding` requires the semantic feature")
+                    }
+                };
+                let mut classifier = classifier
+                    .map_err(|e| anyhow::anyhow!(e))?
+                    .with_label_field(label_field)
+                    .with_score_field(score_field);
+                if let Some(threshold) = threshold {
+                    classifier = classifier.with_threshold(threshold);
+                }
+                if let Some(fallback) = fallback {
+                    classifier = classifier.with_fallback(fallback);
+                }
+                procs.push(Box::new(classifier));
+            }
+            #[cfg(feature = "semantic")]
+            ProcessorConfig::SemanticRowFilter {
+                query,
+                fields,
+                threshold,
+                top_n,
+                score_field,
+                model_path,
+                cache_dir,
+                batch_size,
+            } => {
+                if fields.is_empty() {
+                    bail!("semantic_row_filter requires at least one field");
+                }
+                let analyzer = build_embedder(model_path, cache_dir, batch_size, &pool)?;
+                let mut filter =
+                    SemanticRowFilter::new(analyzer, query, fields).with_score_field(score_field);
+                if let Some(threshold) = threshold {
+                    filter = filter.with_threshold(threshold);
+                }
+                if let Some(n) = top_n {
+                    filter = filter.with_top_n(n);
+                }
+                procs.push(Box::new(filter));
+            }
+        }
+        if !entry.languages.is_empty() {
+            let Some(field) = &language_field else {
+                bail!(
+                    "Processor restricted to languages {:?} needs a language_detector before it",
+                    entry.languages
+                );
+            };
+            procs = procs
+                .into_iter()
+                .map(|p| {
+                    Box::new(LanguageGate::new(p, entry.languages.clone(), field.clone()))
+                        as Box<dyn DataProcessor>
+                })
+                .collect();
+        }
+        staged.extend(procs.into_iter().map(|p| (p, entry.retry.clone())));
+    }
+    #[cfg(any(feature = "semantic", feature = "ner"))]
+    if !pool.is_empty() {
+        info!(
+            models = %pool.len(),
+            threads = %pool.inference().threads(),
+            "Models loaded for inference"
+        );
+    }
+    Ok(staged)
+}
+
+/// The embedding model of a semantic processor, with the pipeline's model settings.
+#[cfg(feature = "semantic")]
+fn build_embedder(
+    model_path: Option<PathBuf>,
+    cache_dir: Option<PathBuf>,
+    batch_size: usize,
+    pool: &ModelPool,
+) -> Result<IntentAnalyzer> {
+    let spec = ModelSpec::new(DEFAULT_EMBEDDING_MODEL)
+        .with_path(model_path)
+        .with_settings(pool.settings())
+        .map_err(|e| anyhow::anyhow!(e))?;
+    let mut analyzer = IntentAnalyzer::from_pool(pool, &spec)
+        .map_err(|e| anyhow::anyhow!(e))?
+        .with_batch_size(batch_size);
+    if let Some(dir) = cache_dir {
+        analyzer = analyzer.with_cache(dir).map_err(|e| anyhow::anyhow!(e))?;
+    }
+    Ok(analyzer)
+}
+
+/// Keyed pseudonymizer shared by the regex and NER maskers of one processor entry, so
+/// both write to the same token vault. `needs_key` is set when the masker hashes or
+/// tokenizes: without a key that output could not be joined across runs.
+fn build_pseudonymizer(
+    key: Option<&KeySource>,
+    vault: Option<PathBuf>,
+    needs_key: bool,
+) -> Result<Option<Arc<Pseudonymizer>>> {
+    let Some(key) = key else {
+        if needs_key {
+            bail!("PII masking with 'hash' or 'tokenize' requires a key");
+        }
+        if vault.is_some() {
+            bail!("pii_masker 'vault' requires a 'key'");
+        }
+        return Ok(None);
+    };
+    let key = key.load().map_err(|e| anyhow::anyhow!(e))?;
+    let mut pseudonymizer = Pseudonymizer::new(&key);
+    if let Some(path) = vault {
+        pseudonymizer =
+            pseudonymizer.with_vault(TokenVault::open(path, &key).map_err(|e| anyhow::anyhow!(e))?);
+    }
+    Ok(Some(Arc::new(pseudonymizer)))
+}
+
+fn build_sink_factory(sink: SinkConfig) -> SinkFactory {
+    match sink {
+        SinkConfig::File { path } => Box::new(move |s| {
+            Ok(Box::new(
+                udo::io::sink::ParquetSink::new(path.clone(), s)
+                    .map_err(|e| udo::UdoError::Pipeline(e.to_string()))?,
+            ))
+        }),
+        SinkConfig::Csv { path, options } => Box::new(move |_s| {
+            Ok(Box::new(
+                udo::io::sink::CsvSink::new(path.clone(), &options)
+                    .map_err(|e| udo::UdoError::Pipeline(e.to_string()))?,
+            ))
+        }),
+        SinkConfig::Avro { path } => Box::new(move |s| {
+            Ok(Box::new(
+                udo::io::sink::AvroSink::new(path.clone(), s)
+                    .map_err(|e| udo::UdoError::Pipeline(e.to_string()))?,
+            ))
+        }),
+        #[cfg(feature = "cloud")]
+        SinkConfig::Cloud { url, .. } => {
+            Box::new(move |s| {
+                let url = url.clone();
+                // We need to block here because sink_factory is synchronous in signature,
+                // but CloudSink::new is async.
+                // In a real generic pipeline, we might make the factory async or use a handle.
+                // For CLI context, blocking is acceptable or we need to refactor factory trait.
+                tokio::task::block_in_place(|| {
+                    tokio::runtime::Handle::current().block_on(async move {
+                        Ok(Box::new(
+                            udo::io::sink::CloudSink::new(&url, s)
+                                .await
+                                .map_err(|e| udo::UdoError::Pipeline(e.to_string()))?,
+                        )
+                            as Box<dyn udo::core::pipeline::OutputSink>)
+                    })
+                })
+            })
+        }
+    }
+}
+
+/// Where processor reports are written: next to a local output, or in the working
+/// directory when the output is an object store URL.
+fn report_path(sink: &SinkConfig) -> PathBuf {
+    match sink {
+        SinkConfig::File { path } | SinkConfig::Csv { path, .. } | SinkConfig::Avro { path } => {
+            report_path_for(&path.to_string_lossy())
+        }
+        #[cfg(feature = "cloud")]
+        SinkConfig::Cloud { url, .. } => report_path_for(url),
+    }
+}
+
+fn report_path_for(output: &str) -> PathBuf {
+    if output.contains("://") {
+        let file = output.rsplit('/').find(|s| !s.is_empty()).unwrap_or("udo");
+        PathBuf::from(format!("{}.report.json", file))
+    } else {
+        PathBuf::from(format!("{}.report.json", output))
+    }
+}
+
+/// Writes the reports of the last run, if any processor produced one, as a JSON array.
+fn write_reports(runner: &udo::PipelineRunner, path: &Path) -> Result<()> {
+    let reports = runner.processor_reports();
+    if reports.is_empty() {
+        return Ok(());
+    }
+    let body = serde_json::to_string_pretty(reports)?;
+    std::fs::write(path, body)
+        .with_context(|| format!("Failed to write report to {}", path.display()))?;
+    info!(path = ?path, reports = %reports.len(), "Processor reports written");
+    Ok(())
+}
+
+fn build_dlq(dlq: Option<SinkConfig>) -> Result<Option<Box<dyn DlqSink>>> {
+    let Some(dlq_cfg) = dlq else {
+        return Ok(None);
+    };
+    Ok(match dlq_cfg {
+        SinkConfig::File { path } => Some(Box::new(
+            udo::io::dlq::FileDlq::new(path).map_err(|e| anyhow::anyhow!(e))?,
+        )),
+        #[cfg(feature = "cloud")]
+        SinkConfig::Cloud { url, rotation } => Some(Box::new(
+            udo::io::dlq::CloudDlq::with_rotation(&url, &rotation)
+                .map_err(|e| anyhow::anyhow!(e))?,
+        )),
+        _ => None, // CSV/Avro DLQ not supported yet
+    })
+}
+
+/// Runs a `models` subcommand: pulls, verifies, lists or quantizes cached models.
+#[cfg(any(feature = "semantic", feature = "ner"))]
+fn run_models(action: ModelCommands) -> Result<()> {
+    match action {
+        ModelCommands::Pull {
+            config,
+            model,
+            cache_dir,
+        } => {
+            let specs = model_specs(config.as_deref(), &model, None, cache_dir)?;
+            let mut pins = Vec::new();
+            for spec in specs {
+                let spec = ModelSpec {
+                    offline: false,
+                    ..spec
+                };
+                let files = ModelFiles::fetch(&spec).map_err(|e| anyhow::anyhow!(e))?;
+                let sha256 = udo::core::model::sha256_file(&files.weights)
+                    .map_err(|e| anyhow::anyhow!(e))?;
+                let commit = files.snapshot();
+                println!(
+                    "pulled {} -> {} (sha256 {})",
+                    spec,
+                    commit.as_deref().unwrap_or("?"),
+                    sha256
+                );
+                pins.push((spec.id, commit.or(spec.revision), sha256));
+            }
+            println!("\n# Pins for the pipeline config:\nmodels:\n  pins:");
+            for (id, revision, sha256) in pins {
+                println!("    {}:", id);
+                if let Some(revision) = revision {
+                    println!("      revision: {}", revision);
+                }
+                println!("      sha256: {}", sha256);
+            }
+            Ok(())
+        }
+        ModelCommands::Verify {
+            config,
+            model,
+            sha256,
+            cache_dir,
+        } => {
+            let specs = model_specs(config.as_deref(), &model, sha256, cache_dir)?;
+            let mut failed = 0;
+            for spec in specs {
+                let spec = ModelSpec {
+                    offline: true,
+                    ..spec
+                };
+                match ModelFiles::fetch(&spec) {
+                    Ok(_) if spec.sha256.is_some() => println!("ok       {}", spec),
+                    Ok(files) => {
+                        let actual = udo::core::model::sha256_file(&files.weights)
+                            .map_err(|e| anyhow::anyhow!(e))?;
+                        println!("unpinned {} (sha256 {})", spec, actual);
+                    }
+                    Err(e) => {
+                        failed += 1;
+                        println!("FAILED   {}: {}", spec, e);
+                    }
+                }
+            }
+            if failed > 0 {
+                bail!("{} model(s) failed verification", failed);
+            }
+            Ok(())
+        }
+        ModelCommands::Quantize {
+            model,
+            model_path,
+            precision,
+            output,
+            cache_dir,
+        } => {
+            let (id, revision) = match model.split_once('@') {
+                Some((id, revision)) => (id, Some(revision.to_string())),
+                None => (model.as_str(), None),
+            };
+            let mut spec = ModelSpec::new(id)
+                .with_revision(revision)
+                .with_path(model_path);
+            spec.cache_dir = cache_dir;
+            let files = ModelFiles::fetch(&spec).map_err(|e| anyhow::anyhow!(e))?;
+
+            std::fs::create_dir_all(&output)?;
+            std::fs::write(
+                output.join("config.json"),
+                files.read_config().map_err(|e| anyhow::anyhow!(e))?,
+            )?;
+            std::fs::copy(&files.tokenizer, output.join("tokenizer.json"))?;
+            let weights = output.join(Precision::Gguf.weights_file());
+            let size = udo::core::bert::write_gguf(&files.weights, precision, &weights)
+                .map_err(|e| anyhow::anyhow!(e))?;
+            let sha256 = udo::core::model::sha256_file(&weights).map_err(|e| anyhow::anyhow!(e))?;
+            println!(
+                "quantized {} to {} -> {} ({} bytes, was {} bytes; sha256 {})",
+                spec,
+                precision,
+                weights.display(),
+                size,
+                std::fs::metadata(&files.weights)?.len(),
+                sha256
+            );
+            println!(
+                "\n# Load it with `model_path: {}` and:\nmodels:\n  precision: gguf",
+                output.display()
+            );
+            Ok(())
+        }
+        ModelCommands::List { cache_dir: dir } => {
+            let dir = cache_dir(dir.as_deref());
+            let models = cached_models(&dir).map_err(|e| anyhow::anyhow!(e))?;
+            if models.is_empty() {
+                println!("No models cached in {}", dir.display());
+            }
+            for model in models {
+                println!(
+                    "{}  {}  [{}]  {} files, {} bytes",
+                    model.id,
+                    model.commit,
+                    model.refs.join(", "),
+                    model.files.len(),
+                    model.size
+                );
+            }
+            Ok(())
+        }
+    }
+}
+
+/// The models a `models` command works on: those the config's processors load, its
+/// pins, and `extra` IDs (`org/name[@revision]`, checked against `sha256` if given).
+#[cfg(any(feature = "semantic", feature = "ner"))]
+fn model_specs(
+    config: Option<&Path>,
+    extra: &[String],
+    sha256: Option<String>,
+    cache_dir: Option<PathBuf>,
+) -> Result<Vec<ModelSpec>> {
+    let mut specs: Vec<ModelSpec> = Vec::new();
+    let mut settings = ModelsConfig::default();
+    if let Some(path) = config {
+        let config = load_config(path)?;
+        settings = config.models.clone();
+        specs.extend(config_models(&config));
+        for id in settings.pins.keys() {
+            specs.push(ModelSpec::new(id.clone()));
+        }
+    }
+    for entry in extra {
+        let (id, revision) = match entry.split_once('@') {
+            Some((id, revision)) => (id, Some(revision.to_string())),
+            None => (entry.as_str(), None),
+        };
+        let mut spec = ModelSpec::new(id).with_revision(revision);
+        spec.sha256 = sha256.clone();
+        specs.push(spec);
+    }
+    if specs.is_empty() {
+        bail!("No models to work on: pass --config or --model");
+    }
+    if cache_dir.is_some() {
+        settings.cache_dir = cache_dir;
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    let mut resolved = Vec::new();
+    for spec in specs {
+        let spec = spec
+            .with_settings(&settings)
+            .map_err(|e| anyhow::anyhow!(e))?;
+        if seen.insert((spec.id.clone(), spec.revision.clone())) {
+            resolved.push(spec);
+        }
+    }
+    Ok(resolved)
+}
+
+/// Hub models loaded by the config's processors; ones read from a `model_path` are skipped.
+#[cfg(any(feature = "semantic", feature = "ner"))]
+fn config_models(config: &PipelineConfig) -> Vec<ModelSpec> {
+    let mut specs = Vec::new();
+    for entry in &config.processors {
+        match &entry.processor {
+            #[cfg(feature = "ner")]
+            ProcessorConfig::PiiMasker {
+                use_ner: true,
+                model_path: None,
+                ner,
+                ..
+            } => specs.push(
+                ModelSpec::new(
+                    ner.model
+                        .as_deref()
+                        .unwrap_or(udo::processors::ner::DEFAULT_NER_MODEL),
+                )
+                .with_revision(ner.revision.clone()),
+            ),
+            #[cfg(feature = "semantic")]
+            ProcessorConfig::SemanticPruner {
+                model_path: None, ..
+            }
+            | ProcessorConfig::Embedding {
+                model_path: None, ..
+            }
+            | ProcessorConfig::SemanticRowFilter {
+                model_path: None, ..
+            }
+            | ProcessorConfig::Classifier {
+                method: ClassifyMethod::Embedding,
+                model_path: None,
+                ..
+            } => specs.push(ModelSpec::new(DEFAULT_EMBEDDING_MODEL)),
+            _ => {}
+        }
+    }
+    specs
+}
+
+fn reveal_tokens(vault: &Path, key: &KeySource, tokens: &[String]) -> Result<()> {
+    let key = key.load().map_err(|e| anyhow::anyhow!(e))?;
+    if !vault.exists() {
+        bail!("Token vault {} does not exist", vault.display());
+    }
+    let vault = TokenVault::open(vault, &key).map_err(|e| anyhow::anyhow!(e))?;
+    for token in tokens {
+        match vault.reveal(token).map_err(|e| anyhow::anyhow!(e))? {
+            Some(original) => println!("{}\t{}", token, original),
+            None => println!("{}\t<not found>", token),
+        }
+    }
+    Ok(())
+}
+
+/// Re-runs the records captured in a DLQ file through the processors and sink of
+/// `config_path`. Records that fail again land in that config's own DLQ.
+async fn replay_dlq(input: PathBuf, config_path: PathBuf, output: Option<String>) -> Result<()> {
+    let mut config = load_config(&config_path)?;
+    config.sink = replay_sink(config.sink, output)?;
+
+    // FileDlq truncates its target on open, which would destroy the file being replayed.
+    if let Some(SinkConfig::File { path }) = &config.dlq
+        && path.exists()
+        && std::fs::canonicalize(path)? == std::fs::canonicalize(&input)?
     {
-        error!(error = %e, "Failed to record metrics");
+        bail!("Replay input must differ from the pipeline's DLQ path");
+    }
+
+    info!(input = ?input, "Replaying dead letters");
+    let source = udo::io::dlq::DlqReplaySource::new(input).await?;
+    let mut runner = udo::PipelineRunner::new(Box::new(source), config.batch_size);
+    if let Some(name) = config.name {
+        runner.set_name(name);
     }
+    if let Some(d) = build_dlq(config.dlq)? {
+        runner.set_dlq(d);
+    }
+    if let Some(rate) = config.max_error_rate {
+        runner.set_max_error_rate(rate, config.error_rate_min_records);
+    }
+    for (p, retry) in build_processors(config.processors, &config.models)? {
+        runner.add_processor_with_retry(p, retry);
+    }
+    let report_path = report_path(&config.sink);
+    let sink_factory = build_sink_factory(config.sink);
+    runner.set_sink_factory(sink_factory);
 
+    let start_time = Instant::now();
+    runner.run(None).await.map_err(|e| anyhow::anyhow!(e))?;
+    info!(duration = ?start_time.elapsed(), "DLQ replay completed");
+    write_reports(&runner, &report_path)?;
     Ok(())
 }
+
+/// The sink a replay writes to: `output` if given, else the pipeline's sink with
+/// `.replay` before the extension. Sinks truncate their target, so writing to the
+/// pipeline's own output would destroy the original job's results.
+fn replay_sink(sink: SinkConfig, output: Option<String>) -> Result<SinkConfig> {
+    let original = match &sink {
+        SinkConfig::File { path } | SinkConfig::Csv { path, .. } | SinkConfig::Avro { path } => {
+            path.to_string_lossy().into_owned()
+        }
+        #[cfg(feature = "cloud")]
+        SinkConfig::Cloud { url, .. } => url.clone(),
+    };
+    let target = match output {
+        Some(output) if output == original => {
+            bail!("Replay output must differ from the pipeline's sink");
+        }
+        Some(output) => output,
+        None => replay_path(&original),
+    };
+    Ok(match sink {
+        SinkConfig::File { .. } => SinkConfig::File {
+            path: PathBuf::from(target),
+        },
+        SinkConfig::Csv { options, .. } => SinkConfig::Csv {
+            path: PathBuf::from(target),
+            options,
+        },
+        SinkConfig::Avro { .. } => SinkConfig::Avro {
+            path: PathBuf::from(target),
+        },
+        #[cfg(feature = "cloud")]
+        SinkConfig::Cloud { rotation, .. } => SinkConfig::Cloud {
+            url: target,
+            rotation,
+        },
+    })
+}
+
+/// `out.parquet` becomes `out.replay.parquet`; paths without an extension, and
+/// prefixes like `s3://bucket/out/`, get `.replay` appended to their last segment.
+fn replay_path(original: &str) -> String {
+    let trimmed = original.trim_end_matches('/');
+    let slashes = &original[trimmed.len()..];
+    let name_start = trimmed.rfind('/').map_or(0, |i| i + 1);
+    match trimmed[name_start..].rfind('.') {
+        Some(dot) if dot > 0 => {
+            let (stem, ext) = trimmed.split_at(name_start + dot);
+            format!("{}.replay{}{}", stem, ext, slashes)
+        }
+        _ => format!("{}.replay{}", trimmed, slashes),
+    }
+}
@@ -0,0 +1,290 @@
+use crate::core::config::ClassLabel;
+use crate::core::error::{Result, UdoError};
+use crate::core::pipeline::DataProcessor;
+#[cfg(feature = "semantic")]
+use crate::processors::semantic::{cosine_similarity, IntentAnalyzer};
+use crate::utils::path::fields_text;
+use arrow::datatypes::{DataType, Field, Schema};
+use async_trait::async_trait;
+use simd_json::{prelude::*, OwnedValue};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+#[cfg(feature = "semantic")]
+use tokio::sync::OnceCell;
+
+pub const DEFAULT_LABEL_FIELD: &str = "_label";
+pub const DEFAULT_LABEL_SCORE_FIELD: &str = "_label_score";
+/// Key of the null-label bucket in [`RecordClassifier::counts`]; no label may use it.
+pub const UNLABELED: &str = "_unlabeled";
+
+/// Lowercased alphanumeric words of `text`.
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+enum Scorer {
+    /// Per label, its keywords as word sequences.
+    Keywords(Vec<Vec<Vec<String>>>),
+    #[cfg(feature = "semantic")]
+    Embedding {
+        analyzer: Arc<IntentAnalyzer>,
+        /// Per label, the embedding of its description, or of its name; computed once,
+        /// by the first record scored.
+        labels: OnceCell<Vec<Vec<f32>>>,
+    },
+}
+
+/// Tags each record with the label its text matches best, e.g. to route billing
+/// complaints and bug reports to different agents.
+///
+/// The text is the configured fields (dotted paths into nested objects), joined by
+/// newlines. With keywords, a label's score is its share of the keyword matches in
+/// the text: keywords match whole words case-insensitively, and each counts once.
+/// With embeddings, it is the cosine similarity of the text to the label's
+/// description. The best label goes to `label_field` and its score to `score_field`;
+/// records without text, whose best score is not positive or is below `threshold`
+/// get the fallback label (null by default) and a null score.
+pub struct RecordClassifier {
+    labels: Vec<ClassLabel>,
+    scorer: Scorer,
+    fields: Vec<String>,
+    threshold: Option<f32>,
+    fallback: Option<String>,
+    label_field: String,
+    score_field: String,
+    /// Records per label, with the fallback last.
+    counts: Vec<AtomicU64>,
+}
+
+impl RecordClassifier {
+    /// Scores labels by their keywords; every label needs at least one.
+    pub fn from_keywords(labels: Vec<ClassLabel>, fields: Vec<String>) -> Result<Self> {
+        let mut rules = Vec::with_capacity(labels.len());
+        for label in &labels {
+            let keywords: Vec<Vec<String>> = label
+                .keywords
+                .iter()
+                .map(|k| words(k))
+                .filter(|k| !k.is_empty())
+                .collect();
+            if keywords.is_empty() {
+                return Err(UdoError::Config(format!(
+                    "Classifier label '{}' has no keywords",
+                    label.name
+                )));
+            }
+            rules.push(keywords);
+        }
+        Self::new(labels, fields, Scorer::Keywords(rules))
+    }
+
+    /// Scores labels by embedding similarity to their descriptions.
+    #[cfg(feature = "semantic")]
+    pub fn from_embeddings(
+        analyzer: IntentAnalyzer,
+        labels: Vec<ClassLabel>,
+        fields: Vec<String>,
+    ) -> Result<Self> {
+        let scorer = Scorer::Embedding {
+            analyzer: Arc::new(analyzer),
+            labels: OnceCell::new(),
+        };
+        Self::new(labels, fields, scorer)
+    }
+
+    fn new(labels: Vec<ClassLabel>, fields: Vec<String>, scorer: Scorer) -> Result<Self> {
+        if labels.is_empty() {
+            return Err(UdoError::Config(
+                "Classifier needs at least one label".to_string(),
+            ));
+        }
+        if fields.is_empty() {
+            return Err(UdoError::Config(
+                "Classifier needs at least one field".to_string(),
+            ));
+        }
+        let mut names = HashSet::new();
+        for label in &labels {
+            if label.name == UNLABELED {
+                return Err(UdoError::Config(format!(
+                    "Classifier label '{}' is reserved",
+                    UNLABELED
+                )));
+            }
+            if !names.insert(label.name.as_str()) {
+                return Err(UdoError::Config(format!(
+                    "Duplicate classifier label '{}'",
+                    label.name
+                )));
+            }
+        }
+        Ok(Self {
+            counts: (0..=labels.len()).map(|_| AtomicU64::new(0)).collect(),
+            labels,
+            scorer,
+            fields,
+            threshold: None,
+            fallback: None,
+            label_field: DEFAULT_LABEL_FIELD.to_string(),
+            score_field: DEFAULT_LABEL_SCORE_FIELD.to_string(),
+        })
+    }
+
+    /// Gives records whose best label scores below `threshold` the fallback label.
+    pub fn with_threshold(mut self, threshold: f32) -> Self {
+        self.threshold = Some(threshold);
+        self
+    }
+
+    pub fn with_fallback(mut self, label: impl Into<String>) -> Self {
+        self.fallback = Some(label.into());
+        self
+    }
+
+    pub fn with_label_field(mut self, field: impl Into<String>) -> Self {
+        self.label_field = field.into();
+        self
+    }
+
+    pub fn with_score_field(mut self, field: impl Into<String>) -> Self {
+        self.score_field = field.into();
+        self
+    }
+
+    /// Number of records given each label, the fallback label (or [`UNLABELED`]) last.
+    pub fn counts(&self) -> Vec<(String, u64)> {
+        let fallback = self.fallback.as_deref().unwrap_or(UNLABELED);
+        self.labels
+            .iter()
+            .map(|l| l.name.as_str())
+            .chain(std::iter::once(fallback))
+            .zip(&self.counts)
+            .map(|(name, count)| (name.to_string(), count.load(Ordering::Relaxed)))
+            .collect()
+    }
+
+    /// Score of every label for `text`, in label order.
+    async fn scores(&self, text: String) -> Result<Vec<f32>> {
+        match &self.scorer {
+            Scorer::Keywords(rules) => {
+                let words = words(&text);
+                let hits: Vec<usize> = rules
+                    .iter()
+                    .map(|keywords| {
+                        keywords
+                            .iter()
+                            .filter(|k| words.windows(k.len()).any(|w| w == k.as_slice()))
+                            .count()
+                    })
+                    .collect();
+                let total: usize = hits.iter().sum();
+                Ok(hits
+                    .into_iter()
+                    .map(|h| {
+                        if total == 0 {
+                            0.0
+                        } else {
+                            h as f32 / total as f32
+                        }
+                    })
+                    .collect())
+            }
+            #[cfg(feature = "semantic")]
+            Scorer::Embedding { analyzer, labels } => {
+                let embedding = analyzer
+                    .embed_async(vec![text])
+                    .await?
+                    .pop()
+                    .unwrap_or_default();
+                let labels = labels
+                    .get_or_try_init(|| {
+                        let texts = self
+                            .labels
+                            .iter()
+                            .map(|l| l.description.clone().unwrap_or_else(|| l.name.clone()))
+                            .collect();
+                        analyzer.embed_async(texts)
+                    })
+                    .await?;
+                Ok(labels
+                    .iter()
+                    .map(|label| cosine_similarity(label, &embedding))
+                    .collect())
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl DataProcessor for RecordClassifier {
+    async fn process(&self, mut record: OwnedValue) -> Result<Option<OwnedValue>> {
+        if !record.is_object() {
+            return Ok(Some(record));
+        }
+        let text = fields_text(&record, &self.fields);
+        let best = if text.trim().is_empty() {
+            None
+        } else {
+            // Earlier labels win ties.
+            let scores = self.scores(text).await?;
+            scores
+                .into_iter()
+                .enumerate()
+                .fold(
+                    None,
+                    |best: Option<(usize, f32)>, (idx, score)| match best {
+                        Some((_, top)) if top >= score => best,
+                        _ => Some((idx, score)),
+                    },
+                )
+                .filter(|(_, score)| *score > 0.0 && self.threshold.is_none_or(|t| *score >= t))
+        };
+
+        let (label, score) = match best {
+            Some((idx, score)) => {
+                self.counts[idx].fetch_add(1, Ordering::Relaxed);
+                (
+                    OwnedValue::from(self.labels[idx].name.clone()),
+                    OwnedValue::from(score as f64),
+                )
+            }
+            None => {
+                self.counts[self.labels.len()].fetch_add(1, Ordering::Relaxed);
+                let label = match &self.fallback {
+                    Some(fallback) => OwnedValue::from(fallback.clone()),
+                    None => OwnedValue::null(),
+                };
+                (label, OwnedValue::null())
+            }
+        };
+        if let Some(obj) = record.as_object_mut() {
+            obj.insert(self.label_field.clone(), label);
+            obj.insert(self.score_field.clone(), score);
+        }
+        Ok(Some(record))
+    }
+
+    fn update_schema(&self, schema: &Arc<Schema>) -> Result<Arc<Schema>> {
+        let mut fields: Vec<_> = schema.fields().iter().cloned().collect();
+        for (name, data_type) in [
+            (&self.label_field, DataType::Utf8),
+            (&self.score_field, DataType::Float64),
+        ] {
+            if schema.field_with_name(name).is_err() {
+                fields.push(Arc::new(Field::new(name.as_str(), data_type, true)));
+            }
+        }
+        Ok(Arc::new(Schema::new(fields)))
+    }
+
+    fn metrics(&self) -> Vec<(String, u64)> {
+        self.counts()
+            .into_iter()
+            .map(|(name, count)| (format!("labels.{}", name), count))
+            .collect()
+    }
+}
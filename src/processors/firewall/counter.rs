@@ -0,0 +1,155 @@
+use crate::core::config::TokenizerConfig;
+use crate::core::error::{Result, UdoError};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use tiktoken_rs::CoreBPE;
+
+/// Pre-tokenization pattern of `cl100k_base`, used for BPE files without their own.
+pub const CL100K_PATTERN: &str = r"(?i:'s|'t|'re|'ve|'m|'ll|'d)|[^\r\n\p{L}\p{N}]?\p{L}+|\p{N}{1,3}| ?[^\s\p{L}\p{N}]+[\r\n]*|\s*[\r\n]+|\s+(?!\S)|\s+";
+
+/// Counts and truncates text in the tokens of a target LLM.
+#[derive(Clone)]
+pub enum TokenCounter {
+    /// About `chars_per_token` characters per token; needs no vocabulary.
+    Approximate {
+        chars_per_token: f64,
+    },
+    Bpe(Arc<CoreBPE>),
+    #[cfg(feature = "ai")]
+    HuggingFace(Arc<tokenizers::Tokenizer>),
+}
+
+impl TokenCounter {
+    pub fn from_config(config: &TokenizerConfig) -> Result<Self> {
+        match config {
+            TokenizerConfig::Approximate { chars_per_token } => {
+                if *chars_per_token <= 0.0 {
+                    return Err(UdoError::Config(
+                        "chars_per_token must be positive".to_string(),
+                    ));
+                }
+                Ok(Self::Approximate {
+                    chars_per_token: *chars_per_token,
+                })
+            }
+            TokenizerConfig::Tiktoken { encoding } => Self::tiktoken(encoding),
+            TokenizerConfig::TiktokenFile { path, pattern } => {
+                Self::tiktoken_file(path, pattern.as_deref().unwrap_or(CL100K_PATTERN))
+            }
+            #[cfg(feature = "ai")]
+            TokenizerConfig::HuggingFace { path } => {
+                let tokenizer = tokenizers::Tokenizer::from_file(path).map_err(|e| {
+                    UdoError::Config(format!("Invalid tokenizer {}: {}", path.display(), e))
+                })?;
+                Ok(Self::HuggingFace(Arc::new(tokenizer)))
+            }
+        }
+    }
+
+    /// A built-in OpenAI encoding: `cl100k_base`, `o200k_base`, `p50k_base` or `r50k_base`.
+    pub fn tiktoken(encoding: &str) -> Result<Self> {
+        let bpe = match encoding {
+            "cl100k_base" => tiktoken_rs::cl100k_base(),
+            "o200k_base" => tiktoken_rs::o200k_base(),
+            "p50k_base" => tiktoken_rs::p50k_base(),
+            "r50k_base" => tiktoken_rs::r50k_base(),
+            other => {
+                return Err(UdoError::Config(format!(
+                    "Unknown tiktoken encoding '{}'",
+                    other
+                )))
+            }
+        }
+        .map_err(|e| UdoError::Config(e.to_string()))?;
+        Ok(Self::Bpe(Arc::new(bpe)))
+    }
+
+    /// A tiktoken-format rank file, one `<base64 token> <rank>` pair per line,
+    /// pre-tokenized with `pattern`.
+    pub fn tiktoken_file(path: impl AsRef<Path>, pattern: &str) -> Result<Self> {
+        let path = path.as_ref();
+        let invalid = |line: usize, reason: &str| {
+            UdoError::Config(format!(
+                "Invalid BPE file {} at line {}: {}",
+                path.display(),
+                line + 1,
+                reason
+            ))
+        };
+
+        let mut encoder: HashMap<Vec<u8>, tiktoken_rs::Rank> = HashMap::new();
+        for (idx, line) in std::fs::read_to_string(path)?.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (token, rank) = line
+                .split_once(' ')
+                .ok_or_else(|| invalid(idx, "expected '<token> <rank>'"))?;
+            let token = STANDARD
+                .decode(token)
+                .map_err(|e| invalid(idx, &e.to_string()))?;
+            let rank = rank
+                .trim()
+                .parse()
+                .map_err(|_| invalid(idx, "rank is not a number"))?;
+            if encoder.insert(token, rank).is_some() {
+                return Err(invalid(idx, "duplicate token"));
+            }
+        }
+        let bpe = CoreBPE::new(encoder.into_iter().collect(), Default::default(), pattern)
+            .map_err(|e| UdoError::Config(format!("Invalid BPE file {}: {}", path.display(), e)))?;
+        Ok(Self::Bpe(Arc::new(bpe)))
+    }
+
+    pub fn count(&self, text: &str) -> usize {
+        match self {
+            Self::Approximate { chars_per_token } => {
+                (text.chars().count() as f64 / chars_per_token).ceil() as usize
+            }
+            Self::Bpe(bpe) => bpe.encode_ordinary(text).len(),
+            #[cfg(feature = "ai")]
+            Self::HuggingFace(tokenizer) => tokenizer
+                .encode(text, false)
+                .map(|e| e.len())
+                .unwrap_or_else(|_| text.split_whitespace().count()),
+        }
+    }
+
+    /// The longest prefix of `text` made of its first `max_tokens` tokens, cut at a
+    /// character boundary.
+    pub fn truncate<'a>(&self, text: &'a str, max_tokens: usize) -> &'a str {
+        let end = match self {
+            Self::Approximate { chars_per_token } => {
+                let chars = (max_tokens as f64 * chars_per_token).floor() as usize;
+                text.char_indices()
+                    .nth(chars)
+                    .map(|(idx, _)| idx)
+                    .unwrap_or(text.len())
+            }
+            Self::Bpe(bpe) => {
+                let mut tokens = bpe.encode_ordinary(text);
+                if tokens.len() <= max_tokens {
+                    return text;
+                }
+                tokens.truncate(max_tokens);
+                bpe._decode_native_and_split(tokens).map(|b| b.len()).sum()
+            }
+            #[cfg(feature = "ai")]
+            Self::HuggingFace(tokenizer) => match tokenizer.encode(text, false) {
+                Ok(encoding) if encoding.len() > max_tokens => match max_tokens {
+                    0 => 0,
+                    n => encoding.get_offsets()[n - 1].1,
+                },
+                _ => return text,
+            },
+        };
+        let mut end = end.min(text.len());
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        &text[..end]
+    }
+}
@@ -0,0 +1,201 @@
+use crate::core::config::TokenOverflow;
+use crate::core::error::Result;
+use crate::core::pipeline::DataProcessor;
+use async_trait::async_trait;
+use simd_json::{prelude::*, OwnedValue};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::debug;
+
+pub mod counter;
+
+use counter::TokenCounter;
+
+/// Keeps records within an LLM token budget ("token firewall").
+///
+/// Tokens are counted over top-level field values, with non-string values counted as
+/// JSON. A record over `max_tokens_per_record` is trimmed field by field, least
+/// important first: fields not listed in `priority`, then the listed ones from last to
+/// first. Trimming truncates string fields or drops fields, per the `overflow` setting.
+/// `max_tokens_per_batch` then keeps the rows of each batch, in order, while they fit.
+pub struct TokenFirewall {
+    counter: TokenCounter,
+    max_record_tokens: Option<usize>,
+    max_batch_tokens: Option<usize>,
+    /// Top-level fields, most important first.
+    priority: Vec<String>,
+    overflow: TokenOverflow,
+    records: AtomicU64,
+    tokens_before: AtomicU64,
+    tokens_after: AtomicU64,
+    fields_truncated: AtomicU64,
+    fields_dropped: AtomicU64,
+    records_dropped: AtomicU64,
+}
+
+impl TokenFirewall {
+    pub fn new(counter: TokenCounter) -> Self {
+        Self {
+            counter,
+            max_record_tokens: None,
+            max_batch_tokens: None,
+            priority: Vec::new(),
+            overflow: TokenOverflow::default(),
+            records: AtomicU64::new(0),
+            tokens_before: AtomicU64::new(0),
+            tokens_after: AtomicU64::new(0),
+            fields_truncated: AtomicU64::new(0),
+            fields_dropped: AtomicU64::new(0),
+            records_dropped: AtomicU64::new(0),
+        }
+    }
+
+    pub fn with_record_budget(mut self, max_tokens: usize) -> Self {
+        self.max_record_tokens = Some(max_tokens);
+        self
+    }
+
+    pub fn with_batch_budget(mut self, max_tokens: usize) -> Self {
+        self.max_batch_tokens = Some(max_tokens);
+        self
+    }
+
+    pub fn with_priority(mut self, fields: Vec<String>) -> Self {
+        self.priority = fields;
+        self
+    }
+
+    pub fn with_overflow(mut self, overflow: TokenOverflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    fn value_tokens(&self, value: &OwnedValue) -> usize {
+        if value.is_null() {
+            return 0;
+        }
+        match value.as_str() {
+            Some(s) => self.counter.count(s),
+            None => self
+                .counter
+                .count(&simd_json::to_string(value).unwrap_or_default()),
+        }
+    }
+
+    /// Tokens of all top-level values of `record`.
+    pub fn record_tokens(&self, record: &OwnedValue) -> usize {
+        match record.as_object() {
+            Some(obj) => obj.values().map(|v| self.value_tokens(v)).sum(),
+            None => self.value_tokens(record),
+        }
+    }
+
+    /// Field names in the order they are trimmed: unlisted fields from last to first,
+    /// then listed fields from least to most important.
+    fn trim_order(&self, keys: &[String]) -> Vec<String> {
+        let mut order: Vec<String> = keys
+            .iter()
+            .rev()
+            .filter(|k| !self.priority.contains(k))
+            .cloned()
+            .collect();
+        order.extend(
+            self.priority
+                .as_slice()
+                .iter()
+                .rev()
+                .filter(|p| keys.contains(p))
+                .cloned(),
+        );
+        order
+    }
+}
+
+#[async_trait]
+impl DataProcessor for TokenFirewall {
+    async fn process(&self, mut record: OwnedValue) -> Result<Option<OwnedValue>> {
+        let before = self.record_tokens(&record);
+        let mut total = before;
+
+        if let Some(budget) = self.max_record_tokens
+            && total > budget
+            && let Some(obj) = record.as_object_mut()
+        {
+            let keys: Vec<String> = obj.keys().map(|k| k.to_string()).collect();
+            for key in self.trim_order(&keys) {
+                if total <= budget {
+                    break;
+                }
+                let Some(value) = obj.get(key.as_str()) else {
+                    continue;
+                };
+                let cost = self.value_tokens(value);
+                if cost == 0 {
+                    continue;
+                }
+                let excess = total - budget;
+                let truncated = match (self.overflow, value.as_str()) {
+                    (TokenOverflow::Truncate, Some(text)) if cost > excess => {
+                        Some(self.counter.truncate(text, cost - excess).to_string())
+                    }
+                    _ => None,
+                };
+                match truncated {
+                    Some(text) => {
+                        let kept = self.counter.count(&text);
+                        total = total - cost + kept;
+                        obj.insert(key.clone(), OwnedValue::from(text));
+                        self.fields_truncated.fetch_add(1, Ordering::Relaxed);
+                        debug!(field = %key, tokens = %cost, kept = %kept, "Truncated field");
+                    }
+                    None => {
+                        obj.remove(key.as_str());
+                        total -= cost;
+                        self.fields_dropped.fetch_add(1, Ordering::Relaxed);
+                        debug!(field = %key, tokens = %cost, "Dropped field");
+                    }
+                }
+            }
+        }
+
+        self.records.fetch_add(1, Ordering::Relaxed);
+        self.tokens_before
+            .fetch_add(before as u64, Ordering::Relaxed);
+        self.tokens_after.fetch_add(total as u64, Ordering::Relaxed);
+        Ok(Some(record))
+    }
+
+    async fn process_batch(&self, rows: Vec<OwnedValue>) -> Result<Vec<Option<OwnedValue>>> {
+        let Some(budget) = self.max_batch_tokens else {
+            return Ok(rows.into_iter().map(Some).collect());
+        };
+        let mut used = 0;
+        let mut kept = Vec::with_capacity(rows.len());
+        for row in rows {
+            let tokens = self.record_tokens(&row);
+            if used + tokens <= budget {
+                used += tokens;
+                kept.push(Some(row));
+            } else {
+                self.tokens_after
+                    .fetch_sub(tokens as u64, Ordering::Relaxed);
+                self.records_dropped.fetch_add(1, Ordering::Relaxed);
+                kept.push(None);
+            }
+        }
+        Ok(kept)
+    }
+
+    fn metrics(&self) -> Vec<(String, u64)> {
+        [
+            ("records", &self.records),
+            ("tokens_before", &self.tokens_before),
+            ("tokens_after", &self.tokens_after),
+            ("fields_truncated", &self.fields_truncated),
+            ("fields_dropped", &self.fields_dropped),
+            ("records_dropped", &self.records_dropped),
+        ]
+        .into_iter()
+        .map(|(name, counter)| (name.to_string(), counter.load(Ordering::Relaxed)))
+        .collect()
+    }
+}
@@ -0,0 +1,417 @@
+use crate::core::error::Result;
+use crate::core::pipeline::DataProcessor;
+use crate::utils::path::lookup;
+use arrow::datatypes::{DataType, Field, Schema};
+use async_trait::async_trait;
+use simd_json::{prelude::*, OwnedValue};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+pub const DEFAULT_LANGUAGE_FIELD: &str = "_lang";
+pub const DEFAULT_CONFIDENCE_FIELD: &str = "_lang_confidence";
+
+/// Code that processors restricted to languages can list to also get records whose
+/// language was not detected.
+pub const UNDETERMINED: &str = "und";
+
+/// Frequent short words of the Latin-script languages, which tell them apart even in
+/// a sentence or two.
+const COMMON_WORDS: &[(&str, &[&str])] = &[
+    (
+        "en",
+        &[
+            "the", "and", "of", "to", "is", "in", "that", "it", "for", "you", "was", "with", "on",
+            "are", "this", "have", "be", "not", "from", "by", "we", "my", "please", "your",
+        ],
+    ),
+    (
+        "de",
+        &[
+            "der", "die", "und", "das", "ist", "nicht", "ich", "sie", "es", "mit", "den", "ein",
+            "eine", "zu", "von", "auf", "für", "sich", "dem", "auch", "wir", "bitte", "wurde",
+            "aber",
+        ],
+    ),
+    (
+        "fr",
+        &[
+            "le", "la", "les", "et", "est", "des", "une", "un", "du", "que", "pas", "pour", "dans",
+            "en", "je", "vous", "nous", "sur", "avec", "ce", "il", "qui", "mais", "au",
+        ],
+    ),
+    (
+        "es",
+        &[
+            "el", "la", "los", "las", "y", "es", "de", "que", "en", "un", "una", "no", "por",
+            "para", "con", "se", "su", "lo", "del", "al", "como", "pero", "mi", "está",
+        ],
+    ),
+    (
+        "it",
+        &[
+            "il", "la", "di", "e", "che", "non", "un", "una", "per", "in", "sono", "con", "del",
+            "della", "gli", "le", "mi", "è", "ma", "si", "questo", "ho", "al", "anche",
+        ],
+    ),
+    (
+        "nl",
+        &[
+            "de", "het", "een", "en", "van", "is", "niet", "ik", "dat", "je", "op", "te", "met",
+            "voor", "zijn", "er", "maar", "ook", "wij", "dit", "naar", "heb", "bij", "wat",
+        ],
+    ),
+    (
+        "pt",
+        &[
+            "o", "a", "os", "as", "e", "de", "do", "da", "que", "não", "um", "uma", "em", "para",
+            "com", "se", "por", "é", "no", "na", "mas", "eu", "você", "mais",
+        ],
+    ),
+];
+
+/// Letters only one of the Latin-script languages above uses; each counts as a word.
+const LETTER_HINTS: &[(&str, &[char])] = &[
+    ("de", &['ß', 'ä', 'ö', 'ü']),
+    ("es", &['ñ', '¿', '¡']),
+    ("pt", &['ã', 'õ']),
+    ("fr", &['è', 'ê', 'œ', 'ù', 'â', 'î']),
+    ("it", &['ì', 'ò']),
+];
+
+/// Scripts written by one language (or, for Han, decided by the presence of kana).
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Script {
+    Latin,
+    Kana,
+    Han,
+    Hangul,
+    Cyrillic,
+    Arabic,
+    Greek,
+    Hebrew,
+    Thai,
+    Devanagari,
+}
+
+fn script(c: char) -> Option<Script> {
+    Some(match c as u32 {
+        0x3040..=0x30FF | 0x31F0..=0x31FF | 0xFF66..=0xFF9F => Script::Kana,
+        0x3400..=0x4DBF | 0x4E00..=0x9FFF => Script::Han,
+        0x1100..=0x11FF | 0x3130..=0x318F | 0xAC00..=0xD7AF => Script::Hangul,
+        0x0400..=0x04FF => Script::Cyrillic,
+        0x0600..=0x06FF => Script::Arabic,
+        0x0370..=0x03FF => Script::Greek,
+        0x0590..=0x05FF => Script::Hebrew,
+        0x0E00..=0x0E7F => Script::Thai,
+        0x0900..=0x097F => Script::Devanagari,
+        _ if c.is_alphabetic()
+            && ((c as u32) < 0x250 || (0x1E00..=0x1EFF).contains(&(c as u32))) =>
+        {
+            Script::Latin
+        }
+        _ => return None,
+    })
+}
+
+/// A text's language as an ISO 639-1 code, with a confidence in `[0, 1]`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Detection {
+    pub language: &'static str,
+    pub confidence: f32,
+}
+
+/// Detects the language of `text` from its most frequent script, and for Latin script
+/// from its common words. Japanese is told from Chinese by its kana. The confidence is
+/// the share of letters in the deciding script, times, for Latin script, the best
+/// language's share of the common-word matches. `None` when there is nothing to go
+/// by, e.g. no letters or only Latin letters without any common word.
+pub fn detect(text: &str) -> Option<Detection> {
+    let mut letters = 0usize;
+    let mut scripts: HashMap<Script, usize> = HashMap::new();
+    for c in text.chars() {
+        if let Some(s) = script(c) {
+            letters += 1;
+            *scripts.entry(s).or_default() += 1;
+        }
+    }
+    if letters == 0 {
+        return None;
+    }
+    // Japanese mixes kana with kanji; Han without kana is Chinese.
+    let kana = scripts.remove(&Script::Kana).unwrap_or(0);
+    if kana > 0 {
+        let han = scripts.remove(&Script::Han).unwrap_or(0);
+        scripts.insert(Script::Kana, kana + han);
+    }
+    let mut scripts: Vec<(Script, usize)> = scripts.into_iter().collect();
+    scripts.sort_by_key(|(script, count)| (std::cmp::Reverse(*count), *script));
+
+    // Latin letters without a common word, e.g. product names, leave it to the next script.
+    for (script, count) in scripts {
+        let share = count as f32 / letters as f32;
+        let language = match script {
+            Script::Latin => match latin_language(text) {
+                Some((language, confidence)) => {
+                    return Some(Detection {
+                        language,
+                        confidence: share * confidence,
+                    });
+                }
+                None => continue,
+            },
+            Script::Kana => "ja",
+            Script::Han => "zh",
+            Script::Hangul => "ko",
+            Script::Cyrillic => "ru",
+            Script::Arabic => "ar",
+            Script::Greek => "el",
+            Script::Hebrew => "he",
+            Script::Thai => "th",
+            Script::Devanagari => "hi",
+        };
+        return Some(Detection {
+            language,
+            confidence: share,
+        });
+    }
+    None
+}
+
+/// The Latin-script language with the most common-word and letter matches, and its
+/// share of all matches. Earlier languages win ties.
+fn latin_language(text: &str) -> Option<(&'static str, f32)> {
+    let lower = text.to_lowercase();
+    let mut hits = vec![0usize; COMMON_WORDS.len()];
+    for word in lower.split(|c: char| !c.is_alphanumeric()) {
+        for (idx, (_, words)) in COMMON_WORDS.iter().enumerate() {
+            if words.contains(&word) {
+                hits[idx] += 1;
+            }
+        }
+    }
+    for c in lower.chars() {
+        for (language, letters) in LETTER_HINTS {
+            if letters.contains(&c)
+                && let Some(idx) = COMMON_WORDS.iter().position(|(l, _)| l == language)
+            {
+                hits[idx] += 1;
+            }
+        }
+    }
+    let mut best = 0;
+    for (idx, &count) in hits.iter().enumerate() {
+        if count > hits[best] {
+            best = idx;
+        }
+    }
+    let (count, total) = (hits[best], hits.iter().sum::<usize>());
+    if count == 0 {
+        return None;
+    }
+    Some((COMMON_WORDS[best].0, count as f32 / total as f32))
+}
+
+/// Annotates each record with the language of its text and the detection's
+/// confidence (see [`detect`]).
+///
+/// The text is the configured fields (dotted paths into nested objects), joined by
+/// newlines. Records whose language is not detected, or detected with less than
+/// `min_confidence`, get nulls.
+pub struct LanguageDetector {
+    fields: Vec<String>,
+    language_field: String,
+    confidence_field: String,
+    min_confidence: f32,
+    /// Records per detected language.
+    counts: Mutex<BTreeMap<&'static str, u64>>,
+    undetermined: AtomicU64,
+}
+
+impl LanguageDetector {
+    pub fn new(fields: Vec<String>) -> Self {
+        Self {
+            fields,
+            language_field: DEFAULT_LANGUAGE_FIELD.to_string(),
+            confidence_field: DEFAULT_CONFIDENCE_FIELD.to_string(),
+            min_confidence: 0.0,
+            counts: Mutex::new(BTreeMap::new()),
+            undetermined: AtomicU64::new(0),
+        }
+    }
+
+    pub fn with_min_confidence(mut self, min_confidence: f32) -> Self {
+        self.min_confidence = min_confidence;
+        self
+    }
+
+    pub fn with_language_field(mut self, field: impl Into<String>) -> Self {
+        self.language_field = field.into();
+        self
+    }
+
+    pub fn with_confidence_field(mut self, field: impl Into<String>) -> Self {
+        self.confidence_field = field.into();
+        self
+    }
+
+    /// Field the detected language is written to, e.g. for [`LanguageGate`].
+    pub fn language_field(&self) -> &str {
+        &self.language_field
+    }
+}
+
+#[async_trait]
+impl DataProcessor for LanguageDetector {
+    async fn process(&self, mut record: OwnedValue) -> Result<Option<OwnedValue>> {
+        if !record.is_object() {
+            return Ok(Some(record));
+        }
+        // Only string fields: serialized numbers or objects would skew the detection.
+        let text: Vec<&str> = self
+            .fields
+            .iter()
+            .filter_map(|field| lookup(&record, field)?.as_str())
+            .collect();
+        let detection = detect(&text.join("\n")).filter(|d| d.confidence >= self.min_confidence);
+        let (language, confidence) = match detection {
+            Some(d) => {
+                if let Ok(mut counts) = self.counts.lock() {
+                    *counts.entry(d.language).or_default() += 1;
+                }
+                (
+                    OwnedValue::from(d.language),
+                    OwnedValue::from(d.confidence as f64),
+                )
+            }
+            None => {
+                self.undetermined.fetch_add(1, Ordering::Relaxed);
+                (OwnedValue::null(), OwnedValue::null())
+            }
+        };
+        if let Some(obj) = record.as_object_mut() {
+            obj.insert(self.language_field.clone(), language);
+            obj.insert(self.confidence_field.clone(), confidence);
+        }
+        Ok(Some(record))
+    }
+
+    fn update_schema(&self, schema: &Arc<Schema>) -> Result<Arc<Schema>> {
+        let mut fields: Vec<_> = schema.fields().iter().cloned().collect();
+        for (name, data_type) in [
+            (&self.language_field, DataType::Utf8),
+            (&self.confidence_field, DataType::Float64),
+        ] {
+            if schema.field_with_name(name).is_err() {
+                fields.push(Arc::new(Field::new(name.as_str(), data_type, true)));
+            }
+        }
+        Ok(Arc::new(Schema::new(fields)))
+    }
+
+    fn metrics(&self) -> Vec<(String, u64)> {
+        let mut metrics: Vec<(String, u64)> = self
+            .counts
+            .lock()
+            .map(|counts| {
+                counts
+                    .iter()
+                    .map(|(language, count)| (format!("languages.{}", language), *count))
+                    .collect()
+            })
+            .unwrap_or_default();
+        metrics.push((
+            format!("languages.{}", UNDETERMINED),
+            self.undetermined.load(Ordering::Relaxed),
+        ));
+        metrics
+    }
+}
+
+/// Runs a processor only on records in one of `languages`, as annotated by a
+/// [`LanguageDetector`] earlier in the pipeline; other records pass through untouched.
+/// [`UNDETERMINED`] matches records without a detected language.
+pub struct LanguageGate {
+    inner: Box<dyn DataProcessor>,
+    languages: HashSet<String>,
+    field: String,
+}
+
+impl LanguageGate {
+    pub fn new(
+        inner: Box<dyn DataProcessor>,
+        languages: impl IntoIterator<Item = String>,
+        field: impl Into<String>,
+    ) -> Self {
+        Self {
+            inner,
+            languages: languages.into_iter().map(|l| l.to_lowercase()).collect(),
+            field: field.into(),
+        }
+    }
+
+    fn applies(&self, record: &OwnedValue) -> bool {
+        let language = record
+            .get(self.field.as_str())
+            .and_then(|v| v.as_str())
+            .unwrap_or(UNDETERMINED);
+        self.languages.contains(&language.to_lowercase())
+    }
+}
+
+#[async_trait]
+impl DataProcessor for LanguageGate {
+    async fn process(&self, record: OwnedValue) -> Result<Option<OwnedValue>> {
+        if self.applies(&record) {
+            self.inner.process(record).await
+        } else {
+            Ok(Some(record))
+        }
+    }
+
+    fn update_schema(&self, schema: &Arc<Schema>) -> Result<Arc<Schema>> {
+        self.inner.update_schema(schema)
+    }
+
+    fn observe_samples(&self, records: &[OwnedValue]) {
+        self.inner.observe_samples(records)
+    }
+
+    /// The inner processor sees only the batch's rows in its languages; its slots
+    /// take the places of those rows.
+    async fn process_batch(&self, rows: Vec<OwnedValue>) -> Result<Vec<Option<OwnedValue>>> {
+        let mut matched = Vec::new();
+        let mut slots = Vec::with_capacity(rows.len());
+        for row in rows {
+            if self.applies(&row) {
+                matched.push(row);
+                slots.push(None);
+            } else {
+                slots.push(Some(row));
+            }
+        }
+        if matched.is_empty() {
+            return Ok(slots);
+        }
+        let mut processed = self.inner.process_batch(matched).await?.into_iter();
+        Ok(slots
+            .into_iter()
+            .map(|slot| match slot {
+                Some(row) => Some(row),
+                None => processed.next().flatten(),
+            })
+            .collect())
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn metrics(&self) -> Vec<(String, u64)> {
+        self.inner.metrics()
+    }
+
+    fn report(&self) -> Option<serde_json::Value> {
+        self.inner.report()
+    }
+}
@@ -1,5 +1,9 @@
+pub mod classify;
+pub mod firewall;
+pub mod language;
 #[cfg(feature = "ner")]
 pub mod ner;
 pub mod pii;
+pub mod secrets;
 #[cfg(feature = "semantic")]
 pub mod semantic;
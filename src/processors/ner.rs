@@ -1,75 +1,600 @@
+use crate::core::bert::{Bert, CompactBert, Weights};
+use crate::core::config::Precision;
 use crate::core::error::{Result, UdoError};
-use crate::core::model::BertModelContainer;
-use candle_core::Tensor;
-use tracing::debug;
+use crate::core::inference::{InferencePool, MicroBatcher};
+use crate::core::model::{sample_batch, LoadReport, ModelFiles, ModelPool, ModelSpec};
+use candle_core::safetensors::MmapedSafetensors;
+use candle_core::{DType, Device, Tensor, D};
+use candle_nn::{Linear, Module, VarBuilder};
+use candle_transformers::models::{bert, debertav2, distilbert, xlm_roberta};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokenizers::{Encoding, Tokenizer, TruncationDirection, TruncationParams, TruncationStrategy};
+use tracing::{debug, info};
 
-pub struct NerAnalyzer {
-    container: BertModelContainer,
+/// Model used when none is configured: an English CoNLL-03 BERT.
+pub const DEFAULT_NER_MODEL: &str = "dbmdz/bert-large-cased-finetuned-conll03-english";
+
+const DEFAULT_MAX_TOKENS: usize = 512;
+const DEFAULT_STRIDE: usize = 128;
+const DEFAULT_BATCH_SIZE: usize = 16;
+
+/// Window overlap used when none is configured: a quarter of the window, at most 128.
+pub fn default_stride(max_tokens: usize) -> usize {
+    DEFAULT_STRIDE.min(max_tokens / 4)
+}
+
+fn model_err(e: impl std::fmt::Display) -> UdoError {
+    UdoError::AiModel(e.to_string())
+}
+
+/// The fields of `config.json` that decide how a token-classification model is loaded.
+#[derive(Deserialize)]
+struct ModelConfig {
+    model_type: Option<String>,
+    #[serde(default)]
+    id2label: HashMap<String, String>,
+    pad_token_id: Option<u32>,
+    max_position_embeddings: Option<usize>,
+}
+
+/// Encoder architectures that can back a token-classification model.
+enum Encoder {
+    Bert(Bert),
+    DistilBert(distilbert::DistilBertModel),
+    /// RoBERTa and XLM-RoBERTa share an architecture.
+    Roberta(xlm_roberta::XLMRobertaModel),
+    DebertaV2(Box<debertav2::DebertaV2Model>),
+}
+
+impl Encoder {
+    fn load(model_type: &str, config: &str, vb: VarBuilder) -> Result<Self> {
+        fn parse<'a, T: Deserialize<'a>>(config: &'a str) -> Result<T> {
+            serde_json::from_str(config).map_err(|e| UdoError::Config(e.to_string()))
+        }
+        // Token-classification checkpoints nest the encoder under its model type.
+        let prefixed = |prefix: &str| {
+            if vb.contains_tensor(&format!("{}.embeddings.word_embeddings.weight", prefix)) {
+                vb.pp(prefix)
+            } else {
+                vb.clone()
+            }
+        };
+        Ok(match model_type {
+            "bert" => Self::Bert(Bert::Reference(
+                bert::BertModel::load(prefixed("bert"), &parse(config)?).map_err(model_err)?,
+            )),
+            "distilbert" => Self::DistilBert(
+                distilbert::DistilBertModel::load(prefixed("distilbert"), &parse(config)?)
+                    .map_err(model_err)?,
+            ),
+            "roberta" | "xlm-roberta" | "camembert" => Self::Roberta(
+                xlm_roberta::XLMRobertaModel::new(&parse(config)?, prefixed("roberta"))
+                    .map_err(model_err)?,
+            ),
+            "deberta-v2" => Self::DebertaV2(Box::new(
+                debertav2::DebertaV2Model::load(prefixed("deberta"), &parse(config)?)
+                    .map_err(model_err)?,
+            )),
+            other => {
+                return Err(UdoError::Config(format!(
+                    "Unsupported NER model type '{}' (expected bert, distilbert, roberta, \
+                     xlm-roberta or deberta-v2)",
+                    other
+                )))
+            }
+        })
+    }
+
+    /// Hidden states for a padded batch; `mask` is 1 for real tokens and 0 for padding.
+    fn forward(&self, ids: &Tensor, mask: &Tensor) -> candle_core::Result<Tensor> {
+        match self {
+            Self::Bert(model) => model.forward(ids, &ids.zeros_like()?, Some(mask)),
+            Self::DistilBert(model) => {
+                // DistilBERT takes the positions to hide, broadcastable over heads.
+                let (batch, len) = mask.dims2()?;
+                let hidden = mask.eq(0u32)?.reshape((batch, 1, 1, len))?;
+                model.forward(ids, &hidden)
+            }
+            Self::Roberta(model) => model.forward(ids, mask, &ids.zeros_like()?, None, None, None),
+            Self::DebertaV2(model) => model.forward(ids, None, Some(mask.to_dtype(DType::I64)?)),
+        }
+    }
+}
+
+/// The loaded weights of a token-classification model, shared by every analyzer
+/// built on it.
+pub struct NerModel {
+    encoder: Encoder,
+    /// Token-classification head on top of the encoder.
+    classifier: Linear,
+    /// Label per class ID, from the model's `id2label`.
     labels: Vec<String>,
+    /// The model's tokenizer, before any windowing.
+    tokenizer: Tokenizer,
+    device: Device,
+    pad_id: u32,
+    /// Longest window the model's position embeddings allow.
+    max_tokens: usize,
+    report: LoadReport,
+}
+
+pub struct NerAnalyzer {
+    model: Arc<NerModel>,
+    /// The model's tokenizer, set up to split long texts into overlapping windows.
+    tokenizer: Tokenizer,
+    batch_size: usize,
+    /// Where [`NerAnalyzer::predict_batch_async`] runs the model, and how long it batches.
+    inference: Option<(Arc<InferencePool>, Duration)>,
+    batcher: OnceLock<MicroBatcher<String, Vec<EntitySpan>>>,
+}
+
+impl NerAnalyzer {
+    pub fn new(model_path: Option<PathBuf>) -> Result<Self> {
+        Self::load(DEFAULT_NER_MODEL, None, model_path)
+    }
+
+    /// Loads a token-classification model by hub ID and optional revision, or from
+    /// `model_path` when given. Labels are read from the model's `id2label`.
+    pub fn load(
+        model_id: &str,
+        revision: Option<&str>,
+        model_path: Option<PathBuf>,
+    ) -> Result<Self> {
+        Self::from_spec(
+            &ModelSpec::new(model_id)
+                .with_revision(revision.map(str::to_string))
+                .with_path(model_path),
+        )
+    }
+
+    /// Loads the token-classification model described by `spec`.
+    pub fn from_spec(spec: &ModelSpec) -> Result<Self> {
+        Self::from_model(Arc::new(NerModel::load(spec)?))
+    }
+
+    /// Shares the pool's copy of the model and runs async prediction on its threads.
+    pub fn from_pool(pool: &ModelPool, spec: &ModelSpec) -> Result<Self> {
+        let model = pool.get_or_load(spec, || NerModel::load(spec))?;
+        Ok(Self::from_model(model)?.with_inference(pool.inference().clone(), pool.batch_wait()))
+    }
+
+    fn from_model(model: Arc<NerModel>) -> Result<Self> {
+        let max_tokens = model.max_tokens;
+        Self {
+            tokenizer: model.tokenizer.clone(),
+            model,
+            batch_size: DEFAULT_BATCH_SIZE,
+            inference: None,
+            batcher: OnceLock::new(),
+        }
+        .with_window(max_tokens, default_stride(max_tokens))
+    }
+
+    /// Runs [`NerAnalyzer::predict_batch_async`] on `pool`, merging requests that
+    /// arrive within `max_wait` of each other into shared forward passes.
+    pub fn with_inference(mut self, pool: Arc<InferencePool>, max_wait: Duration) -> Self {
+        self.inference = Some((pool, max_wait));
+        self
+    }
+}
+
+impl NerModel {
+    fn load(spec: &ModelSpec) -> Result<Self> {
+        let device = Device::Cpu;
+        let files = ModelFiles::fetch(spec)?;
+        let config_json = files.read_config()?;
+        let config: ModelConfig = serde_json::from_str(&config_json)
+            .map_err(|e| UdoError::Config(format!("Invalid NER model config: {}", e)))?;
+        let labels = label_list(&config.id2label)?;
+        let model_type = config.model_type.as_deref().unwrap_or("bert");
+
+        let loading = LoadReport::start(spec.precision());
+        let (encoder, weight, bias, weights_bytes) = if spec.precision() == Precision::F32 {
+            let vb = unsafe {
+                VarBuilder::from_mmaped_safetensors(&[&files.weights], DType::F32, &device)
+                    .map_err(model_err)?
+            };
+            let encoder = Encoder::load(model_type, &config_json, vb)?;
+
+            let tensors = unsafe { MmapedSafetensors::new(&files.weights) }.map_err(model_err)?;
+            let weight = tensors.load("classifier.weight", &device).map_err(|e| {
+                UdoError::AiModel(format!("NER model has no token classifier: {}", e))
+            })?;
+            let bias = tensors.load("classifier.bias", &device).ok();
+            let bytes = std::fs::metadata(&files.weights)?.len();
+            (encoder, weight, bias, bytes)
+        } else {
+            // Only BERT has an encoder that runs below F32.
+            if model_type != "bert" {
+                return Err(UdoError::Config(format!(
+                    "NER model type '{}' only loads at f32 precision, not {}",
+                    model_type,
+                    spec.precision()
+                )));
+            }
+            let bert_config: bert::Config =
+                serde_json::from_str(&config_json).map_err(|e| UdoError::Config(e.to_string()))?;
+            let weights = Weights::open(&files.weights, spec.precision(), &device)?;
+            let encoder = CompactBert::load(&weights, &bert_config, Some("bert"))?;
+            // The head is tiny; it stays F32 like the hidden states it reads.
+            let shape = (labels.len(), bert_config.hidden_size);
+            if !weights.contains("classifier.weight") {
+                return Err(UdoError::AiModel(
+                    "NER model has no token classifier".to_string(),
+                ));
+            }
+            let weight = weights
+                .tensor("classifier.weight", shape)?
+                .to_dtype(DType::F32)
+                .map_err(model_err)?;
+            let bias = if weights.contains("classifier.bias") {
+                let bias = weights.tensor("classifier.bias", labels.len())?;
+                Some(bias.to_dtype(DType::F32).map_err(model_err)?)
+            } else {
+                None
+            };
+            let bytes = weights.bytes();
+            (Encoder::Bert(Bert::Compact(encoder)), weight, bias, bytes)
+        };
+        let num_labels = weight.dim(0).map_err(model_err)?;
+        if num_labels != labels.len() {
+            return Err(UdoError::AiModel(format!(
+                "NER classifier has {} outputs but id2label has {} labels",
+                num_labels,
+                labels.len()
+            )));
+        }
+
+        let tokenizer = files.load_tokenizer()?;
+        let pad_id = tokenizer
+            .get_padding()
+            .map(|p| p.pad_id)
+            .or(config.pad_token_id)
+            .unwrap_or(0);
+        // Windows cannot be longer than the model's position embeddings.
+        let max_tokens = config
+            .max_position_embeddings
+            .map_or(DEFAULT_MAX_TOKENS, |max| max.min(DEFAULT_MAX_TOKENS));
+        let classifier = Linear::new(weight, bias);
+        let report = loading.finish(weights_bytes, || {
+            let (ids, mask) = sample_batch(&tokenizer, &device)?;
+            classifier.forward(&encoder.forward(&ids, &mask)?)?;
+            Ok(())
+        })?;
+        info!(model = %spec, model_type = %model_type, labels = ?labels, "Loaded NER model");
+        report.log(spec, "Model load cost");
+
+        Ok(Self {
+            encoder,
+            classifier,
+            labels,
+            tokenizer,
+            device,
+            pad_id,
+            max_tokens,
+            report,
+        })
+    }
 }
 
 impl NerAnalyzer {
-    pub fn new(model_path: Option<std::path::PathBuf>) -> Result<Self> {
-        let container = BertModelContainer::load(
-            "dbmdz/bert-large-cased-finetuned-conll03-english",
-            model_path,
-        )?;
-
-        let labels = vec![
-            "O".to_string(),
-            "B-MISC".to_string(),
-            "I-MISC".to_string(),
-            "B-PER".to_string(),
-            "I-PER".to_string(),
-            "B-ORG".to_string(),
-            "I-ORG".to_string(),
-            "B-LOC".to_string(),
-            "I-LOC".to_string(),
-        ];
-
-        Ok(Self { container, labels })
-    }
-
-    pub fn predict(&self, text: &str) -> Result<Vec<(String, String)>> {
-        let tokens = self
-            .container
+    /// The model's labels, e.g. `B-PER`, indexed by class ID.
+    pub fn labels(&self) -> &[String] {
+        &self.model.labels
+    }
+
+    /// Precision, memory and latency of loading the model.
+    pub fn load_report(&self) -> &LoadReport {
+        &self.model.report
+    }
+
+    /// Longest window the model allows, special tokens included.
+    pub fn max_tokens(&self) -> usize {
+        self.model.max_tokens
+    }
+
+    /// Texts longer than `max_tokens` (special tokens included) are split into
+    /// windows that overlap by `stride` tokens. `max_tokens` cannot exceed
+    /// [`NerAnalyzer::max_tokens`].
+    pub fn with_window(mut self, max_tokens: usize, stride: usize) -> Result<Self> {
+        if max_tokens < 8 || stride * 2 >= max_tokens {
+            return Err(UdoError::Config(format!(
+                "Invalid NER window: max_tokens {} must be at least 8 and more than twice \
+                 the stride {}",
+                max_tokens, stride
+            )));
+        }
+        if max_tokens > self.model.max_tokens {
+            return Err(UdoError::Config(format!(
+                "Invalid NER window: max_tokens {} exceeds the model's limit of {}",
+                max_tokens, self.model.max_tokens
+            )));
+        }
+        self.tokenizer
+            .with_truncation(Some(TruncationParams {
+                direction: TruncationDirection::Right,
+                max_length: max_tokens,
+                strategy: TruncationStrategy::LongestFirst,
+                stride,
+            }))
+            .map_err(|e| UdoError::Config(format!("Invalid NER window: {}", e)))?;
+        Ok(self)
+    }
+
+    /// Maximum number of windows per forward pass.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Like [`NerAnalyzer::predict_batch`], but off the async runtime and micro-batched
+    /// with concurrent calls when an inference pool is set; inline otherwise.
+    pub async fn predict_batch_async(
+        self: &Arc<Self>,
+        texts: Vec<String>,
+    ) -> Result<Vec<Vec<EntitySpan>>> {
+        let Some((pool, max_wait)) = &self.inference else {
+            let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
+            return self.predict_batch(&texts);
+        };
+        let batcher = self.batcher.get_or_init(|| {
+            // Weak, as the batcher lives inside the analyzer.
+            let analyzer = Arc::downgrade(self);
+            MicroBatcher::new(pool.clone(), self.batch_size, *max_wait, move |texts| {
+                let analyzer = analyzer
+                    .upgrade()
+                    .ok_or_else(|| UdoError::AiModel("NER model was dropped".to_string()))?;
+                let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
+                analyzer.predict_batch(&texts)
+            })
+        });
+        batcher.call(texts).await
+    }
+
+    /// Entities of a single text. See [`NerAnalyzer::predict_batch`].
+    pub fn predict(&self, text: &str) -> Result<Vec<EntitySpan>> {
+        Ok(self.predict_batch(&[text])?.pop().unwrap_or_default())
+    }
+
+    /// Entities of each text, in input order. Texts are tokenized into windows,
+    /// padded and run through the model in batches; predictions for tokens covered
+    /// by two windows come from the window where the token has more context.
+    /// Consecutive `B-`/`I-` tokens of one type are merged into one entity, and
+    /// entities are widened to whole words so a name split into word pieces is
+    /// redacted completely. An entity's score is the mean probability of the tokens
+    /// that were labelled with it.
+    pub fn predict_batch(&self, texts: &[&str]) -> Result<Vec<Vec<EntitySpan>>> {
+        let encodings = self
             .tokenizer
-            .encode(text, true)
-            .map_err(|e| UdoError::AiModel(e.to_string()))?;
-        let token_ids = Tensor::new(tokens.get_ids(), &self.container.device)
-            .map_err(|e| UdoError::AiModel(e.to_string()))?
-            .unsqueeze(0)
-            .map_err(|e| UdoError::AiModel(e.to_string()))?;
-        let token_type_ids =
-            Tensor::zeros_like(&token_ids).map_err(|e| UdoError::AiModel(e.to_string()))?;
-
-        let logits = self
-            .container
+            .encode_batch(texts.to_vec(), true)
+            .map_err(model_err)?;
+
+        let mut windows: Vec<(usize, &Encoding)> = Vec::new();
+        for (text_idx, encoding) in encodings.iter().enumerate() {
+            windows.push((text_idx, encoding));
+            windows.extend(encoding.get_overflowing().iter().map(|w| (text_idx, w)));
+        }
+
+        let mut tokens: Vec<BTreeMap<(usize, usize), TokenPrediction>> =
+            vec![BTreeMap::new(); texts.len()];
+        for chunk in windows.chunks(self.batch_size) {
+            let encodings: Vec<&Encoding> = chunk.iter().map(|(_, w)| *w).collect();
+            let predictions = self.classify(&encodings)?;
+            for ((text_idx, window), predictions) in chunk.iter().zip(predictions) {
+                collect_tokens(window, &predictions, &mut tokens[*text_idx]);
+            }
+        }
+
+        Ok(texts
+            .iter()
+            .zip(tokens)
+            .map(|(text, tokens)| self.aggregate(text, tokens.into_values()))
+            .collect())
+    }
+
+    /// Runs one padded batch and returns the predicted label ID and its softmax
+    /// probability for every token of every window.
+    fn classify(&self, windows: &[&Encoding]) -> Result<Vec<Vec<(u32, f32)>>> {
+        let width = windows.iter().map(|w| w.len()).max().unwrap_or(0);
+
+        let mut ids = Vec::with_capacity(windows.len() * width);
+        let mut mask = Vec::with_capacity(windows.len() * width);
+        for window in windows {
+            let padding = width - window.len();
+            ids.extend_from_slice(window.get_ids());
+            ids.extend(std::iter::repeat_n(self.model.pad_id, padding));
+            mask.extend_from_slice(window.get_attention_mask());
+            mask.extend(std::iter::repeat_n(0u32, padding));
+        }
+        let shape = (windows.len(), width);
+        let token_ids = Tensor::from_vec(ids, shape, &self.model.device).map_err(model_err)?;
+        let attention_mask =
+            Tensor::from_vec(mask, shape, &self.model.device).map_err(model_err)?;
+
+        let hidden = self
             .model
-            .forward(&token_ids, &token_type_ids, None)
-            .map_err(|e| UdoError::AiModel(e.to_string()))?;
-        let logits = logits
-            .squeeze(0)
-            .map_err(|e| UdoError::AiModel(e.to_string()))?;
-
-        let predictions = logits
-            .argmax(1)
-            .map_err(|e| UdoError::AiModel(e.to_string()))?;
-        let pred_ids = predictions
-            .to_vec1::<u32>()
-            .map_err(|e| UdoError::AiModel(e.to_string()))?;
-
-        let mut results = Vec::new();
-        let token_strings = tokens.get_tokens();
-
-        for (idx, &pred_id) in pred_ids.iter().enumerate() {
-            let label = &self.labels[pred_id as usize];
-            if label != "O" {
-                debug!(token = %token_strings[idx], label = %label, "PII Entity detected");
-                results.push((token_strings[idx].clone(), label.clone()));
+            .encoder
+            .forward(&token_ids, &attention_mask)
+            .map_err(model_err)?;
+        let logits = self.model.classifier.forward(&hidden).map_err(model_err)?;
+        let probs = candle_nn::ops::softmax(&logits, D::Minus1)
+            .map_err(model_err)?
+            .to_vec3::<f32>()
+            .map_err(model_err)?;
+
+        Ok(probs
+            .into_iter()
+            .zip(windows)
+            .map(|(rows, window)| {
+                rows.into_iter()
+                    .take(window.len())
+                    .map(|row| {
+                        row.iter()
+                            .enumerate()
+                            .fold((0u32, f32::MIN), |best, (id, &p)| {
+                                if p > best.1 {
+                                    (id as u32, p)
+                                } else {
+                                    best
+                                }
+                            })
+                    })
+                    .collect()
+            })
+            .collect())
+    }
+
+    /// Merges per-token predictions, ordered by offset, into entities.
+    fn aggregate(
+        &self,
+        text: &str,
+        tokens: impl Iterator<Item = TokenPrediction>,
+    ) -> Vec<EntitySpan> {
+        let mut spans: Vec<EntitySpan> = Vec::new();
+        let mut token_counts: Vec<u32> = Vec::new();
+        let mut last_word = None;
+        for token in tokens {
+            let (start, end) = (token.start, token.end);
+
+            // Word pieces following an entity token belong to the same entity.
+            if token.word.is_some() && token.word == last_word {
+                if let Some(span) = spans.last_mut() {
+                    span.end = span.end.max(end);
+                }
+                continue;
             }
+
+            let label = match self.model.labels.get(token.label as usize) {
+                Some(label) if label != "O" => label,
+                _ => {
+                    last_word = None;
+                    continue;
+                }
+            };
+            // BIO, BIOES and BILOU tags; plain labels (IO tagging) continue an
+            // adjacent entity of the same type.
+            let (prefix, kind) = match label.split_once('-') {
+                Some((prefix, kind)) if prefix.len() == 1 => (prefix, kind),
+                _ => ("I", label.as_str()),
+            };
+
+            let continues = matches!(prefix, "I" | "E" | "L")
+                && spans.last().is_some_and(|span| {
+                    span.label == kind
+                        && span.end <= start
+                        && text
+                            .get(span.end..start)
+                            .is_some_and(|gap| gap.trim().is_empty())
+                });
+            match (spans.last_mut(), token_counts.last_mut()) {
+                (Some(span), Some(count)) if continues => {
+                    span.end = end;
+                    span.score += token.prob;
+                    *count += 1;
+                }
+                _ => {
+                    spans.push(EntitySpan {
+                        label: kind.to_string(),
+                        start,
+                        end,
+                        score: token.prob,
+                    });
+                    token_counts.push(1);
+                }
+            }
+            debug!(label = %kind, start = %start, end = %end, "PII Entity detected");
+            last_word = token.word;
+        }
+
+        for (span, count) in spans.iter_mut().zip(token_counts) {
+            span.score /= count as f32;
         }
+        spans
+    }
+}
+
+/// Orders `id2label` (whose keys are stringified IDs) into a list indexed by ID.
+fn label_list(id2label: &HashMap<String, String>) -> Result<Vec<String>> {
+    if id2label.is_empty() {
+        return Err(UdoError::Config(
+            "NER model config has no id2label".to_string(),
+        ));
+    }
+    let mut labels = vec![None; id2label.len()];
+    for (id, label) in id2label {
+        let slot = id
+            .parse::<usize>()
+            .ok()
+            .and_then(|id| labels.get_mut(id))
+            .ok_or_else(|| UdoError::Config(format!("Invalid id2label ID '{}'", id)))?;
+        *slot = Some(label.clone());
+    }
+    labels
+        .into_iter()
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| UdoError::Config("id2label IDs are not contiguous".to_string()))
+}
+
+#[derive(Debug, Clone)]
+struct TokenPrediction {
+    start: usize,
+    end: usize,
+    word: Option<u32>,
+    label: u32,
+    prob: f32,
+    /// Distance to the nearer edge of the window the prediction came from.
+    context: usize,
+}
+
+/// Adds a window's token predictions to `tokens`, keyed by byte offsets. Where
+/// windows overlap, the prediction made further from a window edge wins.
+fn collect_tokens(
+    window: &Encoding,
+    predictions: &[(u32, f32)],
+    tokens: &mut BTreeMap<(usize, usize), TokenPrediction>,
+) {
+    let special = window.get_special_tokens_mask();
+    let content: Vec<usize> = (0..window.len()).filter(|&i| special[i] == 0).collect();
+    for (pos, &idx) in content.iter().enumerate() {
+        let (start, end) = window.get_offsets()[idx];
+        let (label, prob) = predictions[idx];
+        let prediction = TokenPrediction {
+            start,
+            end,
+            word: window.get_word_ids()[idx],
+            label,
+            prob,
+            context: pos.min(content.len() - 1 - pos),
+        };
+        match tokens.get(&(start, end)) {
+            Some(existing) if existing.context >= prediction.context => {}
+            _ => {
+                tokens.insert((start, end), prediction);
+            }
+        }
+    }
+}
+
+/// An entity found by [`NerAnalyzer::predict_batch`]. `start` and `end` are byte
+/// offsets into the input and always fall on character boundaries.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntitySpan {
+    /// Entity type without the BIO prefix, e.g. `PER`.
+    pub label: String,
+    pub start: usize,
+    pub end: usize,
+    /// Mean softmax probability of the entity's labelled tokens.
+    pub score: f32,
+}
 
-        Ok(results)
+impl EntitySpan {
+    /// The entity's position in `text` counted in characters rather than bytes.
+    pub fn char_range(&self, text: &str) -> std::ops::Range<usize> {
+        let start = text[..self.start].chars().count();
+        start..start + text[self.start..self.end].chars().count()
     }
 }
@@ -0,0 +1,269 @@
+use crate::core::error::{Result, UdoError};
+use regex::Regex;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// Extra check run on a regex match to weed out false positives (checksums, ranges).
+pub type Validator = fn(&str) -> bool;
+
+/// A regex-based PII detector. When the pattern has a capture group, only the first
+/// group is reported as the PII span (the rest is context such as "DOB:").
+pub struct Detector {
+    pub id: &'static str,
+    pub regex: Regex,
+    pub validator: Option<Validator>,
+    pub confidence: f32,
+}
+
+/// A PII occurrence inside a string, as byte offsets.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PiiMatch {
+    pub detector: &'static str,
+    pub start: usize,
+    pub end: usize,
+    pub confidence: f32,
+}
+
+/// IDs of the detectors shipped with udo, in evaluation order.
+pub const BUILTIN_DETECTORS: &[&str] = &[
+    "email",
+    "credit_card",
+    "iban",
+    "us_ssn",
+    "ipv4",
+    "ipv6",
+    "mac_address",
+    "phone",
+    "passport",
+    "date_of_birth",
+    "street_address",
+];
+
+fn builtin(id: &str) -> Result<Detector> {
+    let (id, pattern, validator, confidence): (&'static str, &str, Option<Validator>, f32) =
+        match id {
+            "email" => (
+                "email",
+                r"(?i)[A-Z0-9._%+-]+@[A-Z0-9.-]+\.[A-Z]{2,}",
+                None,
+                0.95,
+            ),
+            "credit_card" => (
+                "credit_card",
+                r"\b\d(?:[ -]?\d){12,18}\b",
+                Some(luhn_valid),
+                0.9,
+            ),
+            "iban" => (
+                "iban",
+                r"\b[A-Z]{2}\d{2}(?: ?[A-Z0-9]){11,30}\b",
+                Some(iban_valid),
+                0.9,
+            ),
+            "us_ssn" => ("us_ssn", r"\b\d{3}-\d{2}-\d{4}\b", Some(ssn_valid), 0.85),
+            "phone" => (
+                "phone",
+                // E.164, then common national layouts: (555) 123-4567, 555-123-4567, 030 1234567
+                r"(?:\+[1-9]\d{7,14}\b|\+?\b\d{1,3}[ .-]\(?\d{2,4}\)?[ .-]\d{3,4}[ .-]?\d{3,4}\b|\(\d{3}\) ?\d{3}[ .-]\d{4}\b|\b\d{3}[.-]\d{3}[.-]\d{4}\b|\b0\d{2,4}[ /-]\d{5,8}\b)",
+                Some(phone_valid),
+                0.7,
+            ),
+            "ipv4" => (
+                "ipv4",
+                r"\b\d{1,3}(?:\.\d{1,3}){3}\b",
+                Some(|s| s.parse::<Ipv4Addr>().is_ok()),
+                0.8,
+            ),
+            "ipv6" => (
+                "ipv6",
+                // Not inside a word or path (`std::vec`, `Vec<u8>::new`): a boundary before
+                // the address, and after it a word boundary or, for `...::`, no word.
+                r"(?i)(?:^|[^0-9a-z_:.])((?:[0-9a-f]{0,4}:){2,7}(?:[0-9a-f]{1,4}\b|\B))",
+                Some(ipv6_valid),
+                0.8,
+            ),
+            "mac_address" => (
+                "mac_address",
+                r"\b[0-9A-Fa-f]{2}(?:[:-][0-9A-Fa-f]{2}){5}\b",
+                None,
+                0.8,
+            ),
+            "passport" => (
+                "passport",
+                r"(?i)\bpassport(?:\s*(?:no\.?|number|num|#))?\s*[:#]?\s*([A-Z0-9]{6,9})\b",
+                None,
+                0.75,
+            ),
+            "date_of_birth" => (
+                "date_of_birth",
+                r"(?i)\b(?:dob|d\.o\.b\.|date of birth|birth ?date|born(?: on)?)\s*[:\-]?\s*(\d{1,4}[-/.]\d{1,2}[-/.]\d{1,4})",
+                None,
+                0.8,
+            ),
+            "street_address" => (
+                "street_address",
+                r"\b\d{1,6}\s+(?:[A-Z][A-Za-z]*\.?\s+){1,4}(?:Street|St|Avenue|Ave|Road|Rd|Boulevard|Blvd|Lane|Ln|Drive|Dr|Court|Ct|Way|Place|Pl|Terrace|Square|Sq)\b\.?",
+                None,
+                0.6,
+            ),
+            other => {
+                return Err(UdoError::Config(format!(
+                    "Unknown PII detector '{}'. Available: {}",
+                    other,
+                    BUILTIN_DETECTORS.join(", ")
+                )))
+            }
+        };
+
+    Ok(Detector {
+        id,
+        regex: Regex::new(pattern)
+            .map_err(|e| UdoError::Config(format!("Invalid regex for {}: {}", id, e)))?,
+        validator,
+        confidence,
+    })
+}
+
+pub struct DetectorRegistry {
+    detectors: Vec<Detector>,
+}
+
+impl DetectorRegistry {
+    /// All built-in detectors.
+    pub fn builtin() -> Result<Self> {
+        Self::select(None, &[])
+    }
+
+    /// Built-in detectors filtered by an optional allow-list and a deny-list of IDs.
+    pub fn select(enabled: Option<&[String]>, disabled: &[String]) -> Result<Self> {
+        for id in enabled.unwrap_or_default().iter().chain(disabled) {
+            if !BUILTIN_DETECTORS.contains(&id.as_str()) {
+                builtin(id)?;
+            }
+        }
+
+        let detectors = BUILTIN_DETECTORS
+            .iter()
+            .filter(|id| enabled.is_none_or(|e| e.iter().any(|x| x == *id)))
+            .filter(|id| !disabled.iter().any(|x| x == *id))
+            .map(|id| builtin(id))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { detectors })
+    }
+
+    pub fn push(&mut self, detector: Detector) {
+        self.detectors.push(detector);
+    }
+
+    pub fn ids(&self) -> Vec<&'static str> {
+        self.detectors.iter().map(|d| d.id).collect()
+    }
+
+    /// Non-overlapping matches in `text`, ordered by position. Where two detectors
+    /// claim overlapping spans, the earlier detector in the registry wins.
+    pub fn find(&self, text: &str) -> Vec<PiiMatch> {
+        let mut found: Vec<PiiMatch> = Vec::new();
+        for detector in &self.detectors {
+            for caps in detector.regex.captures_iter(text) {
+                let Some(m) = caps.get(1).or_else(|| caps.get(0)) else {
+                    continue;
+                };
+                if let Some(validate) = detector.validator
+                    && !validate(m.as_str())
+                {
+                    continue;
+                }
+                if found.iter().any(|f| m.start() < f.end && f.start < m.end()) {
+                    continue;
+                }
+                found.push(PiiMatch {
+                    detector: detector.id,
+                    start: m.start(),
+                    end: m.end(),
+                    confidence: detector.confidence,
+                });
+            }
+        }
+        found.sort_by_key(|m| m.start);
+        found
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        !self.find(text).is_empty()
+    }
+}
+
+pub fn luhn_valid(candidate: &str) -> bool {
+    let digits: Vec<u32> = candidate.chars().filter_map(|c| c.to_digit(10)).collect();
+    if !(13..=19).contains(&digits.len()) {
+        return false;
+    }
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| {
+            if i % 2 == 1 {
+                let doubled = d * 2;
+                if doubled > 9 {
+                    doubled - 9
+                } else {
+                    doubled
+                }
+            } else {
+                d
+            }
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+/// ISO 13616 mod-97 check.
+pub fn iban_valid(candidate: &str) -> bool {
+    let compact: String = candidate.chars().filter(|c| !c.is_whitespace()).collect();
+    if !(15..=34).contains(&compact.len()) {
+        return false;
+    }
+    let (head, tail) = compact.split_at(4);
+    let mut remainder: u32 = 0;
+    for c in tail.chars().chain(head.chars()) {
+        let value = match c.to_digit(36) {
+            Some(v) => v,
+            None => return false,
+        };
+        // Letters expand to two digits (A = 10 ... Z = 35).
+        remainder = if value >= 10 {
+            (remainder * 100 + value) % 97
+        } else {
+            (remainder * 10 + value) % 97
+        };
+    }
+    remainder == 1
+}
+
+/// Rejects SSNs the SSA never issues: area 000, 666 or 9xx, group 00, serial 0000.
+fn ssn_valid(candidate: &str) -> bool {
+    let parts: Vec<&str> = candidate.split('-').collect();
+    let [area, group, serial] = parts.as_slice() else {
+        return false;
+    };
+    *area != "000"
+        && *area != "666"
+        && !area.starts_with('9')
+        && *group != "00"
+        && *serial != "0000"
+}
+
+/// A parseable address with at least two non-empty groups, so `d::` and `::1` are
+/// left alone, that is not the unspecified `::`.
+fn ipv6_valid(candidate: &str) -> bool {
+    let groups = candidate.split(':').filter(|g| !g.is_empty()).count();
+    groups >= 2
+        && candidate
+            .parse::<Ipv6Addr>()
+            .is_ok_and(|addr| !addr.is_unspecified())
+}
+
+fn phone_valid(candidate: &str) -> bool {
+    let digits = candidate.chars().filter(char::is_ascii_digit).count();
+    (7..=15).contains(&digits)
+}
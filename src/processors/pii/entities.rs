@@ -0,0 +1,54 @@
+use crate::core::config::{EntityRule, PiiAction};
+use crate::core::error::{Result, UdoError};
+use std::collections::HashMap;
+
+/// Which NER entity types a masker acts on, how confident the model must be, and
+/// what happens to each type.
+#[derive(Debug, Clone, Default)]
+pub struct EntityRules {
+    rules: HashMap<String, EntityRule>,
+    min_score: f32,
+}
+
+impl EntityRules {
+    /// Only the types in `rules` are acted on, unless it is empty. `min_score`
+    /// applies to types without their own threshold.
+    pub fn new(rules: HashMap<String, EntityRule>, min_score: f32) -> Result<Self> {
+        for (label, rule) in &rules {
+            if let Some(action @ (PiiAction::Drop | PiiAction::Allow)) = rule.action {
+                return Err(UdoError::Config(format!(
+                    "Entity type '{}' cannot use action '{:?}'; use a field policy instead",
+                    label, action
+                )));
+            }
+        }
+        Ok(Self { rules, min_score })
+    }
+
+    /// The configured entity types; empty when every type is acted on.
+    pub fn labels(&self) -> impl Iterator<Item = &str> {
+        self.rules.keys().map(String::as_str)
+    }
+
+    /// Whether any entity type is configured with `action`.
+    pub fn uses(&self, action: PiiAction) -> bool {
+        self.rules.values().any(|r| r.action == Some(action))
+    }
+
+    /// The action for an entity found in a field whose action is `field_action`,
+    /// or `None` when the entity should be ignored.
+    pub fn action(&self, label: &str, score: f32, field_action: PiiAction) -> Option<PiiAction> {
+        let rule = self.rules.get(label);
+        if rule.is_none() && !self.rules.is_empty() {
+            return None;
+        }
+        let min_score = rule.and_then(|r| r.min_score).unwrap_or(self.min_score);
+        if score < min_score {
+            return None;
+        }
+        if matches!(field_action, PiiAction::DetectOnly | PiiAction::Tag) {
+            return Some(field_action);
+        }
+        Some(rule.and_then(|r| r.action).unwrap_or(field_action))
+    }
+}
@@ -0,0 +1,332 @@
+use crate::core::config::PiiAction;
+use crate::core::error::Result;
+use crate::core::pipeline::DataProcessor;
+use crate::utils::path::{format_path, PathSegment};
+use async_trait::async_trait;
+use simd_json::{owned::Object, prelude::*, OwnedValue};
+use std::sync::Arc;
+use tracing::debug;
+
+pub mod detectors;
+pub mod entities;
+pub mod policy;
+pub mod redact;
+pub mod report;
+pub mod tokenize;
+
+use detectors::DetectorRegistry;
+use policy::FieldPolicy;
+use redact::{RedactionTemplates, Redactor, Span};
+use report::{Detection, DetectionReport};
+use tokenize::Pseudonymizer;
+
+/// Record field listing the PII found in fields whose action is `tag`, as `path`,
+/// `label` and the byte span (`start`, `end`) within the field's original text.
+pub const ENTITIES_FIELD: &str = "_entities";
+
+/// A `tag` finding: where it is and what it is.
+fn entity_tag(path: &[PathSegment], label: &str, start: usize, end: usize) -> OwnedValue {
+    let mut tag = Object::new();
+    tag.insert("path".into(), OwnedValue::from(format_path(path)));
+    tag.insert("label".into(), OwnedValue::from(label));
+    tag.insert("start".into(), OwnedValue::from(start as u64));
+    tag.insert("end".into(), OwnedValue::from(end as u64));
+    OwnedValue::from(tag)
+}
+
+/// Appends `tags` to the record's `_entities` array, which an earlier masker may have
+/// started.
+fn annotate(record: &mut OwnedValue, tags: Vec<OwnedValue>) {
+    if tags.is_empty() {
+        return;
+    }
+    let Some(obj) = record.as_object_mut() else {
+        return;
+    };
+    match obj.get_mut(ENTITIES_FIELD).and_then(|v| v.as_array_mut()) {
+        Some(existing) => existing.extend(tags),
+        None => {
+            obj.insert(ENTITIES_FIELD.into(), OwnedValue::from(tags));
+        }
+    }
+}
+
+pub struct PiiMasker {
+    registry: DetectorRegistry,
+    policy: FieldPolicy,
+    templates: RedactionTemplates,
+    pseudonymizer: Option<Arc<Pseudonymizer>>,
+    report: DetectionReport,
+}
+
+impl PiiMasker {
+    /// Masker with every built-in detector enabled.
+    pub fn new(mask_mode: &str) -> Result<Self> {
+        Ok(Self::with_detectors(
+            mask_mode,
+            DetectorRegistry::builtin()?,
+        ))
+    }
+
+    pub fn with_detectors(mask_mode: &str, registry: DetectorRegistry) -> Self {
+        Self {
+            registry,
+            policy: FieldPolicy::new(PiiAction::from_mode(mask_mode)),
+            templates: RedactionTemplates::default(),
+            pseudonymizer: None,
+            report: DetectionReport::default(),
+        }
+    }
+
+    /// Replacement templates keyed by detector ID, e.g. `email: "<EMAIL>"`.
+    pub fn with_templates(mut self, templates: RedactionTemplates) -> Self {
+        self.templates = templates;
+        self
+    }
+
+    /// Keyed hashing and tokenization for the `hash` and `tokenize` modes.
+    pub fn with_pseudonymizer(mut self, pseudonymizer: Arc<Pseudonymizer>) -> Self {
+        self.pseudonymizer = Some(pseudonymizer);
+        self
+    }
+
+    /// Field-scoped actions; replaces the mode given at construction.
+    pub fn with_policy(mut self, policy: FieldPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    fn mask_value(
+        &self,
+        path: &[PathSegment],
+        action: PiiAction,
+        value: &mut OwnedValue,
+        redactor: &mut Redactor,
+        tags: &mut Vec<OwnedValue>,
+    ) -> Result<()> {
+        let Some(s) = value.as_str() else {
+            return Ok(());
+        };
+        let matches = self.registry.find(s);
+        if matches.is_empty() {
+            return Ok(());
+        }
+        if action == PiiAction::Tag {
+            tags.extend(
+                matches
+                    .iter()
+                    .map(|m| entity_tag(path, m.detector, m.start, m.end)),
+            );
+            return Ok(());
+        }
+        if action == PiiAction::DetectOnly {
+            let detections: Vec<Detection> = matches
+                .iter()
+                .map(|m| Detection {
+                    label: m.detector,
+                    start: m.start,
+                    end: m.end,
+                    confidence: m.confidence,
+                })
+                .collect();
+            debug!(path = %format_path(path), findings = %detections.len(), "PII detected");
+            self.report.record(path, s, &detections, &[]);
+            return Ok(());
+        }
+        let spans: Vec<Span> = matches
+            .iter()
+            .map(|m| Span {
+                label: m.detector,
+                start: m.start,
+                end: m.end,
+            })
+            .collect();
+        debug!(path = %format_path(path), spans = %spans.len(), "PII masked");
+        *value = OwnedValue::from(redactor.redact(s, &spans, action)?);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl DataProcessor for PiiMasker {
+    async fn process(&self, mut record: OwnedValue) -> Result<Option<OwnedValue>> {
+        let mut redactor = Redactor::new(&self.templates, self.pseudonymizer.as_deref());
+        let mut tags = Vec::new();
+        self.policy.walk(&mut record, &mut |path, action, value| {
+            self.mask_value(path, action, value, &mut redactor, &mut tags)
+        })?;
+        annotate(&mut record, tags);
+        Ok(Some(record))
+    }
+
+    fn metrics(&self) -> Vec<(String, u64)> {
+        self.report.metrics()
+    }
+
+    fn report(&self) -> Option<serde_json::Value> {
+        self.policy
+            .uses(PiiAction::DetectOnly)
+            .then(|| self.report.to_json())
+    }
+}
+
+#[cfg(feature = "ner")]
+use crate::processors::ner::EntitySpan;
+#[cfg(feature = "ner")]
+use entities::EntityRules;
+#[cfg(feature = "ner")]
+use tracing::warn;
+
+#[cfg(feature = "ner")]
+pub struct NerPiiMasker {
+    analyzer: Arc<crate::processors::ner::NerAnalyzer>,
+    policy: FieldPolicy,
+    templates: RedactionTemplates,
+    pseudonymizer: Option<Arc<Pseudonymizer>>,
+    entities: EntityRules,
+    report: DetectionReport,
+}
+
+#[cfg(feature = "ner")]
+impl NerPiiMasker {
+    pub fn new(analyzer: crate::processors::ner::NerAnalyzer, mask_mode: &str) -> Self {
+        Self {
+            analyzer: Arc::new(analyzer),
+            policy: FieldPolicy::new(PiiAction::from_mode(mask_mode)),
+            templates: RedactionTemplates::default(),
+            pseudonymizer: None,
+            entities: EntityRules::default(),
+            report: DetectionReport::default(),
+        }
+    }
+
+    /// Entity types to act on, with per-type thresholds and actions.
+    pub fn with_entities(mut self, entities: EntityRules) -> Self {
+        let known: Vec<&str> = self
+            .analyzer
+            .labels()
+            .iter()
+            .map(|l| l.split_once('-').map_or(l.as_str(), |(_, kind)| kind))
+            .collect();
+        for label in entities.labels() {
+            if !known.contains(&label) {
+                warn!(entity = %label, known = ?known, "NER model never predicts this entity type");
+            }
+        }
+        self.entities = entities;
+        self
+    }
+
+    /// Replacement templates keyed by entity label, e.g. `PER: "<PERSON_{n}>"`.
+    pub fn with_templates(mut self, templates: RedactionTemplates) -> Self {
+        self.templates = templates;
+        self
+    }
+
+    /// Keyed hashing and tokenization for the `hash` and `tokenize` modes.
+    pub fn with_pseudonymizer(mut self, pseudonymizer: Arc<Pseudonymizer>) -> Self {
+        self.pseudonymizer = Some(pseudonymizer);
+        self
+    }
+
+    /// Field-scoped actions; replaces the mode given at construction.
+    pub fn with_policy(mut self, policy: FieldPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    fn mask_entities(
+        &self,
+        path: &[PathSegment],
+        action: PiiAction,
+        value: &mut OwnedValue,
+        entities: &[EntitySpan],
+        redactor: &mut Redactor,
+        tags: &mut Vec<OwnedValue>,
+    ) -> Result<()> {
+        let Some(s) = value.as_str() else {
+            return Ok(());
+        };
+        let mut detections = Vec::new();
+        let mut spans = Vec::new();
+        // Everything the model found, acted on or not, stays out of report samples.
+        let mut found = Vec::with_capacity(entities.len());
+        for e in entities {
+            match self.entities.action(&e.label, e.score, action) {
+                None => found.push(Span {
+                    label: &e.label,
+                    start: e.start,
+                    end: e.end,
+                }),
+                Some(PiiAction::DetectOnly) => detections.push(Detection {
+                    label: &e.label,
+                    start: e.start,
+                    end: e.end,
+                    confidence: e.score,
+                }),
+                Some(PiiAction::Tag) => {
+                    tags.push(entity_tag(path, &e.label, e.start, e.end));
+                    found.push(Span {
+                        label: &e.label,
+                        start: e.start,
+                        end: e.end,
+                    });
+                }
+                Some(action) => {
+                    let span = Span {
+                        label: &e.label,
+                        start: e.start,
+                        end: e.end,
+                    };
+                    found.push(span.clone());
+                    spans.push((span, action));
+                }
+            }
+        }
+        if !detections.is_empty() {
+            debug!(path = %format_path(path), findings = %detections.len(), "PII detected");
+            self.report.record(path, s, &detections, &found);
+        }
+        if !spans.is_empty() {
+            *value = OwnedValue::from(redactor.redact_each(s, &spans)?);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "ner")]
+#[async_trait]
+impl DataProcessor for NerPiiMasker {
+    async fn process(&self, mut record: OwnedValue) -> Result<Option<OwnedValue>> {
+        let mut redactor = Redactor::new(&self.templates, self.pseudonymizer.as_deref());
+
+        // Collect every string the policy scans so the model sees the record as one
+        // batch, then apply the results in the same walk order.
+        let mut texts = Vec::new();
+        self.policy.walk(&mut record, &mut |_, _, value| {
+            texts.extend(value.as_str().map(str::to_string));
+            Ok(())
+        })?;
+        if texts.is_empty() {
+            return Ok(Some(record));
+        }
+        let mut entities = self.analyzer.predict_batch_async(texts).await?.into_iter();
+
+        let mut tags = Vec::new();
+        self.policy.walk(&mut record, &mut |path, action, value| {
+            let found = entities.next().unwrap_or_default();
+            self.mask_entities(path, action, value, &found, &mut redactor, &mut tags)
+        })?;
+        annotate(&mut record, tags);
+        Ok(Some(record))
+    }
+
+    fn metrics(&self) -> Vec<(String, u64)> {
+        self.report.metrics()
+    }
+
+    fn report(&self) -> Option<serde_json::Value> {
+        (self.policy.uses(PiiAction::DetectOnly) || self.entities.uses(PiiAction::DetectOnly))
+            .then(|| self.report.to_json())
+    }
+}
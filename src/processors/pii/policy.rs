@@ -0,0 +1,133 @@
+use crate::core::config::{PiiAction, PiiPolicy};
+use crate::core::error::{Result, UdoError};
+use crate::utils::path::{FieldSelector, PathSegment};
+use simd_json::{prelude::*, OwnedValue};
+
+/// A compiled [`PiiPolicy`] that decides, per field, whether and how a masker acts.
+#[derive(Debug, Clone)]
+pub struct FieldPolicy {
+    default: PiiAction,
+    include: Vec<FieldSelector>,
+    exclude: Vec<FieldSelector>,
+    rules: Vec<(FieldSelector, PiiAction)>,
+}
+
+impl FieldPolicy {
+    /// Scan every field and apply `default` to what is found.
+    pub fn new(default: PiiAction) -> Self {
+        Self {
+            default,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            rules: Vec::new(),
+        }
+    }
+
+    /// Compiles `policy`, falling back to the masker's `mode` for unlisted fields.
+    pub fn compile(policy: &PiiPolicy, mode: &str) -> Result<Self> {
+        let parse_all = |selectors: &[String]| {
+            selectors
+                .iter()
+                .map(|s| FieldSelector::parse(s))
+                .collect::<Result<Vec<_>>>()
+        };
+        let default = policy.default.unwrap_or(PiiAction::from_mode(mode));
+        if default == PiiAction::Drop {
+            return Err(UdoError::Config(
+                "PII policy default cannot be 'drop'".to_string(),
+            ));
+        }
+        Ok(Self {
+            default,
+            include: parse_all(&policy.include)?,
+            exclude: parse_all(&policy.exclude)?,
+            rules: policy
+                .fields
+                .iter()
+                .map(|rule| Ok((FieldSelector::parse(&rule.path)?, rule.action)))
+                .collect::<Result<Vec<_>>>()?,
+        })
+    }
+
+    pub fn default_action(&self) -> PiiAction {
+        self.default
+    }
+
+    /// Whether any field can end up with `action`.
+    pub fn uses(&self, action: PiiAction) -> bool {
+        self.default == action || self.rules.iter().any(|(_, a)| *a == action)
+    }
+
+    /// Walks `record`, removing dropped fields and calling `visit` with the location
+    /// and action of every string that should be scanned. Dropped array elements
+    /// become null so the positions of their siblings are kept.
+    pub fn walk<F>(&self, record: &mut OwnedValue, visit: &mut F) -> Result<()>
+    where
+        F: FnMut(&[PathSegment], PiiAction, &mut OwnedValue) -> Result<()>,
+    {
+        let mut path = Vec::new();
+        let included = self.include.is_empty();
+        self.walk_node(record, &mut path, included, self.default, visit)?;
+        Ok(())
+    }
+
+    /// Returns false when the node must be dropped.
+    fn walk_node<F>(
+        &self,
+        value: &mut OwnedValue,
+        path: &mut Vec<PathSegment>,
+        included: bool,
+        inherited: PiiAction,
+        visit: &mut F,
+    ) -> Result<bool>
+    where
+        F: FnMut(&[PathSegment], PiiAction, &mut OwnedValue) -> Result<()>,
+    {
+        if self.exclude.iter().any(|s| s.matches(path)) {
+            return Ok(true);
+        }
+        let rule = self
+            .rules
+            .iter()
+            .find(|(s, _)| s.matches(path))
+            .map(|(_, action)| *action);
+        // A field rule counts as an explicit include of the field.
+        let included = included || rule.is_some() || self.include.iter().any(|s| s.matches(path));
+        let action = rule.unwrap_or(inherited);
+
+        if action == PiiAction::Drop && !path.is_empty() {
+            return Ok(false);
+        }
+
+        // `allow` only skips scanning; children are still walked so that more
+        // specific rules below an allowed field apply.
+        if value.as_str().is_some() {
+            if included && action != PiiAction::Allow {
+                visit(path, action, value)?;
+            }
+        } else if let Some(obj) = value.as_object_mut() {
+            let mut dropped = Vec::new();
+            for (key, child) in obj.iter_mut() {
+                path.push(PathSegment::Key(key.clone()));
+                let keep = self.walk_node(child, path, included, action, visit)?;
+                path.pop();
+                if !keep {
+                    dropped.push(key.clone());
+                }
+            }
+            for key in dropped {
+                obj.remove(&key);
+            }
+        } else if let Some(arr) = value.as_array_mut() {
+            for (idx, child) in arr.iter_mut().enumerate() {
+                path.push(PathSegment::Index(idx));
+                let keep = self.walk_node(child, path, included, action, visit)?;
+                path.pop();
+                if !keep {
+                    *child = OwnedValue::null();
+                }
+            }
+        }
+        Ok(true)
+    }
+}
@@ -0,0 +1,130 @@
+use crate::core::config::PiiAction;
+use crate::core::error::{Result, UdoError};
+use crate::processors::pii::tokenize::Pseudonymizer;
+use std::collections::HashMap;
+
+/// Replacement text per entity label (a detector ID such as `email`, or an NER label
+/// such as `PER`). Templates may use `{label}` and `{n}`, where `{n}` numbers the
+/// distinct values of a label within one record: `<PERSON_{n}>` gives `<PERSON_1>`,
+/// `<PERSON_2>`, and a repeated name keeps its number. The `default` key applies to
+/// labels without their own template.
+#[derive(Debug, Clone, Default)]
+pub struct RedactionTemplates {
+    templates: HashMap<String, String>,
+}
+
+impl RedactionTemplates {
+    pub fn new(templates: HashMap<String, String>) -> Self {
+        Self { templates }
+    }
+
+    fn get(&self, label: &str) -> Option<&str> {
+        self.templates
+            .get(label)
+            .or_else(|| self.templates.get("default"))
+            .map(String::as_str)
+    }
+}
+
+/// A labelled byte range of a string to redact.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span<'a> {
+    pub label: &'a str,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Rewrites spans of strings within one record. Keep one `Redactor` per record so
+/// `{n}` numbering is consistent across its fields. Without a pseudonymizer, the
+/// `Hash` and `Tokenize` actions fail: their output must be keyed to be joinable.
+pub struct Redactor<'t> {
+    templates: &'t RedactionTemplates,
+    pseudonymizer: Option<&'t Pseudonymizer>,
+    numbering: HashMap<(String, String), usize>,
+    counts: HashMap<String, usize>,
+}
+
+impl<'t> Redactor<'t> {
+    pub fn new(
+        templates: &'t RedactionTemplates,
+        pseudonymizer: Option<&'t Pseudonymizer>,
+    ) -> Self {
+        Self {
+            templates,
+            pseudonymizer,
+            numbering: HashMap::new(),
+            counts: HashMap::new(),
+        }
+    }
+
+    /// Returns `text` with every span replaced: by its keyed hash for `Hash`, by a
+    /// format-preserving token for `Tokenize`, and by its template otherwise. Spans
+    /// must not overlap; spans that do not fall on character boundaries are left
+    /// untouched.
+    pub fn redact(&mut self, text: &str, spans: &[Span], action: PiiAction) -> Result<String> {
+        let spans: Vec<(Span, PiiAction)> = spans.iter().map(|s| (s.clone(), action)).collect();
+        self.redact_each(text, &spans)
+    }
+
+    /// Like [`Redactor::redact`], with an action per span.
+    pub fn redact_each(&mut self, text: &str, spans: &[(Span, PiiAction)]) -> Result<String> {
+        let mut spans: Vec<&(Span, PiiAction)> = spans
+            .iter()
+            .filter(|(s, _)| {
+                s.start < s.end
+                    && s.end <= text.len()
+                    && text.is_char_boundary(s.start)
+                    && text.is_char_boundary(s.end)
+            })
+            .collect();
+        spans.sort_by_key(|(s, _)| s.start);
+
+        let mut out = String::with_capacity(text.len());
+        let mut last = 0;
+        for (span, action) in spans {
+            if span.start < last {
+                continue;
+            }
+            out.push_str(&text[last..span.start]);
+            out.push_str(&self.replacement(span.label, &text[span.start..span.end], *action)?);
+            last = span.end;
+        }
+        out.push_str(&text[last..]);
+        Ok(out)
+    }
+
+    fn replacement(&mut self, label: &str, value: &str, action: PiiAction) -> Result<String> {
+        match (action, self.pseudonymizer) {
+            (PiiAction::Hash, Some(p)) => return Ok(p.hash(value)),
+            (PiiAction::Tokenize, Some(p)) => return p.tokenize(label, value),
+            (PiiAction::Hash | PiiAction::Tokenize, None) => {
+                return Err(UdoError::Config(format!(
+                    "PII action '{:?}' requires a key",
+                    action
+                )));
+            }
+            _ => {}
+        }
+
+        let Some(template) = self.templates.get(label) else {
+            return Ok(match label {
+                "email" => "****@masked.com".to_string(),
+                _ => "[REDACTED]".to_string(),
+            });
+        };
+
+        let mut out = template.replace("{label}", label);
+        if out.contains("{n}") {
+            let next = self.counts.get(label).copied().unwrap_or(0) + 1;
+            let n = *self
+                .numbering
+                .entry((label.to_string(), value.to_string()))
+                .or_insert_with(|| {
+                    self.counts.insert(label.to_string(), next);
+                    next
+                });
+            out = out.replace("{n}", &n.to_string());
+        }
+        Ok(out)
+    }
+}
@@ -0,0 +1,173 @@
+use super::redact::Span;
+use crate::utils::path::{format_pattern, PathSegment};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+const MAX_SAMPLES: usize = 3;
+/// Characters of context kept on each side of a finding in a sample snippet.
+const SAMPLE_CONTEXT: usize = 20;
+
+/// One PII occurrence inside a string, as a byte range.
+#[derive(Debug, Clone)]
+pub struct Detection<'a> {
+    pub label: &'a str,
+    pub start: usize,
+    pub end: usize,
+    pub confidence: f32,
+}
+
+/// Aggregated findings for one field path and PII type.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldFinding {
+    /// Field path with array indices folded, e.g. `$.tickets[*].body`.
+    pub path: String,
+    pub pii_type: String,
+    pub count: u64,
+    pub mean_confidence: f64,
+    pub max_confidence: f64,
+    /// Context around the first few findings, with all PII replaced by `<type>`.
+    pub samples: Vec<String>,
+}
+
+#[derive(Default)]
+struct Entry {
+    count: u64,
+    confidence_sum: f64,
+    max_confidence: f32,
+    samples: Vec<String>,
+}
+
+/// Collects `detect_only` findings across all records of a run.
+#[derive(Default)]
+pub struct DetectionReport {
+    entries: Mutex<BTreeMap<(String, String), Entry>>,
+}
+
+impl DetectionReport {
+    /// Records the `detections` found in `text` at `path`. Samples are cut from `text`
+    /// with every detection and every `other` span (PII the caller found but does not
+    /// report, e.g. masked or below its threshold) replaced by its placeholder.
+    pub fn record(
+        &self,
+        path: &[PathSegment],
+        text: &str,
+        detections: &[Detection],
+        other: &[Span],
+    ) {
+        if detections.is_empty() {
+            return;
+        }
+        let mut spans: Vec<(usize, usize, &str, Option<&Detection>)> = detections
+            .iter()
+            .map(|d| (d.start, d.end, d.label, Some(d)))
+            .chain(other.iter().map(|s| (s.start, s.end, s.label, None)))
+            .filter(|&(start, end, _, _)| {
+                start < end
+                    && end <= text.len()
+                    && text.is_char_boundary(start)
+                    && text.is_char_boundary(end)
+            })
+            .collect();
+        spans.sort_by_key(|&(start, end, _, _)| (start, std::cmp::Reverse(end)));
+
+        // Redact every span once, overlapping ones under a single placeholder,
+        // remembering where each detection's placeholder ended up.
+        let mut redacted = String::with_capacity(text.len());
+        let mut placed = Vec::with_capacity(detections.len());
+        let mut placeholder = (0, 0);
+        let mut last = 0;
+        for (start, end, label, detection) in spans {
+            if start >= last {
+                redacted.push_str(&text[last..start]);
+                let at = redacted.len();
+                redacted.push_str(&format!("<{}>", label));
+                placeholder = (at, redacted.len());
+            }
+            last = last.max(end);
+            if let Some(d) = detection {
+                placed.push((d, placeholder.0, placeholder.1));
+            }
+        }
+        redacted.push_str(&text[last..]);
+
+        let field = format_pattern(path);
+        let Ok(mut entries) = self.entries.lock() else {
+            return;
+        };
+        for (d, start, end) in placed {
+            let entry = entries
+                .entry((field.clone(), d.label.to_string()))
+                .or_default();
+            entry.count += 1;
+            entry.confidence_sum += d.confidence as f64;
+            entry.max_confidence = entry.max_confidence.max(d.confidence);
+            if entry.samples.len() < MAX_SAMPLES {
+                let sample = snippet(&redacted, start, end);
+                if !entry.samples.contains(&sample) {
+                    entry.samples.push(sample);
+                }
+            }
+        }
+    }
+
+    pub fn findings(&self) -> Vec<FieldFinding> {
+        let Ok(entries) = self.entries.lock() else {
+            return Vec::new();
+        };
+        entries
+            .iter()
+            .map(|((path, pii_type), entry)| FieldFinding {
+                path: path.clone(),
+                pii_type: pii_type.clone(),
+                count: entry.count,
+                mean_confidence: round(entry.confidence_sum / entry.count as f64),
+                max_confidence: round(entry.max_confidence as f64),
+                samples: entry.samples.clone(),
+            })
+            .collect()
+    }
+
+    /// Total findings per PII type, as `detected.<type>` metrics.
+    pub fn metrics(&self) -> Vec<(String, u64)> {
+        let mut totals: BTreeMap<String, u64> = BTreeMap::new();
+        for finding in self.findings() {
+            *totals.entry(finding.pii_type).or_default() += finding.count;
+        }
+        totals
+            .into_iter()
+            .map(|(pii_type, count)| (format!("detected.{}", pii_type), count))
+            .collect()
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({ "findings": self.findings() })
+    }
+}
+
+/// Confidences are model outputs; four decimals is all the precision they carry.
+fn round(confidence: f64) -> f64 {
+    (confidence * 1e4).round() / 1e4
+}
+
+fn snippet(redacted: &str, start: usize, end: usize) -> String {
+    let before: Vec<char> = redacted[..start]
+        .chars()
+        .rev()
+        .take(SAMPLE_CONTEXT)
+        .collect();
+    let before: String = before.into_iter().rev().collect();
+    let after: String = redacted[end..].chars().take(SAMPLE_CONTEXT).collect();
+    format!(
+        "{}{}{}{}{}",
+        if before.len() < start { "..." } else { "" },
+        before,
+        &redacted[start..end],
+        after,
+        if end + after.len() < redacted.len() {
+            "..."
+        } else {
+            ""
+        }
+    )
+}
@@ -0,0 +1,327 @@
+use crate::core::error::{Result, UdoError};
+use crate::processors::pii::detectors::luhn_valid;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::warn;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Derivations tried per value before tokenization gives up on finding a token the
+/// vault does not already hold for another value.
+const TOKEN_ROUNDS: u32 = 64;
+
+fn hmac(key: &[u8], parts: &[&[u8]]) -> [u8; 32] {
+    let mut mac = <HmacSha256 as Mac>::new_from_slice(key).expect("HMAC accepts any key length");
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+/// Keyed pseudonymization: HMAC-SHA256 hashes and deterministic, format-preserving
+/// tokens. Separate subkeys are derived from the master key for each purpose.
+pub struct Pseudonymizer {
+    hash_key: [u8; 32],
+    token_key: [u8; 32],
+    vault: Option<TokenVault>,
+}
+
+impl Pseudonymizer {
+    pub fn new(key: &[u8]) -> Self {
+        Self {
+            hash_key: hmac(key, &[b"udo-pii-hash"]),
+            token_key: hmac(key, &[b"udo-pii-token"]),
+            vault: None,
+        }
+    }
+
+    /// Records every token in `vault` so it can be re-identified later.
+    pub fn with_vault(mut self, vault: TokenVault) -> Self {
+        self.vault = Some(vault);
+        self
+    }
+
+    /// Hex HMAC-SHA256 of `value`.
+    pub fn hash(&self, value: &str) -> String {
+        hex::encode(hmac(&self.hash_key, &[value.as_bytes()]))
+    }
+
+    /// Deterministic token with the shape of `value`: letters stay letters of the same
+    /// case and digits stay digits, while separators are kept. Emails keep their
+    /// top-level domain; card numbers keep their last four digits and stay Luhn-valid.
+    ///
+    /// Short values have few possible tokens, so two values can draw the same one. With
+    /// a vault, a token already held for another value is redrawn from the next round
+    /// of the key stream, so every token reveals exactly one value; the vault keeps the
+    /// choice stable across runs.
+    pub fn tokenize(&self, label: &str, value: &str) -> Result<String> {
+        let Some(vault) = &self.vault else {
+            return Ok(self.token(label, value, 0));
+        };
+        for round in 0..TOKEN_ROUNDS {
+            let token = self.token(label, value, round);
+            if vault.claim(&token, label, value)? {
+                return Ok(token);
+            }
+        }
+        Err(UdoError::Pipeline(format!(
+            "Token vault holds every {} token tried for a value of this shape",
+            label
+        )))
+    }
+
+    fn token(&self, label: &str, value: &str, round: u32) -> String {
+        let mut stream = KeyStream::new(&self.token_key, value, round);
+        match label {
+            "email" => match value.rsplit_once('.') {
+                Some((head, tld)) if head.contains('@') => {
+                    format!("{}.{}", shape_preserving(head, &mut stream), tld)
+                }
+                _ => shape_preserving(value, &mut stream),
+            },
+            "credit_card" => card_token(value, &mut stream),
+            _ => shape_preserving(value, &mut stream),
+        }
+    }
+}
+
+/// Pseudo-random bytes derived from the token key and the value being tokenized.
+/// Round 0 is the value's first-choice token; later rounds redraw it.
+struct KeyStream<'a> {
+    key: &'a [u8],
+    value: &'a str,
+    round: u32,
+    block: [u8; 32],
+    counter: u32,
+    pos: usize,
+}
+
+impl<'a> KeyStream<'a> {
+    fn new(key: &'a [u8], value: &'a str, round: u32) -> Self {
+        Self {
+            key,
+            value,
+            round,
+            block: [0; 32],
+            counter: 0,
+            pos: 32,
+        }
+    }
+
+    fn next(&mut self, modulus: u8) -> u8 {
+        if self.pos == self.block.len() {
+            let counter = self.counter.to_be_bytes();
+            let round = self.round.to_be_bytes();
+            self.block = if self.round == 0 {
+                hmac(self.key, &[&counter, self.value.as_bytes()])
+            } else {
+                hmac(
+                    self.key,
+                    &[b"round", &round, &counter, self.value.as_bytes()],
+                )
+            };
+            self.counter += 1;
+            self.pos = 0;
+        }
+        let byte = self.block[self.pos];
+        self.pos += 1;
+        byte % modulus
+    }
+}
+
+fn shape_preserving(value: &str, stream: &mut KeyStream) -> String {
+    value
+        .chars()
+        .map(|c| {
+            if c.is_ascii_digit() {
+                (b'0' + stream.next(10)) as char
+            } else if c.is_ascii_uppercase() {
+                (b'A' + stream.next(26)) as char
+            } else if c.is_alphabetic() {
+                (b'a' + stream.next(26)) as char
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+fn card_token(value: &str, stream: &mut KeyStream) -> String {
+    let digit_count = value.chars().filter(|c| c.is_ascii_digit()).count();
+    let keep_from = digit_count.saturating_sub(4);
+    let mut seen = 0;
+    let mut token: Vec<char> = value
+        .chars()
+        .map(|c| {
+            if !c.is_ascii_digit() {
+                return c;
+            }
+            seen += 1;
+            if seen > keep_from {
+                c
+            } else {
+                (b'0' + stream.next(10)) as char
+            }
+        })
+        .collect();
+
+    // Adjust the first digit so the token still passes a Luhn check.
+    if keep_from > 0
+        && let Some(first) = token.iter().position(|c| c.is_ascii_digit())
+    {
+        for d in '0'..='9' {
+            token[first] = d;
+            if luhn_valid(&token.iter().collect::<String>()) {
+                break;
+            }
+        }
+    }
+    token.into_iter().collect()
+}
+
+#[derive(Serialize, Deserialize)]
+struct VaultEntry {
+    token: String,
+    label: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// What the vault knows about the value behind a token: its entry as read from the
+/// file, or, once checked, a keyed fingerprint of it.
+enum Known {
+    Sealed(VaultEntry),
+    Fingerprint([u8; 32]),
+}
+
+/// Append-only NDJSON file mapping tokens to their AES-256-GCM encrypted originals.
+/// Only holders of the pipeline key can read the originals back.
+pub struct TokenVault {
+    path: PathBuf,
+    cipher: Aes256Gcm,
+    fingerprint_key: [u8; 32],
+    state: Mutex<(HashMap<String, Known>, File)>,
+}
+
+impl TokenVault {
+    pub fn open(path: impl AsRef<Path>, key: &[u8]) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let vault_key = hmac(key, &[b"udo-pii-vault"]);
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&vault_key));
+
+        let mut known = HashMap::new();
+        if path.exists() {
+            for entry in read_entries(&path)? {
+                known.insert(entry.token.clone(), Known::Sealed(entry));
+            }
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self {
+            path,
+            cipher,
+            fingerprint_key: hmac(key, &[b"udo-pii-vault-fingerprint"]),
+            state: Mutex::new((known, file)),
+        })
+    }
+
+    /// Records that `token` stands for `original`. Returns `false`, storing nothing,
+    /// when the vault already holds `token` for a different value.
+    fn claim(&self, token: &str, label: &str, original: &str) -> Result<bool> {
+        let fingerprint = hmac(&self.fingerprint_key, &[original.as_bytes()]);
+        let mut state = self
+            .state
+            .lock()
+            .map_err(|_| UdoError::Pipeline("Token vault lock poisoned".to_string()))?;
+        if let Some(known) = state.0.get_mut(token) {
+            if let Known::Sealed(entry) = known {
+                let stored = self.decrypt(entry)?;
+                *known = Known::Fingerprint(hmac(&self.fingerprint_key, &[stored.as_bytes()]));
+            }
+            return Ok(matches!(known, Known::Fingerprint(f) if *f == fingerprint));
+        }
+
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: original.as_bytes(),
+                    aad: token.as_bytes(),
+                },
+            )
+            .map_err(|e| UdoError::Pipeline(format!("Token vault encryption failed: {}", e)))?;
+        let entry = VaultEntry {
+            token: token.to_string(),
+            label: label.to_string(),
+            nonce: hex::encode(nonce),
+            ciphertext: hex::encode(ciphertext),
+        };
+        let line = serde_json::to_string(&entry)
+            .map_err(|e| UdoError::Pipeline(format!("Token vault encoding failed: {}", e)))?;
+        writeln!(state.1, "{}", line)?;
+        state
+            .0
+            .insert(token.to_string(), Known::Fingerprint(fingerprint));
+        Ok(true)
+    }
+
+    /// The original value behind `token`, if the vault has it.
+    pub fn reveal(&self, token: &str) -> Result<Option<String>> {
+        read_entries(&self.path)?
+            .into_iter()
+            .find(|e| e.token == token)
+            .map(|entry| self.decrypt(&entry))
+            .transpose()
+    }
+
+    fn decrypt(&self, entry: &VaultEntry) -> Result<String> {
+        let nonce = hex::decode(&entry.nonce)
+            .map_err(|e| UdoError::Config(format!("Corrupt token vault entry: {}", e)))?;
+        let ciphertext = hex::decode(&entry.ciphertext)
+            .map_err(|e| UdoError::Config(format!("Corrupt token vault entry: {}", e)))?;
+        if nonce.len() != 12 {
+            return Err(UdoError::Config(
+                "Corrupt token vault entry: bad nonce".to_string(),
+            ));
+        }
+        let plaintext = self
+            .cipher
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &ciphertext,
+                    aad: entry.token.as_bytes(),
+                },
+            )
+            .map_err(|_| {
+                UdoError::Config("Token vault entry cannot be decrypted with this key".to_string())
+            })?;
+        String::from_utf8(plaintext)
+            .map_err(|e| UdoError::Config(format!("Corrupt token vault entry: {}", e)))
+    }
+}
+
+fn read_entries(path: &Path) -> Result<Vec<VaultEntry>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut entries = Vec::new();
+    for (idx, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => warn!(line = %(idx + 1), error = %e, "Skipping malformed token vault entry"),
+        }
+    }
+    Ok(entries)
+}
@@ -0,0 +1,196 @@
+use crate::core::error::{Result, UdoError};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use tracing::{info, warn};
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    key: String,
+    embedding: Vec<f32>,
+}
+
+/// Entries kept by [`EmbeddingCache::open`].
+pub const DEFAULT_MAX_ENTRIES: usize = 100_000;
+
+/// On-disk embeddings of one model, keyed by the SHA-256 of the embedded text.
+/// Entries are appended as NDJSON to `<dir>/<model fingerprint>.ndjson`, so a
+/// different model never reads another model's vectors.
+///
+/// At most `max_entries` are kept, evicting the least recently used. The file is
+/// rewritten with only the live entries when it is opened with stale lines, and once
+/// appends have made it twice as long as the cache.
+pub struct EmbeddingCache {
+    path: PathBuf,
+    max_entries: usize,
+    state: Mutex<State>,
+}
+
+struct State {
+    /// Embedding and last use of each key.
+    entries: HashMap<String, (Vec<f32>, u64)>,
+    /// Keys by last use, oldest first.
+    recency: BTreeMap<u64, String>,
+    clock: u64,
+    file: File,
+    /// Lines in the file, stale ones included.
+    lines: usize,
+}
+
+impl State {
+    fn touch(&mut self, key: &str) {
+        self.clock += 1;
+        if let Some((_, used)) = self.entries.get_mut(key) {
+            self.recency.remove(used);
+            *used = self.clock;
+            self.recency.insert(self.clock, key.to_string());
+        }
+    }
+
+    fn put(&mut self, key: String, embedding: Vec<f32>, max_entries: usize) {
+        self.clock += 1;
+        if let Some((_, used)) = self.entries.insert(key.clone(), (embedding, self.clock)) {
+            self.recency.remove(&used);
+        }
+        self.recency.insert(self.clock, key);
+        while self.entries.len() > max_entries {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+    }
+}
+
+impl EmbeddingCache {
+    pub fn open(dir: impl AsRef<Path>, fingerprint: &str) -> Result<Self> {
+        Self::open_with_max_entries(dir, fingerprint, DEFAULT_MAX_ENTRIES)
+    }
+
+    pub fn open_with_max_entries(
+        dir: impl AsRef<Path>,
+        fingerprint: &str,
+        max_entries: usize,
+    ) -> Result<Self> {
+        std::fs::create_dir_all(dir.as_ref())?;
+        let path = dir.as_ref().join(format!("{}.ndjson", fingerprint));
+        let max_entries = max_entries.max(1);
+
+        let mut state = State {
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            clock: 0,
+            file: OpenOptions::new().create(true).append(true).open(&path)?,
+            lines: 0,
+        };
+        // Later lines are more recent, so replaying the file rebuilds the LRU order.
+        let reader = BufReader::new(File::open(&path)?);
+        for (idx, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            state.lines += 1;
+            match serde_json::from_str::<CacheEntry>(&line) {
+                Ok(entry) => state.put(entry.key, entry.embedding, max_entries),
+                Err(e) => {
+                    warn!(line = %(idx + 1), error = %e, "Skipping malformed embedding cache entry")
+                }
+            }
+        }
+
+        let cache = Self {
+            path,
+            max_entries,
+            state: Mutex::new(state),
+        };
+        let mut state = cache.lock()?;
+        if state.lines > state.entries.len() {
+            cache.compact(&mut state)?;
+        }
+        info!(path = ?cache.path, entries = %state.entries.len(), "Opened embedding cache");
+        drop(state);
+        Ok(cache)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.lock().map(|s| s.entries.len()).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, text: &str) -> Option<Vec<f32>> {
+        let mut state = self.state.lock().ok()?;
+        let key = key(text);
+        let embedding = state.entries.get(&key).map(|(e, _)| e.clone())?;
+        state.touch(&key);
+        Some(embedding)
+    }
+
+    pub fn insert(&self, text: &str, embedding: &[f32]) -> Result<()> {
+        let mut state = self.lock()?;
+        let key = key(text);
+        if state.entries.contains_key(&key) {
+            state.touch(&key);
+            return Ok(());
+        }
+        let entry = CacheEntry {
+            key,
+            embedding: embedding.to_vec(),
+        };
+        writeln!(state.file, "{}", encode(&entry)?)?;
+        state.lines += 1;
+        state.put(entry.key, entry.embedding, self.max_entries);
+        if state.lines > self.max_entries.saturating_mul(2) {
+            self.compact(&mut state)?;
+        }
+        Ok(())
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, State>> {
+        self.state
+            .lock()
+            .map_err(|_| UdoError::Pipeline("Embedding cache lock poisoned".to_string()))
+    }
+
+    /// Rewrites the file with the live entries, oldest first, through a temporary
+    /// file so an interrupted rewrite leaves the old file in place.
+    fn compact(&self, state: &mut State) -> Result<()> {
+        let tmp = self.path.with_extension("ndjson.tmp");
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        for key in state.recency.values() {
+            let (embedding, _) = &state.entries[key];
+            let entry = CacheEntry {
+                key: key.clone(),
+                embedding: embedding.clone(),
+            };
+            writeln!(writer, "{}", encode(&entry)?)?;
+        }
+        writer.flush()?;
+        drop(writer);
+        std::fs::rename(&tmp, &self.path)?;
+
+        state.file = OpenOptions::new().append(true).open(&self.path)?;
+        state.lines = state.entries.len();
+        Ok(())
+    }
+}
+
+fn encode(entry: &CacheEntry) -> Result<String> {
+    serde_json::to_string(entry)
+        .map_err(|e| UdoError::Pipeline(format!("Embedding cache encoding failed: {}", e)))
+}
+
+fn key(text: &str) -> String {
+    hex::encode(Sha256::digest(text.as_bytes()))
+}
@@ -0,0 +1,141 @@
+use super::IntentAnalyzer;
+use crate::core::error::Result;
+use crate::core::pipeline::DataProcessor;
+use crate::utils::path::lookup;
+use arrow::datatypes::{DataType, Field, Schema};
+use async_trait::async_trait;
+use simd_json::{prelude::*, OwnedValue};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tracing::debug;
+
+pub const DEFAULT_COLUMN_SUFFIX: &str = "_embedding";
+
+/// Writes an embedding of each configured text field as a `FixedSizeList<Float32>`
+/// column, so the output can be loaded straight into a vector store.
+///
+/// Embeddings are computed once per batch: identical texts in a batch are
+/// embedded once, and texts already in the analyzer's cache are not recomputed.
+pub struct EmbeddingProcessor {
+    analyzer: Arc<IntentAnalyzer>,
+    /// Source field (dotted path for nested values) and the column its vector goes to.
+    fields: Vec<(String, String)>,
+    written: AtomicU64,
+}
+
+impl EmbeddingProcessor {
+    pub fn new(analyzer: IntentAnalyzer, fields: Vec<String>) -> Self {
+        let mut processor = Self {
+            analyzer: Arc::new(analyzer),
+            fields: Vec::new(),
+            written: AtomicU64::new(0),
+        };
+        processor.set_columns(fields, DEFAULT_COLUMN_SUFFIX);
+        processor
+    }
+
+    /// Names each output column `<field><suffix>`, with dots in nested paths replaced by `_`.
+    pub fn with_suffix(mut self, suffix: &str) -> Self {
+        let fields = self.fields.drain(..).map(|(field, _)| field).collect();
+        self.set_columns(fields, suffix);
+        self
+    }
+
+    fn set_columns(&mut self, fields: Vec<String>, suffix: &str) {
+        self.fields = fields
+            .into_iter()
+            .map(|field| {
+                let column = format!("{}{}", field.replace('.', "_"), suffix);
+                (field, column)
+            })
+            .collect();
+    }
+
+    /// Output column names, in field order.
+    pub fn columns(&self) -> Vec<&str> {
+        self.fields.iter().map(|(_, c)| c.as_str()).collect()
+    }
+}
+
+#[async_trait]
+impl DataProcessor for EmbeddingProcessor {
+    async fn process(&self, record: OwnedValue) -> Result<Option<OwnedValue>> {
+        Ok(Some(record))
+    }
+
+    fn update_schema(&self, schema: &Arc<Schema>) -> Result<Arc<Schema>> {
+        let item = Arc::new(Field::new("item", DataType::Float32, true));
+        let mut fields: Vec<_> = schema
+            .fields()
+            .iter()
+            .filter(|f| !self.fields.iter().any(|(_, c)| c == f.name()))
+            .cloned()
+            .collect();
+        for (_, column) in &self.fields {
+            fields.push(Arc::new(Field::new(
+                column.as_str(),
+                DataType::FixedSizeList(item.clone(), self.analyzer.dimension() as i32),
+                true,
+            )));
+        }
+        Ok(Arc::new(Schema::new(fields)))
+    }
+
+    async fn process_batch(&self, mut rows: Vec<OwnedValue>) -> Result<Vec<Option<OwnedValue>>> {
+        let mut slots: HashMap<&str, usize> = HashMap::new();
+        let mut texts: Vec<&str> = Vec::new();
+        let mut targets: Vec<(usize, usize, usize)> = Vec::new();
+        for (row_idx, row) in rows.iter().enumerate() {
+            for (field_idx, (field, _)) in self.fields.iter().enumerate() {
+                if let Some(text) = lookup(row, field)
+                    .and_then(|v| v.as_str())
+                    .filter(|s| !s.trim().is_empty())
+                {
+                    let slot = *slots.entry(text).or_insert_with(|| {
+                        texts.push(text);
+                        texts.len() - 1
+                    });
+                    targets.push((row_idx, field_idx, slot));
+                }
+            }
+        }
+        if texts.is_empty() {
+            return Ok(rows.into_iter().map(Some).collect());
+        }
+
+        let texts: Vec<String> = texts.into_iter().map(str::to_string).collect();
+        let count = texts.len();
+        let computed = self.analyzer.computed();
+        let embeddings: Vec<OwnedValue> = self
+            .analyzer
+            .embed_async(texts)
+            .await?
+            .into_iter()
+            .map(|e| OwnedValue::from(e.into_iter().map(f64::from).collect::<Vec<f64>>()))
+            .collect();
+        debug!(
+            texts = %count,
+            computed = %(self.analyzer.computed() - computed),
+            "Embedded batch"
+        );
+
+        for (row_idx, field_idx, slot) in targets {
+            if let Some(obj) = rows[row_idx].as_object_mut() {
+                obj.insert(self.fields[field_idx].1.clone(), embeddings[slot].clone());
+                self.written.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        Ok(rows.into_iter().map(Some).collect())
+    }
+
+    fn metrics(&self) -> Vec<(String, u64)> {
+        vec![
+            (
+                "embeddings_written".to_string(),
+                self.written.load(Ordering::Relaxed),
+            ),
+            ("embeddings_computed".to_string(), self.analyzer.computed()),
+        ]
+    }
+}
@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+
+const K1: f32 = 1.2;
+const B: f32 = 0.75;
+
+/// Lowercased alphanumeric terms of `text`; `_`, `.` and other punctuation separate terms.
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Okapi BM25 over a fixed set of documents, e.g. one per column.
+pub struct Bm25 {
+    docs: Vec<HashMap<String, usize>>,
+    lengths: Vec<usize>,
+    avg_length: f32,
+    doc_freq: HashMap<String, usize>,
+}
+
+impl Bm25 {
+    pub fn new<S: AsRef<str>>(docs: &[S]) -> Self {
+        let mut doc_freq: HashMap<String, usize> = HashMap::new();
+        let mut lengths = Vec::with_capacity(docs.len());
+        let docs: Vec<HashMap<String, usize>> = docs
+            .iter()
+            .map(|doc| {
+                let terms = tokenize(doc.as_ref());
+                lengths.push(terms.len());
+                let mut counts: HashMap<String, usize> = HashMap::new();
+                for term in terms {
+                    *counts.entry(term).or_default() += 1;
+                }
+                for term in counts.keys() {
+                    *doc_freq.entry(term.clone()).or_default() += 1;
+                }
+                counts
+            })
+            .collect();
+        let avg_length = if lengths.is_empty() {
+            0.0
+        } else {
+            lengths.iter().sum::<usize>() as f32 / lengths.len() as f32
+        };
+        Self {
+            docs,
+            lengths,
+            avg_length,
+            doc_freq,
+        }
+    }
+
+    /// Raw BM25 score of every document for `query`, in document order.
+    pub fn scores(&self, query: &str) -> Vec<f32> {
+        let n = self.docs.len() as f32;
+        let mut terms = tokenize(query);
+        terms.sort();
+        terms.dedup();
+
+        self.docs
+            .iter()
+            .zip(&self.lengths)
+            .map(|(counts, &length)| {
+                let norm = if self.avg_length > 0.0 {
+                    1.0 - B + B * length as f32 / self.avg_length
+                } else {
+                    1.0
+                };
+                terms
+                    .iter()
+                    .filter_map(|term| {
+                        let tf = *counts.get(term)? as f32;
+                        let df = self.doc_freq[term] as f32;
+                        let idf = (1.0 + (n - df + 0.5) / (df + 0.5)).ln();
+                        Some(idf * tf * (K1 + 1.0) / (tf + K1 * norm))
+                    })
+                    .sum()
+            })
+            .collect()
+    }
+
+    /// Scores scaled so the best matching document gets 1.0; all zero when nothing matches.
+    pub fn normalized_scores(&self, query: &str) -> Vec<f32> {
+        let scores = self.scores(query);
+        let max = scores.iter().cloned().fold(0.0f32, f32::max);
+        if max <= 0.0 {
+            return vec![0.0; scores.len()];
+        }
+        scores.into_iter().map(|s| s / max).collect()
+    }
+}
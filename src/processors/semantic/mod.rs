@@ -0,0 +1,712 @@
+use crate::core::error::{Result, UdoError};
+use crate::core::inference::{InferencePool, MicroBatcher};
+use crate::core::model::{BertModelContainer, LoadReport, ModelPool, ModelSpec};
+use crate::core::pipeline::DataProcessor;
+use arrow::datatypes::{DataType, Field, Schema};
+use async_trait::async_trait;
+use candle_core::{DType, Tensor};
+use serde::Serialize;
+use simd_json::{prelude::*, OwnedValue};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokenizers::{Tokenizer, TruncationParams};
+use tracing::{debug, info, warn};
+
+pub mod cache;
+pub mod embed;
+pub mod lexical;
+pub mod rows;
+
+use cache::EmbeddingCache;
+pub use embed::EmbeddingProcessor;
+use lexical::Bm25;
+pub use rows::SemanticRowFilter;
+
+/// Sentence embedding model behind every semantic processor.
+pub const DEFAULT_EMBEDDING_MODEL: &str = "sentence-transformers/all-MiniLM-L6-v2";
+const DEFAULT_BATCH_SIZE: usize = 32;
+/// Column score threshold used when no other selection criterion is configured.
+pub const DEFAULT_THRESHOLD: f32 = 0.85;
+const DEFAULT_LEXICAL_WEIGHT: f32 = 0.3;
+const DEFAULT_SAMPLE_VALUES: usize = 3;
+/// Sampled values are cut to this many characters before they are embedded.
+const MAX_SAMPLE_CHARS: usize = 40;
+/// Longest input the embedding model's position embeddings cover.
+const MAX_TOKENS: usize = 512;
+
+fn model_err(e: impl std::fmt::Display) -> UdoError {
+    UdoError::AiModel(e.to_string())
+}
+
+pub struct IntentAnalyzer {
+    /// Weights, possibly shared with other processors through a [`ModelPool`].
+    container: Arc<BertModelContainer>,
+    /// The model's tokenizer, truncating inputs the model cannot take whole.
+    tokenizer: Tokenizer,
+    cache: Option<EmbeddingCache>,
+    batch_size: usize,
+    /// Texts run through the model, i.e. not served from the cache.
+    computed: AtomicU64,
+    /// Where [`IntentAnalyzer::embed_async`] runs the model, and how long it batches.
+    inference: Option<(Arc<InferencePool>, Duration)>,
+    batcher: OnceLock<MicroBatcher<String, Vec<f32>>>,
+}
+
+impl IntentAnalyzer {
+    pub fn new(model_path: Option<std::path::PathBuf>) -> Result<Self> {
+        Self::from_spec(&ModelSpec::new(DEFAULT_EMBEDDING_MODEL).with_path(model_path))
+    }
+
+    /// Loads the embedding model described by `spec`, e.g. a pinned and verified one.
+    pub fn from_spec(spec: &ModelSpec) -> Result<Self> {
+        Self::from_container(Arc::new(BertModelContainer::from_spec(spec)?))
+    }
+
+    /// Shares the pool's copy of the model and runs async embedding on its threads.
+    pub fn from_pool(pool: &ModelPool, spec: &ModelSpec) -> Result<Self> {
+        let container = pool.get_or_load(spec, || BertModelContainer::from_spec(spec))?;
+        Ok(Self::from_container(container)?
+            .with_inference(pool.inference().clone(), pool.batch_wait()))
+    }
+
+    fn from_container(container: Arc<BertModelContainer>) -> Result<Self> {
+        let mut tokenizer = container.tokenizer.clone();
+        if tokenizer.get_truncation().is_none() {
+            tokenizer
+                .with_truncation(Some(TruncationParams {
+                    max_length: MAX_TOKENS,
+                    ..Default::default()
+                }))
+                .map_err(model_err)?;
+        }
+        Ok(Self {
+            container,
+            tokenizer,
+            cache: None,
+            batch_size: DEFAULT_BATCH_SIZE,
+            computed: AtomicU64::new(0),
+            inference: None,
+            batcher: OnceLock::new(),
+        })
+    }
+
+    /// Runs [`IntentAnalyzer::embed_async`] on `pool`, merging requests that arrive
+    /// within `max_wait` of each other into shared forward passes.
+    pub fn with_inference(mut self, pool: Arc<InferencePool>, max_wait: Duration) -> Self {
+        self.inference = Some((pool, max_wait));
+        self
+    }
+
+    /// Reuses embeddings across runs from a cache in `dir`, keyed by the model's
+    /// weights hash and the embedded text.
+    pub fn with_cache(mut self, dir: impl AsRef<Path>) -> Result<Self> {
+        let fingerprint = self.container.fingerprint()?;
+        self.cache = Some(EmbeddingCache::open(dir, &fingerprint)?);
+        Ok(self)
+    }
+
+    /// Maximum number of texts per forward pass.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Length of the embedding vectors.
+    pub fn dimension(&self) -> usize {
+        self.container.hidden_size
+    }
+
+    /// Precision, memory and latency of loading the model.
+    pub fn load_report(&self) -> &LoadReport {
+        &self.container.report
+    }
+
+    /// Number of texts embedded by the model so far, excluding cache hits.
+    pub fn computed(&self) -> u64 {
+        self.computed.load(Ordering::Relaxed)
+    }
+
+    /// Like [`IntentAnalyzer::embed`], but off the async runtime and micro-batched
+    /// with concurrent calls when an inference pool is set; inline otherwise.
+    pub async fn embed_async(self: &Arc<Self>, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+        let Some((pool, max_wait)) = &self.inference else {
+            let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
+            return self.embed(&texts);
+        };
+        let batcher = self.batcher.get_or_init(|| {
+            // Weak, as the batcher lives inside the analyzer.
+            let analyzer = Arc::downgrade(self);
+            MicroBatcher::new(pool.clone(), self.batch_size, *max_wait, move |texts| {
+                let analyzer = analyzer
+                    .upgrade()
+                    .ok_or_else(|| UdoError::AiModel("Embedding model was dropped".to_string()))?;
+                let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
+                analyzer.embed(&texts)
+            })
+        });
+        batcher.call(texts).await
+    }
+
+    pub fn get_embedding(&self, text: &str) -> Result<Vec<f32>> {
+        Ok(self.embed(&[text])?.pop().unwrap_or_default())
+    }
+
+    /// Normalized mean-pooled embeddings of `texts`, in order. Cached texts are not
+    /// recomputed; the rest run through the model in padded batches.
+    pub fn embed(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        let mut embeddings: Vec<Option<Vec<f32>>> = texts
+            .iter()
+            .map(|t| self.cache.as_ref().and_then(|c| c.get(t)))
+            .collect();
+        let missing: Vec<usize> = (0..texts.len())
+            .filter(|&i| embeddings[i].is_none())
+            .collect();
+        if missing.len() < texts.len() {
+            debug!(cached = %(texts.len() - missing.len()), computed = %missing.len(), "Embedding cache lookup");
+        }
+
+        for chunk in missing.chunks(self.batch_size) {
+            let batch: Vec<&str> = chunk.iter().map(|&i| texts[i]).collect();
+            for (&i, embedding) in chunk.iter().zip(self.compute(&batch)?) {
+                if let Some(cache) = &self.cache {
+                    cache.insert(texts[i], &embedding)?;
+                }
+                embeddings[i] = Some(embedding);
+            }
+        }
+        Ok(embeddings.into_iter().flatten().collect())
+    }
+
+    fn compute(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        self.computed
+            .fetch_add(texts.len() as u64, Ordering::Relaxed);
+        let device = &self.container.device;
+        let encodings = self
+            .tokenizer
+            .encode_batch(texts.to_vec(), true)
+            .map_err(model_err)?;
+        let width = encodings.iter().map(|e| e.len()).max().unwrap_or(0);
+
+        let mut ids = Vec::with_capacity(encodings.len() * width);
+        let mut mask = Vec::with_capacity(encodings.len() * width);
+        for encoding in &encodings {
+            let padding = width - encoding.len();
+            ids.extend_from_slice(encoding.get_ids());
+            ids.extend(std::iter::repeat_n(0u32, padding));
+            mask.extend_from_slice(encoding.get_attention_mask());
+            mask.extend(std::iter::repeat_n(0u32, padding));
+        }
+        let shape = (encodings.len(), width);
+        let token_ids = Tensor::from_vec(ids, shape, device).map_err(model_err)?;
+        let attention_mask = Tensor::from_vec(mask, shape, device).map_err(model_err)?;
+        let token_type_ids = token_ids.zeros_like().map_err(model_err)?;
+
+        let embeddings = self
+            .container
+            .model
+            .forward(&token_ids, &token_type_ids, Some(&attention_mask))
+            .map_err(model_err)?;
+
+        // Mean over real tokens only, then L2-normalize each row.
+        let mask = attention_mask
+            .to_dtype(DType::F32)
+            .and_then(|m| m.unsqueeze(2))
+            .map_err(model_err)?;
+        let summed = embeddings
+            .broadcast_mul(&mask)
+            .and_then(|e| e.sum(1))
+            .map_err(model_err)?;
+        let counts = mask.sum(1).map_err(model_err)?;
+        let pooled = summed.broadcast_div(&counts).map_err(model_err)?;
+        let norms = pooled
+            .sqr()
+            .and_then(|p| p.sum_keepdim(1))
+            .and_then(|p| p.sqrt())
+            .map_err(model_err)?;
+        pooled
+            .broadcast_div(&norms)
+            .and_then(|p| p.to_vec2::<f32>())
+            .map_err(model_err)
+    }
+
+    pub fn rank_columns(&self, query: &str, columns: &[String]) -> Result<Vec<(String, f32)>> {
+        let query_emb = self.get_embedding(query)?;
+        self.rank_columns_for(&query_emb, columns)
+    }
+
+    /// Ranks `columns` against an already computed query embedding.
+    pub fn rank_columns_for(
+        &self,
+        query_embedding: &[f32],
+        columns: &[String],
+    ) -> Result<Vec<(String, f32)>> {
+        let texts: Vec<String> = columns.iter().map(|c| c.replace("_", " ")).collect();
+        let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
+        let embeddings = self.embed(&texts)?;
+
+        let mut results: Vec<(String, f32)> = columns
+            .iter()
+            .zip(embeddings)
+            .map(|(col, emb)| (col.clone(), cosine_similarity(query_embedding, &emb)))
+            .collect();
+        results.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+        Ok(results)
+    }
+}
+
+pub(crate) fn cosine_similarity(v1: &[f32], v2: &[f32]) -> f32 {
+    v1.iter().zip(v2.iter()).map(|(a, b)| a * b).sum()
+}
+
+/// Why the pruner kept or dropped a column, with the score of each signal.
+#[derive(Debug, Clone, Serialize)]
+pub struct ColumnDecision {
+    pub column: String,
+    pub keep: bool,
+    /// Weighted combination of `embedding` and `lexical` the selection is based on.
+    pub score: f32,
+    /// Cosine similarity between the query and the column text embeddings.
+    pub embedding: f32,
+    /// BM25 score of the column text for the query, relative to the best column.
+    pub lexical: f32,
+    pub reason: String,
+}
+
+pub struct SemanticProcessor {
+    analyzer: Arc<IntentAnalyzer>,
+    query: String,
+    /// Minimum score a column needs; `None` applies no absolute floor.
+    threshold: Option<f32>,
+    /// Minimum score as a fraction of the best column's score.
+    relative_threshold: Option<f32>,
+    /// Keep at most this many of the best scoring columns.
+    top_k: Option<usize>,
+    /// Columns kept whatever their score, e.g. join keys and IDs.
+    always_keep: HashSet<String>,
+    /// Columns dropped whatever their score.
+    never_keep: HashSet<String>,
+    /// Fail the schema update instead of keeping every column when none is selected.
+    fail_if_empty: bool,
+    /// Share of the score taken by lexical (BM25) matching; the rest is embedding similarity.
+    lexical_weight: f32,
+    /// Data dictionary entries, keyed by column name or dotted nested path.
+    descriptions: HashMap<String, String>,
+    sample_values: usize,
+    /// Distinct example values per column, taken from the warm-up records.
+    samples: Mutex<HashMap<String, Vec<String>>>,
+    /// Embedded on first use and kept for every later schema update.
+    query_embedding: OnceLock<Vec<f32>>,
+    keep_columns: Arc<Mutex<Option<HashSet<String>>>>,
+    decisions: Mutex<Vec<ColumnDecision>>,
+}
+
+impl SemanticProcessor {
+    pub fn new(analyzer: IntentAnalyzer, query: String, threshold: impl Into<Option<f32>>) -> Self {
+        Self {
+            analyzer: Arc::new(analyzer),
+            query,
+            threshold: threshold.into(),
+            relative_threshold: None,
+            top_k: None,
+            always_keep: HashSet::new(),
+            never_keep: HashSet::new(),
+            fail_if_empty: false,
+            lexical_weight: DEFAULT_LEXICAL_WEIGHT,
+            descriptions: HashMap::new(),
+            sample_values: DEFAULT_SAMPLE_VALUES,
+            samples: Mutex::new(HashMap::new()),
+            query_embedding: OnceLock::new(),
+            keep_columns: Arc::new(Mutex::new(None)),
+            decisions: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Column descriptions embedded alongside the column name, keyed by column name
+    /// or by a dotted path into a nested column (e.g. `user.browser.ua`).
+    pub fn with_descriptions(mut self, descriptions: HashMap<String, String>) -> Self {
+        self.descriptions.extend(descriptions);
+        self
+    }
+
+    /// Number of distinct warm-up values added to each column's text; 0 disables sampling.
+    pub fn with_sample_values(mut self, sample_values: usize) -> Self {
+        self.sample_values = sample_values;
+        self
+    }
+
+    /// Weight of the BM25 score in `[0, 1]`; 0 ranks by embeddings alone.
+    pub fn with_lexical_weight(mut self, weight: f32) -> Self {
+        self.lexical_weight = weight.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Also requires a score of at least `fraction` of the best column's score. When
+    /// the best score is not positive, only the best columns pass.
+    pub fn with_relative_threshold(mut self, fraction: f32) -> Self {
+        self.relative_threshold = Some(fraction);
+        self
+    }
+
+    /// Keeps at most `k` of the columns that pass the thresholds, best first.
+    /// Columns in `always_keep` do not count towards `k`.
+    pub fn with_top_k(mut self, k: usize) -> Self {
+        self.top_k = Some(k);
+        self
+    }
+
+    pub fn with_always_keep(mut self, columns: impl IntoIterator<Item = String>) -> Self {
+        self.always_keep.extend(columns);
+        self
+    }
+
+    pub fn with_never_keep(mut self, columns: impl IntoIterator<Item = String>) -> Self {
+        self.never_keep.extend(columns);
+        self
+    }
+
+    /// Makes `update_schema` fail when no column is selected, rather than keeping all.
+    pub fn with_fail_if_empty(mut self, fail_if_empty: bool) -> Self {
+        self.fail_if_empty = fail_if_empty;
+        self
+    }
+
+    /// Keep/drop explanations from the latest schema update, best score first.
+    pub fn decisions(&self) -> Vec<ColumnDecision> {
+        self.decisions.lock().map(|d| d.clone()).unwrap_or_default()
+    }
+
+    fn query_embedding(&self) -> Result<&[f32]> {
+        if let Some(embedding) = self.query_embedding.get() {
+            return Ok(embedding);
+        }
+        let embedding = self.analyzer.get_embedding(&self.query)?;
+        Ok(self.query_embedding.get_or_init(|| embedding))
+    }
+
+    /// Marks the columns to keep in `decisions`, sorted best score first, and explains
+    /// each choice.
+    fn select(&self, decisions: &mut [ColumnDecision]) {
+        let best = decisions
+            .iter()
+            .filter(|d| !self.never_keep.contains(&d.column))
+            .map(|d| d.score)
+            .fold(f32::NEG_INFINITY, f32::max);
+        let mut rank = 0;
+
+        for d in decisions.iter_mut() {
+            if self.never_keep.contains(&d.column) {
+                d.keep = false;
+                d.reason = format!("listed in never_keep (score {:.3})", d.score);
+                continue;
+            }
+            if self.always_keep.contains(&d.column) {
+                d.keep = true;
+                d.reason = format!("listed in always_keep (score {:.3})", d.score);
+                continue;
+            }
+
+            let mut passed = Vec::new();
+            let mut failed = None;
+            if let Some(threshold) = self.threshold {
+                if d.score >= threshold {
+                    passed.push(format!(
+                        "score {:.3} >= threshold {:.3}",
+                        d.score, threshold
+                    ));
+                } else {
+                    failed = Some(format!("score {:.3} < threshold {:.3}", d.score, threshold));
+                }
+            }
+            if failed.is_none()
+                && let Some(fraction) = self.relative_threshold
+            {
+                let floor = if best > 0.0 { best * fraction } else { best };
+                if d.score >= floor {
+                    passed.push(format!(
+                        "score {:.3} >= {} of best {:.3}",
+                        d.score, fraction, best
+                    ));
+                } else {
+                    failed = Some(format!(
+                        "score {:.3} < {} of best {:.3}",
+                        d.score, fraction, best
+                    ));
+                }
+            }
+            if failed.is_none()
+                && let Some(k) = self.top_k
+            {
+                rank += 1;
+                if rank <= k {
+                    passed.push(format!("rank {} within top_k {}", rank, k));
+                } else {
+                    failed = Some(format!("rank {} outside top_k {}", rank, k));
+                }
+            }
+
+            d.keep = failed.is_none();
+            d.reason = match failed {
+                Some(reason) => reason,
+                None if passed.is_empty() => format!("score {:.3}, no selection criteria", d.score),
+                None => passed.join("; "),
+            };
+        }
+    }
+
+    /// Text that represents a column for ranking: its name, nested field paths,
+    /// dictionary descriptions and sampled values.
+    fn column_text(&self, field: &Field, samples: &HashMap<String, Vec<String>>) -> String {
+        let name = field.name();
+        let mut paths = Vec::new();
+        nested_paths(name, field.data_type(), &mut paths);
+
+        let mut parts = vec![humanize(name)];
+        if !paths.is_empty() {
+            let nested: Vec<String> = paths.iter().map(|p| humanize(p)).collect();
+            parts.push(format!("fields: {}", nested.join(", ")));
+        }
+        for key in std::iter::once(name).chain(paths.iter()) {
+            if let Some(description) = self.descriptions.get(key) {
+                parts.push(description.clone());
+            }
+        }
+        if let Some(values) = samples.get(name)
+            && !values.is_empty()
+        {
+            parts.push(format!("examples: {}", values.join(", ")));
+        }
+        parts.join(". ")
+    }
+}
+
+/// Reads a data dictionary: a YAML or JSON map from column name (or dotted nested
+/// path) to its description.
+pub fn load_dictionary(path: impl AsRef<Path>) -> Result<HashMap<String, String>> {
+    let content = std::fs::read_to_string(path.as_ref())?;
+    serde_yaml::from_str(&content).map_err(|e| {
+        UdoError::Config(format!(
+            "Invalid data dictionary {}: {}",
+            path.as_ref().display(),
+            e
+        ))
+    })
+}
+
+fn humanize(name: &str) -> String {
+    name.replace(['_', '.'], " ")
+}
+
+/// Dotted paths of every field nested under `prefix`, looking through lists.
+fn nested_paths(prefix: &str, data_type: &DataType, paths: &mut Vec<String>) {
+    match data_type {
+        DataType::Struct(fields) => {
+            for field in fields {
+                let path = format!("{}.{}", prefix, field.name());
+                paths.push(path.clone());
+                nested_paths(&path, field.data_type(), paths);
+            }
+        }
+        DataType::List(field) | DataType::LargeList(field) => {
+            nested_paths(prefix, field.data_type(), paths)
+        }
+        _ => {}
+    }
+}
+
+/// Adds distinct, non-null scalar values found in `value` until `limit` is reached.
+fn collect_samples(value: &OwnedValue, limit: usize, samples: &mut Vec<String>) {
+    if samples.len() >= limit || value.is_null() {
+        return;
+    }
+    match value {
+        OwnedValue::Object(obj) => obj
+            .values()
+            .for_each(|v| collect_samples(v, limit, samples)),
+        OwnedValue::Array(items) => items
+            .iter()
+            .for_each(|v| collect_samples(v, limit, samples)),
+        _ => {
+            let text = match value.as_str() {
+                Some(s) => s.to_string(),
+                None => value.to_string(),
+            };
+            let text: String = text.chars().take(MAX_SAMPLE_CHARS).collect();
+            if !text.trim().is_empty() && !samples.contains(&text) {
+                samples.push(text);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl DataProcessor for SemanticProcessor {
+    async fn process(&self, mut record: OwnedValue) -> Result<Option<OwnedValue>> {
+        let keep_cols_guard = self
+            .keep_columns
+            .lock()
+            .map_err(|_| UdoError::Pipeline("SemanticProcessor mutex poisoned".to_string()))?;
+        if let Some(keep_cols) = keep_cols_guard.as_ref()
+            && let Some(obj) = record.as_object_mut()
+        {
+            let keys_to_remove: Vec<String> = obj
+                .iter()
+                .filter(|(k, _)| !keep_cols.contains(&k.to_string()))
+                .map(|(k, _)| k.to_string())
+                .collect();
+
+            for key in keys_to_remove {
+                obj.remove(&key);
+            }
+        }
+        Ok(Some(record))
+    }
+
+    fn observe_samples(&self, records: &[OwnedValue]) {
+        if self.sample_values == 0 {
+            return;
+        }
+        let Ok(mut samples) = self.samples.lock() else {
+            return;
+        };
+        for record in records {
+            let Some(obj) = record.as_object() else {
+                continue;
+            };
+            for (key, value) in obj.iter() {
+                let values = samples.entry(key.to_string()).or_default();
+                collect_samples(value, self.sample_values, values);
+            }
+        }
+    }
+
+    fn update_schema(&self, schema: &Arc<Schema>) -> Result<Arc<Schema>> {
+        debug!(query = %self.query, "Analyzing column relevance for query");
+        let texts: Vec<String> = {
+            let samples = self
+                .samples
+                .lock()
+                .map_err(|_| UdoError::Pipeline("SemanticProcessor mutex poisoned".to_string()))?;
+            schema
+                .fields()
+                .iter()
+                .map(|f| self.column_text(f, &samples))
+                .collect()
+        };
+        let text_refs: Vec<&str> = texts.iter().map(String::as_str).collect();
+        let embeddings = self.analyzer.embed(&text_refs)?;
+        let query_embedding = self.query_embedding()?;
+        let lexical = Bm25::new(&texts).normalized_scores(&self.query);
+
+        let mut decisions: Vec<ColumnDecision> = schema
+            .fields()
+            .iter()
+            .zip(embeddings)
+            .zip(lexical)
+            .map(|((field, embedding), lexical)| {
+                let embedding = cosine_similarity(query_embedding, &embedding);
+                let score = (1.0 - self.lexical_weight) * embedding + self.lexical_weight * lexical;
+                ColumnDecision {
+                    column: field.name().to_string(),
+                    keep: false,
+                    score,
+                    embedding,
+                    lexical,
+                    reason: String::new(),
+                }
+            })
+            .collect();
+        decisions.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        for column in self.always_keep.iter().chain(&self.never_keep) {
+            if schema.field_with_name(column).is_err() {
+                warn!(column = %column, "Listed column is not in the schema");
+            }
+        }
+        self.select(&mut decisions);
+
+        let fallback = !decisions.iter().any(|d| d.keep);
+        if fallback && self.fail_if_empty {
+            let best = decisions
+                .first()
+                .map(|d| format!(" (best: {} with {})", d.column, d.reason))
+                .unwrap_or_default();
+            *self.decisions.lock().map_err(|_| {
+                UdoError::Pipeline("SemanticProcessor mutex poisoned".to_string())
+            })? = decisions;
+            return Err(UdoError::Pipeline(format!(
+                "Semantic pruner selected no columns for query '{}'{}",
+                self.query, best
+            )));
+        }
+        if fallback {
+            warn!("No columns selected. Keeping all columns as fallback.");
+            for decision in &mut decisions {
+                decision.keep = true;
+                decision.reason = format!("{}; kept as fallback", decision.reason);
+            }
+        }
+        for d in &decisions {
+            debug!(
+                column = %d.column,
+                score = %d.score,
+                embedding = %d.embedding,
+                lexical = %d.lexical,
+                reason = %d.reason,
+                "{}",
+                if d.keep { "[KEEP]" } else { "[DROP]" }
+            );
+        }
+
+        let keep: HashSet<String> = decisions
+            .iter()
+            .filter(|d| d.keep)
+            .map(|d| d.column.clone())
+            .collect();
+        let relevant_fields: Vec<_> = decisions
+            .iter()
+            .filter(|d| d.keep)
+            .filter_map(|d| schema.fields().iter().find(|f| f.name() == &d.column))
+            .cloned()
+            .collect();
+        *self
+            .decisions
+            .lock()
+            .map_err(|_| UdoError::Pipeline("SemanticProcessor mutex poisoned".to_string()))? =
+            decisions;
+
+        if fallback {
+            return Ok(schema.clone());
+        }
+
+        let mut keep_cols_guard = self
+            .keep_columns
+            .lock()
+            .map_err(|_| UdoError::Pipeline("SemanticProcessor mutex poisoned".to_string()))?;
+        *keep_cols_guard = Some(keep);
+
+        info!(original = %schema.fields().len(), pruned = %relevant_fields.len(), "Schema pruned semantically");
+        Ok(Arc::new(Schema::new(relevant_fields)))
+    }
+
+    fn report(&self) -> Option<serde_json::Value> {
+        let decisions = self.decisions();
+        if decisions.is_empty() {
+            return None;
+        }
+        Some(serde_json::json!({
+            "query": self.query,
+            "threshold": self.threshold,
+            "relative_threshold": self.relative_threshold,
+            "top_k": self.top_k,
+            "lexical_weight": self.lexical_weight,
+            "columns": decisions,
+        }))
+    }
+}
@@ -0,0 +1,169 @@
+use super::{cosine_similarity, IntentAnalyzer};
+use crate::core::error::Result;
+use crate::core::pipeline::DataProcessor;
+use crate::utils::path::fields_text;
+use arrow::datatypes::{DataType, Field, Schema};
+use async_trait::async_trait;
+use simd_json::{prelude::*, OwnedValue};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::OnceCell;
+
+pub const DEFAULT_SCORE_FIELD: &str = "_relevance";
+
+/// Scores each record's text against a query and drops irrelevant rows.
+///
+/// The text is the configured fields (dotted paths into nested objects), joined by
+/// newlines. Every row gets its cosine similarity in `score_field`, or null when it
+/// has none of the fields. `threshold` drops rows as they pass; `top_n` keeps the N
+/// most relevant rows of every batch of `batch_size` records read, the warm-up records
+/// included; the last batch of a run may be smaller.
+pub struct SemanticRowFilter {
+    analyzer: Arc<IntentAnalyzer>,
+    query: String,
+    fields: Vec<String>,
+    threshold: Option<f32>,
+    top_n: Option<usize>,
+    score_field: String,
+    query_embedding: OnceCell<Vec<f32>>,
+    scored: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl SemanticRowFilter {
+    pub fn new(analyzer: IntentAnalyzer, query: String, fields: Vec<String>) -> Self {
+        Self {
+            analyzer: Arc::new(analyzer),
+            query,
+            fields,
+            threshold: None,
+            top_n: None,
+            score_field: DEFAULT_SCORE_FIELD.to_string(),
+            query_embedding: OnceCell::new(),
+            scored: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Drops rows scoring below `threshold`, and rows without text.
+    pub fn with_threshold(mut self, threshold: f32) -> Self {
+        self.threshold = Some(threshold);
+        self
+    }
+
+    /// Keeps the `n` most relevant rows of each batch of `batch_size` records, in their
+    /// original order.
+    pub fn with_top_n(mut self, n: usize) -> Self {
+        self.top_n = Some(n);
+        self
+    }
+
+    pub fn with_score_field(mut self, field: impl Into<String>) -> Self {
+        self.score_field = field.into();
+        self
+    }
+
+    async fn query_embedding(&self) -> Result<&[f32]> {
+        let embedding = self
+            .query_embedding
+            .get_or_try_init(|| async {
+                let embeddings = self.analyzer.embed_async(vec![self.query.clone()]).await;
+                embeddings.map(|mut e| e.pop().unwrap_or_default())
+            })
+            .await?;
+        Ok(embedding)
+    }
+
+    fn relevance(&self, row: &OwnedValue) -> f64 {
+        row.get(self.score_field.as_str())
+            .and_then(|v| v.as_f64())
+            .unwrap_or(f64::NEG_INFINITY)
+    }
+}
+
+#[async_trait]
+impl DataProcessor for SemanticRowFilter {
+    async fn process(&self, mut record: OwnedValue) -> Result<Option<OwnedValue>> {
+        if !record.is_object() {
+            return Ok(Some(record));
+        }
+        let text = fields_text(&record, &self.fields);
+        let relevance = if text.trim().is_empty() {
+            None
+        } else {
+            let embedding = self
+                .analyzer
+                .embed_async(vec![text])
+                .await?
+                .pop()
+                .unwrap_or_default();
+            Some(cosine_similarity(self.query_embedding().await?, &embedding))
+        };
+        self.scored.fetch_add(1, Ordering::Relaxed);
+
+        if let Some(threshold) = self.threshold
+            && !relevance.is_some_and(|r| r >= threshold)
+        {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return Ok(None);
+        }
+        if let Some(obj) = record.as_object_mut() {
+            let value = match relevance {
+                Some(r) => OwnedValue::from(r as f64),
+                None => OwnedValue::null(),
+            };
+            obj.insert(self.score_field.clone(), value);
+        }
+        Ok(Some(record))
+    }
+
+    fn update_schema(&self, schema: &Arc<Schema>) -> Result<Arc<Schema>> {
+        if schema.field_with_name(&self.score_field).is_ok() {
+            return Ok(schema.clone());
+        }
+        let mut fields: Vec<_> = schema.fields().iter().cloned().collect();
+        fields.push(Arc::new(Field::new(
+            self.score_field.as_str(),
+            DataType::Float64,
+            true,
+        )));
+        Ok(Arc::new(Schema::new(fields)))
+    }
+
+    async fn process_batch(&self, rows: Vec<OwnedValue>) -> Result<Vec<Option<OwnedValue>>> {
+        let n = match self.top_n {
+            Some(n) if rows.len() > n => n,
+            _ => return Ok(rows.into_iter().map(Some).collect()),
+        };
+        let mut ranked: Vec<(usize, f64)> = rows
+            .iter()
+            .enumerate()
+            .map(|(idx, row)| (idx, self.relevance(row)))
+            .collect();
+        // Stable, so earlier rows win ties.
+        ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        let keep: HashSet<usize> = ranked.into_iter().take(n).map(|(idx, _)| idx).collect();
+
+        self.dropped
+            .fetch_add((rows.len() - n) as u64, Ordering::Relaxed);
+        Ok(rows
+            .into_iter()
+            .enumerate()
+            .map(|(idx, row)| keep.contains(&idx).then_some(row))
+            .collect())
+    }
+
+    fn metrics(&self) -> Vec<(String, u64)> {
+        vec![
+            (
+                "rows_scored".to_string(),
+                self.scored.load(Ordering::Relaxed),
+            ),
+            (
+                "rows_dropped".to_string(),
+                self.dropped.load(Ordering::Relaxed),
+            ),
+        ]
+    }
+}
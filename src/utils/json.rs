@@ -1,5 +1,8 @@
 use crate::core::error::{Result, UdoError};
-use arrow::array::{ArrayRef, BooleanBuilder, Float64Builder, Int64Builder, StringBuilder};
+use arrow::array::{
+    ArrayRef, BooleanBuilder, FixedSizeListBuilder, Float32Builder, Float64Builder, Int64Builder,
+    StringBuilder,
+};
 use arrow::datatypes::{DataType, Schema};
 use arrow::record_batch::RecordBatch;
 use simd_json::prelude::*;
@@ -11,6 +14,46 @@ pub fn parse_json(json_data: &[u8]) -> Result<OwnedValue> {
     simd_json::to_owned_value(&mut data).map_err(UdoError::JsonParse)
 }
 
+/// Checks that every non-null value of `row` can be stored in its schema column.
+/// `json_rows_to_batch` writes a null for values it cannot convert.
+pub fn check_row(row: &OwnedValue, schema: &Schema) -> Result<()> {
+    let Some(obj) = row.as_object() else {
+        return Err(UdoError::SchemaMismatch(format!(
+            "expected an object, got {:?}",
+            row.value_type()
+        )));
+    };
+
+    for field in schema.fields() {
+        let Some(val) = obj.get(field.name().as_str()) else {
+            continue;
+        };
+        if val.is_null() {
+            continue;
+        }
+        let compatible = match field.data_type() {
+            DataType::Int64 => val.as_i64().is_some() || val.as_u64().is_some(),
+            DataType::Float64 => val.as_f64().is_some() || val.as_i64().is_some(),
+            DataType::Boolean => val.as_bool().is_some(),
+            // Nested values fall back to Utf8 during inference and are stored as nulls.
+            DataType::Utf8 => {
+                val.as_str().is_some() || val.as_object().is_some() || val.as_array().is_some()
+            }
+            DataType::FixedSizeList(_, size) => vector_values(val, *size as usize).is_some(),
+            _ => true,
+        };
+        if !compatible {
+            return Err(UdoError::SchemaMismatch(format!(
+                "field '{}' expects {:?}, got {:?}",
+                field.name(),
+                field.data_type(),
+                val.value_type()
+            )));
+        }
+    }
+    Ok(())
+}
+
 pub fn json_rows_to_batch(rows: &[OwnedValue], schema: Arc<Schema>) -> Result<RecordBatch> {
     let row_count = rows.len();
 
@@ -24,6 +67,16 @@ pub fn json_rows_to_batch(rows: &[OwnedValue], schema: Arc<Schema>) -> Result<Re
                 DataType::Float64 => Box::new(Float64Builder::with_capacity(row_count)),
                 DataType::Utf8 => Box::new(StringBuilder::with_capacity(row_count, row_count * 10)),
                 DataType::Boolean => Box::new(BooleanBuilder::with_capacity(row_count)),
+                DataType::FixedSizeList(item, size) if item.data_type() == &DataType::Float32 => {
+                    Box::new(
+                        FixedSizeListBuilder::with_capacity(
+                            Float32Builder::with_capacity(row_count * *size as usize),
+                            *size,
+                            row_count,
+                        )
+                        .with_field(item.clone()),
+                    )
+                }
                 dt => panic!("Unsupported Arrow type in schema: {:?}", dt), // In a real scenario, return Error
             }
         })
@@ -84,6 +137,13 @@ pub fn json_rows_to_batch(rows: &[OwnedValue], schema: Arc<Schema>) -> Result<Re
                             b.append_null();
                         }
                     }
+                    DataType::FixedSizeList(_, size) => {
+                        let b = builder
+                            .as_any_mut()
+                            .downcast_mut::<FixedSizeListBuilder<Float32Builder>>()
+                            .expect("Internal state mismatch");
+                        append_vector(b, val, *size as usize);
+                    }
                     _ => {}
                 }
             }
@@ -120,6 +180,42 @@ fn append_null(builder: &mut Box<dyn arrow::array::ArrayBuilder>, data_type: &Da
             .downcast_mut::<BooleanBuilder>()
             .expect("Internal state mismatch")
             .append_null(),
+        DataType::FixedSizeList(_, size) => append_vector(
+            builder
+                .as_any_mut()
+                .downcast_mut::<FixedSizeListBuilder<Float32Builder>>()
+                .expect("Internal state mismatch"),
+            None,
+            *size as usize,
+        ),
         _ => {}
     }
 }
+
+/// The numbers of a JSON array of exactly `size` numbers, e.g. an embedding.
+fn vector_values(val: &OwnedValue, size: usize) -> Option<Vec<f32>> {
+    let items = val.as_array().filter(|items| items.len() == size)?;
+    items
+        .iter()
+        .map(|v| v.as_f64().or_else(|| v.as_i64().map(|i| i as f64)))
+        .map(|v| v.map(|v| v as f32))
+        .collect()
+}
+
+/// Appends a vector, or a null list when `val` is missing or not a vector of `size`.
+fn append_vector(
+    builder: &mut FixedSizeListBuilder<Float32Builder>,
+    val: Option<&OwnedValue>,
+    size: usize,
+) {
+    match val.and_then(|v| vector_values(v, size)) {
+        Some(values) => {
+            builder.values().append_slice(&values);
+            builder.append(true);
+        }
+        None => {
+            builder.values().append_nulls(size);
+            builder.append(false);
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use crate::core::error::{Result, UdoError};
+use simd_json::{prelude::*, OwnedValue};
+
+/// One step of a concrete location inside a record.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// The value at a dotted path such as `body.text`, if every key along it exists.
+pub fn lookup<'a>(record: &'a OwnedValue, path: &str) -> Option<&'a OwnedValue> {
+    path.split('.')
+        .try_fold(record, |value, key| value.get(key))
+}
+
+/// The values at `fields` (dotted paths), one per line: strings as they are, other
+/// values as JSON. Missing and null fields are skipped.
+pub fn fields_text(record: &OwnedValue, fields: &[String]) -> String {
+    let mut parts = Vec::new();
+    for field in fields {
+        match lookup(record, field) {
+            Some(v) if v.is_null() => {}
+            Some(v) => match v.as_str() {
+                Some(s) => parts.push(s.to_string()),
+                // `Display` prints nested values in debug form, so serialize them.
+                None => parts.push(simd_json::to_string(v).unwrap_or_default()),
+            },
+            None => {}
+        }
+    }
+    parts.join("\n")
+}
+
+/// Renders a location as `$.user.emails[0]`.
+pub fn format_path(path: &[PathSegment]) -> String {
+    let mut out = String::from("$");
+    for segment in path {
+        match segment {
+            PathSegment::Key(key) => {
+                out.push('.');
+                out.push_str(key);
+            }
+            PathSegment::Index(idx) => out.push_str(&format!("[{}]", idx)),
+        }
+    }
+    out
+}
+
+/// Renders a location with array indices folded, e.g. `$.tickets[*].body`, so that
+/// findings from every element of an array aggregate under one path.
+pub fn format_pattern(path: &[PathSegment]) -> String {
+    let mut out = String::from("$");
+    for segment in path {
+        match segment {
+            PathSegment::Key(key) => {
+                out.push('.');
+                out.push_str(key);
+            }
+            PathSegment::Index(_) => out.push_str("[*]"),
+        }
+    }
+    out
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    Key(String),
+    /// `*`: any key or array element.
+    Any,
+    Index(usize),
+    /// `[*]`: any array element.
+    AnyIndex,
+    /// `..`: zero or more levels.
+    Descend,
+}
+
+/// A JSONPath-style field selector such as `$.user.email`, `$.items[*].note`,
+/// `$.user.*`, `$..ssn` or `$["odd key"]`. A selector matches a location exactly;
+/// callers decide whether a match also covers the location's children.
+#[derive(Debug, Clone)]
+pub struct FieldSelector {
+    source: String,
+    steps: Vec<Step>,
+}
+
+impl FieldSelector {
+    pub fn parse(selector: &str) -> Result<Self> {
+        let invalid = |reason: &str| {
+            UdoError::Config(format!("Invalid field path '{}': {}", selector, reason))
+        };
+
+        let trimmed = selector.trim();
+        let mut rest = match trimmed.strip_prefix('$') {
+            Some(rest) => rest,
+            None if trimmed.starts_with(['.', '[']) => trimmed,
+            // Bare paths like `user.email` are treated as `$.user.email`.
+            None => return Self::parse(&format!("$.{}", trimmed)),
+        };
+
+        let mut steps = Vec::new();
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix("..") {
+                steps.push(Step::Descend);
+                if after.starts_with('[') {
+                    rest = after;
+                    continue;
+                }
+                rest = parse_name(after, &mut steps).ok_or_else(|| invalid("empty name"))?;
+            } else if let Some(after) = rest.strip_prefix('.') {
+                rest = parse_name(after, &mut steps).ok_or_else(|| invalid("empty name"))?;
+            } else if let Some(after) = rest.strip_prefix('[') {
+                let end = after.find(']').ok_or_else(|| invalid("unclosed '['"))?;
+                let inner = after[..end].trim();
+                let step = if inner == "*" {
+                    Step::AnyIndex
+                } else if let Ok(idx) = inner.parse::<usize>() {
+                    Step::Index(idx)
+                } else if inner.len() >= 2
+                    && ((inner.starts_with('"') && inner.ends_with('"'))
+                        || (inner.starts_with('\'') && inner.ends_with('\'')))
+                {
+                    Step::Key(inner[1..inner.len() - 1].to_string())
+                } else {
+                    return Err(invalid(
+                        "expected '*', an index or a quoted key in brackets",
+                    ));
+                };
+                steps.push(step);
+                rest = &after[end + 1..];
+            } else {
+                return Err(invalid("expected '.' or '['"));
+            }
+        }
+
+        if steps.last() == Some(&Step::Descend) {
+            return Err(invalid("'..' must be followed by a field"));
+        }
+        Ok(Self {
+            source: selector.to_string(),
+            steps,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    pub fn matches(&self, path: &[PathSegment]) -> bool {
+        match_steps(&self.steps, path)
+    }
+}
+
+/// Parses a dotted name (or `*`) and returns the remaining input.
+fn parse_name<'a>(input: &'a str, steps: &mut Vec<Step>) -> Option<&'a str> {
+    let end = input.find(['.', '[']).unwrap_or(input.len());
+    let name = &input[..end];
+    if name.is_empty() {
+        return None;
+    }
+    steps.push(if name == "*" {
+        Step::Any
+    } else {
+        Step::Key(name.to_string())
+    });
+    Some(&input[end..])
+}
+
+fn match_steps(steps: &[Step], path: &[PathSegment]) -> bool {
+    match steps.split_first() {
+        None => path.is_empty(),
+        Some((Step::Descend, rest)) => (0..=path.len()).any(|i| match_steps(rest, &path[i..])),
+        Some((step, rest)) => match path.split_first() {
+            None => false,
+            Some((segment, tail)) => step_matches(step, segment) && match_steps(rest, tail),
+        },
+    }
+}
+
+fn step_matches(step: &Step, segment: &PathSegment) -> bool {
+    match (step, segment) {
+        (Step::Any, _) => true,
+        (Step::Key(name), PathSegment::Key(key)) => name == key,
+        (Step::Index(idx), PathSegment::Index(i)) => idx == i,
+        (Step::AnyIndex, PathSegment::Index(_)) => true,
+        _ => false,
+    }
+}
@@ -0,0 +1,136 @@
+use arrow::datatypes::{DataType, Field, Schema};
+use simd_json::prelude::*;
+use simd_json::OwnedValue;
+use std::sync::Arc;
+use udo::core::config::ClassLabel;
+use udo::core::pipeline::DataProcessor;
+use udo::processors::classify::RecordClassifier;
+
+fn json(text: &str) -> OwnedValue {
+    simd_json::to_owned_value(&mut text.as_bytes().to_vec()).unwrap()
+}
+
+fn label(name: &str, keywords: &[&str]) -> ClassLabel {
+    ClassLabel {
+        name: name.to_string(),
+        description: None,
+        keywords: keywords.iter().map(|k| k.to_string()).collect(),
+    }
+}
+
+fn support_labels() -> Vec<ClassLabel> {
+    vec![
+        label("billing_complaint", &["refund", "charged twice", "invoice"]),
+        label("bug_report", &["crash", "error", "stack trace", "invoice"]),
+    ]
+}
+
+#[tokio::test]
+async fn test_keyword_rules_label_records() {
+    let classifier = RecordClassifier::from_keywords(
+        support_labels(),
+        vec!["subject".to_string(), "body.text".to_string()],
+    )
+    .unwrap();
+
+    let record =
+        json(r#"{"subject": "I was CHARGED twice", "body": {"text": "Please refund me."}}"#);
+    let out = classifier.process(record).await.unwrap().unwrap();
+    assert_eq!(out["_label"].as_str(), Some("billing_complaint"));
+    assert_eq!(out["_label_score"].as_f64(), Some(1.0));
+
+    // "invoice" counts for both labels; the bug report has more matches.
+    let record = json(r#"{"subject": "Error", "body": {"text": "app crash on invoice page"}}"#);
+    let out = classifier.process(record).await.unwrap().unwrap();
+    assert_eq!(out["_label"].as_str(), Some("bug_report"));
+    assert_eq!(out["_label_score"].as_f64(), Some(0.75));
+
+    // Ties go to the earlier label; keywords match whole words only.
+    let out = classifier
+        .process(json(
+            r#"{"subject": "invoice", "body": {"text": "errors"}}"#,
+        ))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(out["_label"].as_str(), Some("billing_complaint"));
+    assert_eq!(out["_label_score"].as_f64(), Some(0.5));
+
+    let out = classifier
+        .process(json(r#"{"subject": "Hello there"}"#))
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(out["_label"].is_null());
+    assert!(out["_label_score"].is_null());
+
+    assert_eq!(
+        classifier.metrics(),
+        vec![
+            ("labels.billing_complaint".to_string(), 2),
+            ("labels.bug_report".to_string(), 1),
+            ("labels._unlabeled".to_string(), 1),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_threshold_fallback_and_output_fields() {
+    let classifier = RecordClassifier::from_keywords(support_labels(), vec!["text".to_string()])
+        .unwrap()
+        .with_threshold(0.6)
+        .with_fallback("other")
+        .with_label_field("category")
+        .with_score_field("category_score");
+
+    // Half the matches are billing, half bugs: below the threshold.
+    let out = classifier
+        .process(json(r#"{"text": "refund for the crash"}"#))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(out["category"].as_str(), Some("other"));
+    assert!(out["category_score"].is_null());
+    assert!(out.get("_label").is_none());
+
+    let out = classifier
+        .process(json(r#"{"text": "stack trace attached"}"#))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(out["category"].as_str(), Some("bug_report"));
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("text", DataType::Utf8, true),
+        Field::new("category", DataType::Utf8, true),
+    ]));
+    let updated = classifier.update_schema(&schema).unwrap();
+    let names: Vec<&str> = updated.fields().iter().map(|f| f.name().as_str()).collect();
+    assert_eq!(names, ["text", "category", "category_score"]);
+    assert_eq!(
+        updated
+            .field_with_name("category_score")
+            .unwrap()
+            .data_type(),
+        &DataType::Float64
+    );
+    assert_eq!(classifier.counts()[2], ("other".to_string(), 1));
+}
+
+#[test]
+fn test_classifier_rejects_invalid_labels() {
+    let fields = vec!["text".to_string()];
+    assert!(RecordClassifier::from_keywords(Vec::new(), fields.clone()).is_err());
+    assert!(RecordClassifier::from_keywords(support_labels(), Vec::new()).is_err());
+    assert!(
+        RecordClassifier::from_keywords(vec![label("empty", &["  ", "!"])], fields.clone())
+            .is_err()
+    );
+    let duplicate = vec![label("a", &["x"]), label("a", &["y"])];
+    assert!(RecordClassifier::from_keywords(duplicate, fields.clone()).is_err());
+    // The null-label bucket's key is reserved; `none` is an ordinary label.
+    assert!(
+        RecordClassifier::from_keywords(vec![label("_unlabeled", &["x"])], fields.clone()).is_err()
+    );
+    assert!(RecordClassifier::from_keywords(vec![label("none", &["x"])], fields).is_ok());
+}
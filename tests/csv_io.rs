@@ -0,0 +1,150 @@
+use std::io::Write;
+use std::sync::Arc;
+
+use arrow::array::{Int64Array, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::record_batch::RecordBatch;
+use simd_json::prelude::*;
+use tempfile::NamedTempFile;
+use udo::core::config::{CsvColumn, CsvColumnType, CsvOptions};
+use udo::core::pipeline::{InputSource, OutputSink};
+use udo::io::sink::CsvSink;
+use udo::io::source::CsvSource;
+
+#[tokio::test]
+async fn test_csv_source_infers_schema_with_dialect() {
+    let mut input = NamedTempFile::new().unwrap();
+    writeln!(input, "id;name;score").unwrap();
+    writeln!(input, "1;'Smith; John';2.5").unwrap();
+    writeln!(input, "2;NA;3").unwrap();
+
+    let options = CsvOptions {
+        delimiter: ';',
+        quote: Some('\''),
+        null_value: Some("NA".to_string()),
+        ..Default::default()
+    };
+    let mut source = CsvSource::new(input.path().to_path_buf(), &options, None, 100).unwrap();
+
+    let first = source.next_record().await.unwrap().unwrap();
+    assert_eq!(first.get("id").unwrap().as_i64(), Some(1));
+    assert_eq!(first.get("name").unwrap().as_str(), Some("Smith; John"));
+    assert_eq!(first.get("score").unwrap().as_f64(), Some(2.5));
+
+    let second = source.next_record().await.unwrap().unwrap();
+    assert!(second.get("name").is_none());
+    assert!(source.next_record().await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_csv_source_explicit_schema_and_date_format() {
+    let mut input = NamedTempFile::new().unwrap();
+    writeln!(input, "00042|31/12/2023").unwrap();
+
+    let options = CsvOptions {
+        delimiter: '|',
+        has_header: false,
+        date_format: Some("%d/%m/%Y".to_string()),
+        ..Default::default()
+    };
+    let columns = vec![
+        CsvColumn {
+            name: "code".to_string(),
+            data_type: CsvColumnType::Utf8,
+        },
+        CsvColumn {
+            name: "signup".to_string(),
+            data_type: CsvColumnType::Date,
+        },
+    ];
+    let mut source =
+        CsvSource::new(input.path().to_path_buf(), &options, Some(&columns), 0).unwrap();
+
+    let record = source.next_record().await.unwrap().unwrap();
+    assert_eq!(record.get("code").unwrap().as_str(), Some("00042"));
+    assert_eq!(record.get("signup").unwrap().as_str(), Some("2023-12-31"));
+}
+
+#[tokio::test]
+async fn test_csv_sink_writes_dialect() {
+    let output = NamedTempFile::new().unwrap();
+    let options = CsvOptions {
+        delimiter: '\t',
+        null_value: Some("\\N".to_string()),
+        ..Default::default()
+    };
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int64, true),
+        Field::new("name", DataType::Utf8, true),
+    ]));
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int64Array::from(vec![1, 2])),
+            Arc::new(StringArray::from(vec![Some("a"), None])),
+        ],
+    )
+    .unwrap();
+
+    let mut sink = CsvSink::new(output.path().to_path_buf(), &options).unwrap();
+    sink.write_batch(batch).await.unwrap();
+    sink.close().await.unwrap();
+
+    let written = std::fs::read_to_string(output.path()).unwrap();
+    assert_eq!(written, "id\tname\n1\ta\n2\t\\N\n");
+}
+
+#[tokio::test]
+async fn test_csv_source_rejects_bad_rows_with_their_line() {
+    let mut input = NamedTempFile::new().unwrap();
+    writeln!(input, "id,signup").unwrap();
+    writeln!(input, "1,31/12/2023").unwrap();
+    writeln!(input, "two,01/01/2024").unwrap();
+    writeln!(input, "3").unwrap();
+    writeln!(input, "4,2024-02-30").unwrap();
+    writeln!(input, "5,\"15/03/2024\"").unwrap();
+
+    let options = CsvOptions {
+        date_format: Some("%d/%m/%Y".to_string()),
+        ..Default::default()
+    };
+    let columns = vec![
+        CsvColumn {
+            name: "id".to_string(),
+            data_type: CsvColumnType::Int64,
+        },
+        CsvColumn {
+            name: "signup".to_string(),
+            data_type: CsvColumnType::Date,
+        },
+    ];
+    let mut source =
+        CsvSource::new(input.path().to_path_buf(), &options, Some(&columns), 0).unwrap();
+
+    let mut ids = Vec::new();
+    let mut rejected = Vec::new();
+    loop {
+        match source.next_record().await {
+            Ok(Some(record)) => {
+                ids.push(record.get("id").unwrap().as_i64().unwrap());
+                assert_eq!(source.position(), Some(ids[ids.len() - 1] as u64 + 1));
+            }
+            Ok(None) => break,
+            Err(udo::UdoError::SourceDecode { position, raw, .. }) => {
+                assert_eq!(source.position(), position);
+                rejected.push((position, String::from_utf8(raw).unwrap()));
+            }
+            Err(e) => panic!("unexpected error: {}", e),
+        }
+    }
+    assert_eq!(ids, vec![1, 5]);
+    assert_eq!(
+        rejected,
+        vec![
+            (Some(3), "two,01/01/2024".to_string()),
+            (Some(4), "3".to_string()),
+            (Some(5), "4,2024-02-30".to_string()),
+        ]
+    );
+}
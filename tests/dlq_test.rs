@@ -0,0 +1,439 @@
+use async_trait::async_trait;
+use simd_json::{json, prelude::*, OwnedValue};
+use std::io::Write;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use tempfile::NamedTempFile;
+use udo::core::pipeline::{DataProcessor, DeadLetter, DlqSink, InputSource};
+use udo::io::dlq::{DlqReplaySource, FileDlq};
+use udo::io::source::FileSource;
+use udo::{PipelineRunner, UdoError};
+
+#[derive(Clone, Default)]
+struct MemoryDlq {
+    letters: Arc<Mutex<Vec<DeadLetter>>>,
+}
+
+#[async_trait]
+impl DlqSink for MemoryDlq {
+    async fn write_dead_letter(&mut self, letter: DeadLetter) -> udo::Result<()> {
+        self.letters.lock().unwrap().push(letter);
+        Ok(())
+    }
+}
+
+async fn ndjson_source(lines: &[&str]) -> (NamedTempFile, FileSource) {
+    let mut input = NamedTempFile::new().unwrap();
+    for line in lines {
+        writeln!(input, "{}", line).unwrap();
+    }
+    let source = FileSource::new(input.path().to_path_buf()).await.unwrap();
+    (input, source)
+}
+
+#[tokio::test]
+async fn test_file_dlq_writes_valid_envelope() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("dlq.ndjson");
+
+    let mut dlq = FileDlq::new(path.clone()).unwrap();
+    let record: OwnedValue = json!({"id": 1});
+    let error = UdoError::Pipeline(r#"field "name" is "bad""#.to_string());
+    let mut letter = DeadLetter::new(record, &error);
+    letter.pipeline = Some("orders".to_string());
+    letter.stage = Some(2);
+    letter.source_position = Some(17);
+    dlq.write_dead_letter(letter).await.unwrap();
+    dlq.close().await.unwrap();
+
+    let contents = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(contents.lines().count(), 1);
+    let envelope: serde_json::Value = serde_json::from_str(contents.trim()).unwrap();
+    assert_eq!(
+        envelope["error"],
+        r#"Pipeline Error: field "name" is "bad""#
+    );
+    assert_eq!(envelope["error_kind"], "pipeline");
+    assert_eq!(envelope["pipeline"], "orders");
+    assert_eq!(envelope["stage"], 2);
+    assert_eq!(envelope["source_position"], 17);
+    assert_eq!(envelope["attempts"], 1);
+    assert_eq!(envelope["record"]["id"], 1);
+}
+
+#[tokio::test]
+async fn test_dlq_replay_source_yields_original_records() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("dlq.ndjson");
+
+    let mut dlq = FileDlq::new(path.clone()).unwrap();
+    for i in 0..2 {
+        let record: OwnedValue = json!({ "id": i });
+        let error = UdoError::AiModel("oom".to_string());
+        dlq.write_dead_letter(DeadLetter::new(record, &error))
+            .await
+            .unwrap();
+    }
+    dlq.close().await.unwrap();
+
+    let mut source = DlqReplaySource::new(path).await.unwrap();
+    let first = source.next_record().await.unwrap().unwrap();
+    assert_eq!(first.get("id").unwrap().as_i64(), Some(0));
+    assert_eq!(source.position(), Some(1));
+    assert!(source.next_record().await.unwrap().is_some());
+    assert!(source.next_record().await.unwrap().is_none());
+}
+
+#[tokio::test]
+#[cfg(feature = "cloud")]
+async fn test_cloud_dlq_rotates_parts() {
+    use udo::core::config::DlqRotation;
+    use udo::io::dlq::CloudDlq;
+
+    let dir = tempfile::tempdir().unwrap();
+    let url = format!("file://{}", dir.path().display());
+    let rotation = DlqRotation {
+        max_part_bytes: 1,
+        max_part_age_secs: 3600,
+        ..DlqRotation::default()
+    };
+
+    let mut dlq = CloudDlq::with_rotation(&url, &rotation).unwrap();
+    for i in 0..3 {
+        let record: OwnedValue = json!({ "id": i });
+        let error = UdoError::Pipeline("boom".to_string());
+        dlq.write_dead_letter(DeadLetter::new(record, &error))
+            .await
+            .unwrap();
+    }
+    dlq.close().await.unwrap();
+
+    let parts: Vec<_> = std::fs::read_dir(dir.path())
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    assert_eq!(parts.len(), 3);
+    assert!(parts
+        .iter()
+        .all(|p| p.starts_with("dlq_") && p.ends_with(".ndjson")));
+}
+
+#[tokio::test]
+async fn test_source_and_sink_failures_reach_dlq() {
+    let (_input, source) = ndjson_source(&[
+        r#"{"id": 1}"#,
+        r#"{"id": "#,
+        r#"{"id": 2}"#,
+        r#"{"id": "three"}"#,
+    ])
+    .await;
+
+    let dlq = MemoryDlq::default();
+    let mut runner = PipelineRunner::new(Box::new(source), 10);
+    runner.set_dlq(Box::new(dlq.clone()));
+    runner.run(None).await.unwrap();
+
+    let letters = dlq.letters.lock().unwrap();
+    assert_eq!(letters.len(), 2);
+    let decode = letters
+        .iter()
+        .find(|l| l.error_kind == "source_decode")
+        .unwrap();
+    assert_eq!(decode.source_position, Some(2));
+    assert_eq!(decode.record.as_str(), Some(r#"{"id": "#));
+    let mismatch = letters
+        .iter()
+        .find(|l| l.error_kind == "schema_mismatch")
+        .unwrap();
+    assert_eq!(mismatch.processor.as_deref(), Some("sink"));
+}
+
+#[tokio::test]
+async fn test_max_error_rate_aborts_job() {
+    let mut lines = vec![r#"{"id": 1}"#];
+    lines.extend(std::iter::repeat_n("garbage", 50));
+    let (_input, source) = ndjson_source(&lines).await;
+
+    let mut runner = PipelineRunner::new(Box::new(source), 10);
+    runner.set_max_error_rate(0.1, 10);
+    let err = runner.run(None).await.unwrap_err();
+    assert!(err.to_string().contains("max_error_rate"));
+}
+
+/// Yields one record and one undecodable line, then stalls before ending.
+struct StallingSource {
+    step: u32,
+}
+
+#[async_trait]
+impl InputSource for StallingSource {
+    async fn next_record(&mut self) -> udo::Result<Option<OwnedValue>> {
+        self.step += 1;
+        match self.step {
+            1 => Ok(Some(json!({"id": 1}))),
+            2 => Err(UdoError::SourceDecode {
+                position: Some(2),
+                raw: b"{".to_vec(),
+                reason: "truncated".to_string(),
+            }),
+            _ => {
+                tokio::time::sleep(std::time::Duration::from_millis(2500)).await;
+                Ok(None)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+struct PollingDlq {
+    /// Letters written, and whether the DLQ was closed, at each `flush_due` call.
+    polls: Arc<Mutex<Vec<(usize, bool)>>>,
+    letters: Arc<Mutex<usize>>,
+    closed: Arc<Mutex<bool>>,
+}
+
+#[async_trait]
+impl DlqSink for PollingDlq {
+    async fn write_dead_letter(&mut self, _letter: DeadLetter) -> udo::Result<()> {
+        *self.letters.lock().unwrap() += 1;
+        Ok(())
+    }
+
+    async fn flush_due(&mut self) -> udo::Result<()> {
+        let letters = *self.letters.lock().unwrap();
+        let closed = *self.closed.lock().unwrap();
+        self.polls.lock().unwrap().push((letters, closed));
+        Ok(())
+    }
+
+    async fn close(&mut self) -> udo::Result<()> {
+        *self.closed.lock().unwrap() = true;
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_runner_polls_dlq_while_source_is_idle() {
+    let dlq = PollingDlq::default();
+    let mut runner = PipelineRunner::new(Box::new(StallingSource { step: 0 }), 10);
+    runner.set_dlq(Box::new(dlq.clone()));
+    runner.run(None).await.unwrap();
+
+    // The letter is written before the stall; the timer keeps polling during it.
+    let polls = dlq.polls.lock().unwrap();
+    let idle_polls = polls
+        .iter()
+        .filter(|(letters, closed)| *letters == 1 && !closed);
+    assert!(idle_polls.count() >= 2);
+    assert!(*dlq.closed.lock().unwrap());
+}
+
+#[tokio::test]
+#[cfg(feature = "cloud")]
+async fn test_cloud_dlq_flushes_aged_part_without_new_letters() {
+    use udo::core::config::DlqRotation;
+    use udo::io::dlq::CloudDlq;
+
+    let dir = tempfile::tempdir().unwrap();
+    let url = format!("file://{}", dir.path().display());
+    let rotation = DlqRotation {
+        max_part_bytes: 1 << 20,
+        max_part_age_secs: 1,
+        ..DlqRotation::default()
+    };
+    let parts = || std::fs::read_dir(dir.path()).unwrap().count();
+
+    let mut dlq = CloudDlq::with_rotation(&url, &rotation).unwrap();
+    let error = UdoError::Pipeline("boom".to_string());
+    dlq.write_dead_letter(DeadLetter::new(json!({"id": 1}), &error))
+        .await
+        .unwrap();
+    dlq.flush_due().await.unwrap();
+    assert_eq!(parts(), 0);
+
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    dlq.flush_due().await.unwrap();
+    assert_eq!(parts(), 1);
+}
+
+#[tokio::test]
+#[cfg(feature = "cloud")]
+async fn test_cloud_dlq_keeps_parts_while_uploads_fail() {
+    use udo::core::config::DlqRotation;
+    use udo::io::dlq::CloudDlq;
+
+    // A file where the DLQ directory should be makes every put fail.
+    let dir = tempfile::tempdir().unwrap();
+    let target = dir.path().join("dlq");
+    std::fs::write(&target, b"").unwrap();
+    let url = format!("file://{}", target.display());
+    let rotation = DlqRotation {
+        max_part_bytes: 1,
+        max_part_age_secs: 3600,
+        max_pending_bytes: 1 << 20,
+    };
+
+    let mut dlq = CloudDlq::with_rotation(&url, &rotation).unwrap();
+    let error = UdoError::Pipeline("boom".to_string());
+    for i in 0..3 {
+        dlq.write_dead_letter(DeadLetter::new(json!({ "id": i }), &error))
+            .await
+            .unwrap();
+    }
+    dlq.flush_due().await.unwrap();
+
+    std::fs::remove_file(&target).unwrap();
+    dlq.close().await.unwrap();
+    let parts = std::fs::read_dir(&target).unwrap().count();
+    assert_eq!(parts, 3);
+}
+
+#[tokio::test]
+async fn test_dlq_replay_skips_source_decode_letters() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("dlq.ndjson");
+
+    let mut dlq = FileDlq::new(path.clone()).unwrap();
+    let decode = UdoError::SourceDecode {
+        position: Some(3),
+        raw: br#"{"id": "#.to_vec(),
+        reason: "truncated".to_string(),
+    };
+    dlq.write_dead_letter(DeadLetter::new(OwnedValue::from(r#"{"id": "#), &decode))
+        .await
+        .unwrap();
+    let error = UdoError::AiModel("oom".to_string());
+    dlq.write_dead_letter(DeadLetter::new(json!({"id": 7}), &error))
+        .await
+        .unwrap();
+    dlq.close().await.unwrap();
+
+    let mut source = DlqReplaySource::new(path).await.unwrap();
+    let record = source.next_record().await.unwrap().unwrap();
+    assert_eq!(record.get("id").unwrap().as_i64(), Some(7));
+    assert!(source.next_record().await.unwrap().is_none());
+    assert_eq!(source.skipped(), 1);
+}
+
+#[tokio::test]
+async fn test_aborted_job_still_closes_dlq() {
+    let mut lines = vec![r#"{"id": 1}"#];
+    lines.extend(std::iter::repeat_n("garbage", 50));
+    let (_input, source) = ndjson_source(&lines).await;
+
+    let dlq = PollingDlq::default();
+    let mut runner = PipelineRunner::new(Box::new(source), 10);
+    runner.set_dlq(Box::new(dlq.clone()));
+    runner.set_max_error_rate(0.1, 10);
+    assert!(runner.run(None).await.is_err());
+
+    assert!(*dlq.letters.lock().unwrap() > 0);
+    assert!(*dlq.closed.lock().unwrap());
+}
+
+/// Keeps only the first row of each batch.
+struct FirstOfBatch;
+
+#[async_trait]
+impl DataProcessor for FirstOfBatch {
+    async fn process(&self, record: OwnedValue) -> udo::Result<Option<OwnedValue>> {
+        Ok(Some(record))
+    }
+
+    async fn process_batch(&self, rows: Vec<OwnedValue>) -> udo::Result<Vec<Option<OwnedValue>>> {
+        Ok(rows
+            .into_iter()
+            .enumerate()
+            .map(|(i, row)| (i == 0).then_some(row))
+            .collect())
+    }
+}
+
+/// Counts the records it processes and fails every batch.
+struct FailingBatch {
+    processed: Arc<AtomicU32>,
+}
+
+#[async_trait]
+impl DataProcessor for FailingBatch {
+    async fn process(&self, record: OwnedValue) -> udo::Result<Option<OwnedValue>> {
+        self.processed.fetch_add(1, Ordering::SeqCst);
+        Ok(Some(record))
+    }
+
+    async fn process_batch(&self, _rows: Vec<OwnedValue>) -> udo::Result<Vec<Option<OwnedValue>>> {
+        Err(UdoError::Pipeline("batch rejected".to_string()))
+    }
+
+    fn name(&self) -> &str {
+        "failing_batch"
+    }
+}
+
+#[tokio::test]
+async fn test_stages_run_in_order_and_failed_batches_reach_dlq() {
+    let lines: Vec<String> = (0..5).map(|i| format!(r#"{{"id": {}}}"#, i)).collect();
+    let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+    let (_input, source) = ndjson_source(&lines).await;
+
+    let processed = Arc::new(AtomicU32::new(0));
+    let dlq = MemoryDlq::default();
+    let mut runner = PipelineRunner::new(Box::new(source), 2);
+    runner.set_dlq(Box::new(dlq.clone()));
+    runner.add_processor(Box::new(FirstOfBatch));
+    runner.add_processor(Box::new(FailingBatch {
+        processed: processed.clone(),
+    }));
+    runner.run(None).await.unwrap();
+
+    // Batches of 2, 2 and 1 records: only the first row of each reaches the next stage.
+    assert_eq!(processed.load(Ordering::SeqCst), 3);
+    let letters = dlq.letters.lock().unwrap();
+    let indices: Vec<Option<u64>> = letters.iter().map(|l| l.record_index).collect();
+    assert_eq!(indices, [Some(0), Some(2), Some(4)]);
+    for letter in letters.iter() {
+        assert_eq!(letter.stage, Some(1));
+        assert_eq!(letter.processor.as_deref(), Some("failing_batch"));
+        assert_eq!(letter.error, "Pipeline Error: batch rejected");
+    }
+}
+
+/// Panics on the record with `id` 1.
+struct PanickingProcessor;
+
+#[async_trait]
+impl DataProcessor for PanickingProcessor {
+    async fn process(&self, record: OwnedValue) -> udo::Result<Option<OwnedValue>> {
+        if record["id"].as_u64() == Some(1) {
+            panic!("boom");
+        }
+        Ok(Some(record))
+    }
+}
+
+#[tokio::test]
+async fn test_processor_panic_reaches_dlq() {
+    let (_input, source) = ndjson_source(&[r#"{"id": 0}"#, r#"{"id": 1}"#, r#"{"id": 2}"#]).await;
+
+    let dlq = MemoryDlq::default();
+    let mut runner = PipelineRunner::new(Box::new(source), 10);
+    runner.set_dlq(Box::new(dlq.clone()));
+    runner.add_processor(Box::new(PanickingProcessor));
+    runner.run(None).await.unwrap();
+
+    {
+        let letters = dlq.letters.lock().unwrap();
+        assert_eq!(letters.len(), 1);
+        assert_eq!(letters[0].error_kind, "panic");
+        assert_eq!(letters[0].record_index, Some(1));
+        assert_eq!(letters[0].stage, Some(0));
+        assert_eq!(letters[0].record["id"].as_u64(), Some(1));
+    }
+
+    // The panicked record counts against the error budget.
+    let (_input, source) = ndjson_source(&[r#"{"id": 0}"#, r#"{"id": 1}"#, r#"{"id": 2}"#]).await;
+    let mut runner = PipelineRunner::new(Box::new(source), 10);
+    runner.add_processor(Box::new(PanickingProcessor));
+    runner.set_max_error_rate(0.1, 1);
+    assert!(runner.run(None).await.is_err());
+}
@@ -0,0 +1,117 @@
+use simd_json::prelude::*;
+use simd_json::OwnedValue;
+use std::io::Write;
+use udo::core::config::TokenOverflow;
+use udo::core::pipeline::DataProcessor;
+use udo::processors::firewall::counter::{TokenCounter, CL100K_PATTERN};
+use udo::processors::firewall::TokenFirewall;
+
+fn json(text: &str) -> OwnedValue {
+    simd_json::to_owned_value(&mut text.as_bytes().to_vec()).unwrap()
+}
+
+fn metric(firewall: &TokenFirewall, name: &str) -> u64 {
+    firewall
+        .metrics()
+        .into_iter()
+        .find(|(k, _)| k == name)
+        .map(|(_, v)| v)
+        .unwrap()
+}
+
+/// One character per token, so budgets in the tests are easy to follow.
+fn chars() -> TokenCounter {
+    TokenCounter::Approximate {
+        chars_per_token: 1.0,
+    }
+}
+
+#[test]
+fn test_approximate_counter_counts_and_truncates() {
+    let counter = TokenCounter::Approximate {
+        chars_per_token: 4.0,
+    };
+    assert_eq!(counter.count(""), 0);
+    assert_eq!(counter.count("abcdefghi"), 3);
+    assert_eq!(counter.truncate("abcdefghi", 2), "abcdefgh");
+    assert_eq!(counter.truncate("héllo wörld", 1), "héll");
+    assert_eq!(counter.truncate("abc", 5), "abc");
+}
+
+#[test]
+fn test_tiktoken_encodings_and_rank_files() {
+    let cl100k = TokenCounter::tiktoken("cl100k_base").unwrap();
+    assert_eq!(cl100k.count("hello world"), 2);
+    assert_eq!(cl100k.truncate("hello world, again", 2), "hello world");
+    assert!(TokenCounter::tiktoken("gpt-9").is_err());
+
+    use base64::Engine;
+    let encode = |bytes: &[u8]| base64::engine::general_purpose::STANDARD.encode(bytes);
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    for byte in 0..=255u8 {
+        writeln!(file, "{} {}", encode(&[byte]), byte).unwrap();
+    }
+    writeln!(file, "{} 256", encode(b"ab")).unwrap();
+    let counter = TokenCounter::tiktoken_file(file.path(), CL100K_PATTERN).unwrap();
+    assert_eq!(counter.count("abab"), 2);
+    assert_eq!(counter.count("abc"), 2);
+    assert_eq!(counter.truncate("abcab", 2), "abc");
+
+    writeln!(file, "not-a-rank").unwrap();
+    let err = TokenCounter::tiktoken_file(file.path(), CL100K_PATTERN)
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("line 258"));
+}
+
+#[tokio::test]
+async fn test_firewall_trims_fields_by_priority() {
+    let record = json(&format!(
+        r#"{{"title": "abcdefghij", "meta": {{"k": 1}}, "body": "{}"}}"#,
+        "x".repeat(30)
+    ));
+
+    let firewall = TokenFirewall::new(chars())
+        .with_record_budget(20)
+        .with_priority(vec!["title".to_string(), "body".to_string()]);
+    assert_eq!(firewall.record_tokens(&record), 47);
+    let out = firewall.process(record.clone()).await.unwrap().unwrap();
+    // The unlisted, non-string `meta` goes first, then `body` is cut to fit.
+    assert!(out.get("meta").is_none());
+    assert_eq!(out["title"].as_str(), Some("abcdefghij"));
+    assert_eq!(out["body"].as_str().unwrap().len(), 10);
+    assert_eq!(firewall.record_tokens(&out), 20);
+    assert_eq!(metric(&firewall, "tokens_before"), 47);
+    assert_eq!(metric(&firewall, "tokens_after"), 20);
+    assert_eq!(metric(&firewall, "fields_truncated"), 1);
+    assert_eq!(metric(&firewall, "fields_dropped"), 1);
+
+    let dropping = TokenFirewall::new(chars())
+        .with_record_budget(20)
+        .with_priority(vec!["title".to_string(), "body".to_string()])
+        .with_overflow(TokenOverflow::DropFields);
+    let out = dropping.process(record).await.unwrap().unwrap();
+    assert!(out.get("meta").is_none() && out.get("body").is_none());
+    assert_eq!(dropping.record_tokens(&out), 10);
+}
+
+#[tokio::test]
+async fn test_firewall_batch_budget_keeps_rows_that_fit() {
+    let firewall = TokenFirewall::new(chars()).with_batch_budget(12);
+    let mut rows = Vec::new();
+    for text in ["aaaaa", "bbbbb", "cccccccccccccccccccc", "dd"] {
+        let record = json(&format!(r#"{{"text": "{}"}}"#, text));
+        rows.push(firewall.process(record).await.unwrap().unwrap());
+    }
+    let kept = firewall.process_batch(rows).await.unwrap();
+    let texts: Vec<&str> = kept
+        .iter()
+        .flatten()
+        .map(|r| r["text"].as_str().unwrap())
+        .collect();
+    assert_eq!(texts, vec!["aaaaa", "bbbbb", "dd"]);
+    assert_eq!(metric(&firewall, "records"), 4);
+    assert_eq!(metric(&firewall, "records_dropped"), 1);
+    assert_eq!(metric(&firewall, "tokens_before"), 32);
+    assert_eq!(metric(&firewall, "tokens_after"), 12);
+}
@@ -0,0 +1,136 @@
+use arrow::datatypes::Schema;
+use async_trait::async_trait;
+use simd_json::prelude::*;
+use simd_json::OwnedValue;
+use std::sync::Arc;
+use udo::core::pipeline::DataProcessor;
+use udo::processors::language::{detect, LanguageDetector, LanguageGate};
+
+fn json(text: &str) -> OwnedValue {
+    simd_json::to_owned_value(&mut text.as_bytes().to_vec()).unwrap()
+}
+
+#[test]
+fn test_detects_languages_by_script_and_common_words() {
+    for (text, expected) in [
+        ("Please send me the invoice for my last order.", "en"),
+        (
+            "Ich habe die Rechnung nicht bekommen, bitte schicken Sie sie erneut.",
+            "de",
+        ),
+        ("Je n'ai pas reçu la facture pour ma commande.", "fr"),
+        ("No he recibido la factura de mi pedido, por favor.", "es"),
+        ("Non ho ricevuto la fattura per il mio ordine.", "it"),
+        ("Ik heb de factuur niet ontvangen, kunt u die sturen?", "nl"),
+        ("Não recebi a fatura do meu pedido.", "pt"),
+        ("請求書がまだ届いていません。", "ja"),
+        ("我还没有收到发票。", "zh"),
+        ("청구서를 아직 받지 못했습니다.", "ko"),
+        ("Я не получил счёт за заказ.", "ru"),
+    ] {
+        let detection = detect(text).unwrap_or_else(|| panic!("nothing for {}", text));
+        assert_eq!(detection.language, expected, "{}", text);
+        assert!(detection.confidence > 0.0 && detection.confidence <= 1.0);
+    }
+
+    // Kanji and English product names in Japanese text still make it Japanese.
+    assert_eq!(detect("iPhone の請求書").unwrap().language, "ja");
+    assert_eq!(detect("Danke!").map(|d| d.language), None);
+    assert!(detect("12345 !!").is_none());
+    assert!(detect("").is_none());
+
+    let clear = detect("Das ist nicht die Rechnung, die ich bestellt habe.").unwrap();
+    let mixed = detect("Das invoice ist the wrong one").unwrap();
+    assert!(clear.confidence > mixed.confidence);
+}
+
+#[tokio::test]
+async fn test_detector_annotates_records() {
+    let detector = LanguageDetector::new(vec!["subject".to_string(), "body.text".to_string()])
+        .with_min_confidence(0.5);
+
+    let out = detector
+        .process(json(
+            r#"{"subject": "Rechnung", "body": {"text": "Ich habe die Rechnung nicht bekommen."}}"#,
+        ))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(out["_lang"].as_str(), Some("de"));
+    assert!(out["_lang_confidence"].as_f64().unwrap() >= 0.5);
+
+    for record in [
+        r#"{"subject": "12345"}"#,
+        r#"{"subject": "the die und de"}"#,
+    ] {
+        let out = detector.process(json(record)).await.unwrap().unwrap();
+        assert!(out["_lang"].is_null(), "{}", record);
+        assert!(out["_lang_confidence"].is_null());
+    }
+
+    let schema = detector.update_schema(&Arc::new(Schema::empty())).unwrap();
+    let names: Vec<&str> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+    assert_eq!(names, ["_lang", "_lang_confidence"]);
+    assert_eq!(
+        detector.metrics(),
+        vec![
+            ("languages.de".to_string(), 1),
+            ("languages.und".to_string(), 2),
+        ]
+    );
+}
+
+/// Marks every record it processes and keeps only the first row of each batch.
+struct Marker;
+
+#[async_trait]
+impl DataProcessor for Marker {
+    async fn process(&self, mut record: OwnedValue) -> udo::Result<Option<OwnedValue>> {
+        if let Some(obj) = record.as_object_mut() {
+            obj.insert("marked".to_string(), OwnedValue::from(true));
+        }
+        Ok(Some(record))
+    }
+
+    async fn process_batch(&self, rows: Vec<OwnedValue>) -> udo::Result<Vec<Option<OwnedValue>>> {
+        Ok(rows
+            .into_iter()
+            .enumerate()
+            .map(|(i, row)| (i == 0).then_some(row))
+            .collect())
+    }
+
+    fn name(&self) -> &str {
+        "marker"
+    }
+}
+
+#[tokio::test]
+async fn test_gate_runs_processor_only_for_its_languages() {
+    let gate = LanguageGate::new(
+        Box::new(Marker),
+        ["DE".to_string(), "und".to_string()],
+        "_lang",
+    );
+    assert_eq!(gate.name(), "marker");
+
+    let mut rows = Vec::new();
+    for (id, lang) in [(1, "\"de\""), (2, "\"en\""), (3, "null"), (4, "\"ja\"")] {
+        let record = json(&format!(r#"{{"id": {}, "_lang": {}}}"#, id, lang));
+        rows.push(gate.process(record).await.unwrap().unwrap());
+    }
+    let marked: Vec<bool> = rows.iter().map(|r| r.get("marked").is_some()).collect();
+    assert_eq!(marked, [true, false, true, false]);
+
+    // Only German and undetermined rows reach the processor's batch step; it keeps
+    // the first of them, in the place of the first.
+    let ids: Vec<u64> = gate
+        .process_batch(rows)
+        .await
+        .unwrap()
+        .iter()
+        .flatten()
+        .map(|r| r["id"].as_u64().unwrap())
+        .collect();
+    assert_eq!(ids, [1, 2, 4]);
+}
@@ -0,0 +1,257 @@
+#[cfg(any(feature = "semantic", feature = "ner"))]
+use std::collections::HashMap;
+#[cfg(any(feature = "semantic", feature = "ner"))]
+use std::path::Path;
+#[cfg(any(feature = "semantic", feature = "ner"))]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(any(feature = "semantic", feature = "ner"))]
+use std::sync::{Arc, Mutex};
+#[cfg(any(feature = "semantic", feature = "ner"))]
+use std::time::Duration;
+#[cfg(any(feature = "semantic", feature = "ner"))]
+use udo::core::config::{ModelPin, ModelsConfig};
+#[cfg(any(feature = "semantic", feature = "ner"))]
+use udo::core::inference::{InferencePool, MicroBatcher};
+#[cfg(any(feature = "semantic", feature = "ner"))]
+use udo::core::model::{cached_models, sha256_file, ModelFiles, ModelPool, ModelSpec};
+
+/// Lays out a hub cache entry the way `hf-hub` does: `refs/<branch>` names the commit
+/// whose snapshot directory holds the files.
+#[cfg(any(feature = "semantic", feature = "ner"))]
+fn stage_model(cache: &Path, id: &str, commit: &str, refs: &[&str], weights: &[u8]) {
+    let repo = cache.join(format!("models--{}", id.replace('/', "--")));
+    let snapshot = repo.join("snapshots").join(commit);
+    std::fs::create_dir_all(&snapshot).unwrap();
+    std::fs::create_dir_all(repo.join("refs")).unwrap();
+    for r in refs {
+        std::fs::write(repo.join("refs").join(r), commit).unwrap();
+    }
+    std::fs::write(snapshot.join("config.json"), "{}").unwrap();
+    std::fs::write(snapshot.join("tokenizer.json"), "{}").unwrap();
+    std::fs::write(snapshot.join("model.safetensors"), weights).unwrap();
+}
+
+#[test]
+#[cfg(any(feature = "semantic", feature = "ner"))]
+fn test_offline_fetch_resolves_refs_and_commits() {
+    let cache = tempfile::tempdir().unwrap();
+    stage_model(cache.path(), "org/tiny", "abc123", &["main"], b"weights-v1");
+    stage_model(cache.path(), "org/tiny", "def456", &[], b"weights-v2");
+
+    let settings = ModelsConfig {
+        cache_dir: Some(cache.path().to_path_buf()),
+        offline: true,
+        ..Default::default()
+    };
+    let spec = ModelSpec::new("org/tiny").with_settings(&settings).unwrap();
+    let files = ModelFiles::fetch(&spec).unwrap();
+    assert_eq!(files.snapshot().as_deref(), Some("abc123"));
+
+    let spec = ModelSpec::new("org/tiny")
+        .with_revision(Some("def456".to_string()))
+        .with_settings(&settings)
+        .unwrap();
+    let files = ModelFiles::fetch(&spec).unwrap();
+    assert_eq!(files.snapshot().as_deref(), Some("def456"));
+    assert_eq!(std::fs::read(&files.weights).unwrap(), b"weights-v2");
+
+    let missing = ModelSpec::new("org/absent")
+        .with_settings(&settings)
+        .unwrap();
+    let err = ModelFiles::fetch(&missing).err().unwrap().to_string();
+    assert!(err.contains("models pull"), "{}", err);
+}
+
+#[test]
+#[cfg(any(feature = "semantic", feature = "ner"))]
+fn test_pins_apply_revision_and_verify_checksum() {
+    let cache = tempfile::tempdir().unwrap();
+    stage_model(cache.path(), "org/tiny", "abc123", &["main"], b"weights-v1");
+    let weights = cache
+        .path()
+        .join("models--org--tiny/snapshots/abc123/model.safetensors");
+    let good = sha256_file(&weights).unwrap();
+
+    let pinned = |sha256: &str| ModelsConfig {
+        cache_dir: Some(cache.path().to_path_buf()),
+        offline: true,
+        pins: HashMap::from([(
+            "org/tiny".to_string(),
+            ModelPin {
+                revision: Some("abc123".to_string()),
+                sha256: Some(sha256.to_string()),
+                ..Default::default()
+            },
+        )]),
+        ..Default::default()
+    };
+
+    let spec = ModelSpec::new("org/tiny")
+        .with_settings(&pinned(&good))
+        .unwrap();
+    assert_eq!(spec.revision.as_deref(), Some("abc123"));
+    assert!(ModelFiles::fetch(&spec).is_ok());
+
+    let spec = ModelSpec::new("org/tiny")
+        .with_settings(&pinned(&"0".repeat(64)))
+        .unwrap();
+    let err = ModelFiles::fetch(&spec).err().unwrap().to_string();
+    assert!(err.contains("Checksum mismatch"), "{}", err);
+
+    // A processor asking for another revision than the pin is a config error.
+    let conflict = ModelSpec::new("org/tiny")
+        .with_revision(Some("main".to_string()))
+        .with_settings(&pinned(&good));
+    assert!(conflict.is_err());
+}
+
+#[test]
+#[cfg(any(feature = "semantic", feature = "ner"))]
+fn test_spec_precision_wins_over_pin_and_pipeline() {
+    use udo::core::config::Precision;
+
+    let settings = ModelsConfig {
+        precision: Precision::F16,
+        pins: HashMap::from([(
+            "org/pinned".to_string(),
+            ModelPin {
+                precision: Some(Precision::Q8_0),
+                ..Default::default()
+            },
+        )]),
+        ..Default::default()
+    };
+    let resolve = |spec: ModelSpec| spec.with_settings(&settings).unwrap().precision();
+
+    assert_eq!(ModelSpec::new("org/plain").precision(), Precision::F32);
+    assert_eq!(resolve(ModelSpec::new("org/plain")), Precision::F16);
+    assert_eq!(resolve(ModelSpec::new("org/pinned")), Precision::Q8_0);
+    assert_eq!(
+        resolve(ModelSpec::new("org/pinned").with_precision(Precision::Q4_0)),
+        Precision::Q4_0
+    );
+    assert_eq!(
+        resolve(ModelSpec::new("org/plain").with_precision(Precision::F32)),
+        Precision::F32
+    );
+}
+
+#[test]
+#[cfg(any(feature = "semantic", feature = "ner"))]
+fn test_cached_models_lists_snapshots_with_refs() {
+    let cache = tempfile::tempdir().unwrap();
+    stage_model(cache.path(), "org/tiny", "abc123", &["main", "v1"], b"1234");
+    stage_model(cache.path(), "org/tiny", "def456", &[], b"12");
+    stage_model(cache.path(), "other/model", "fff000", &["main"], b"");
+    std::fs::create_dir_all(cache.path().join("datasets--org--data")).unwrap();
+
+    let models = cached_models(cache.path()).unwrap();
+    let listed: Vec<(&str, &str)> = models
+        .iter()
+        .map(|m| (m.id.as_str(), m.commit.as_str()))
+        .collect();
+    assert_eq!(
+        listed,
+        vec![
+            ("org/tiny", "abc123"),
+            ("org/tiny", "def456"),
+            ("other/model", "fff000")
+        ]
+    );
+    assert_eq!(models[0].refs, vec!["main", "v1"]);
+    assert!(models[1].refs.is_empty());
+    assert_eq!(models[0].files.len(), 3);
+    assert_eq!(models[0].size, 4 + 2 + 2);
+
+    let empty = tempfile::tempdir().unwrap();
+    assert!(cached_models(&empty.path().join("missing"))
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+#[cfg(any(feature = "semantic", feature = "ner"))]
+fn test_model_pool_shares_models_by_spec_and_type() {
+    let pool = ModelPool::new(&ModelsConfig::default());
+    let loads = AtomicUsize::new(0);
+    let load = |name: &str| {
+        loads.fetch_add(1, Ordering::Relaxed);
+        Ok(name.to_string())
+    };
+
+    let spec = ModelSpec::new("org/tiny");
+    let first = pool.get_or_load(&spec, || load("first")).unwrap();
+    let second = pool.get_or_load(&spec, || load("second")).unwrap();
+    assert!(Arc::ptr_eq(&first, &second));
+    assert_eq!(loads.load(Ordering::Relaxed), 1);
+
+    // Another revision, or another kind of model for the same ID, loads separately.
+    let pinned = ModelSpec::new("org/tiny").with_revision(Some("abc123".to_string()));
+    assert_eq!(
+        *pool.get_or_load(&pinned, || load("pinned")).unwrap(),
+        "pinned"
+    );
+    let other = pool.get_or_load(&spec, || Ok(7u32)).unwrap();
+    assert_eq!(*other, 7);
+    assert_eq!(pool.len(), 3);
+
+    let failed = pool.get_or_load(&ModelSpec::new("org/broken"), || {
+        Err::<String, _>(udo::UdoError::AiModel("no weights".to_string()))
+    });
+    assert!(failed.is_err());
+    assert_eq!(pool.len(), 3);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[cfg(any(feature = "semantic", feature = "ner"))]
+async fn test_micro_batcher_merges_concurrent_requests() {
+    let pool = Arc::new(InferencePool::new(2));
+    let batches = Arc::new(Mutex::new(Vec::new()));
+    let seen = batches.clone();
+    let batcher = Arc::new(MicroBatcher::new(
+        pool,
+        16,
+        Duration::from_millis(50),
+        move |inputs: Vec<u32>| {
+            seen.lock().unwrap().push(inputs.len());
+            Ok(inputs.into_iter().map(|i| i * 10).collect())
+        },
+    ));
+
+    let calls = (0..8u32).map(|i| {
+        let batcher = batcher.clone();
+        tokio::spawn(async move { batcher.call(vec![i, i + 100]).await })
+    });
+    for (i, call) in futures::future::join_all(calls)
+        .await
+        .into_iter()
+        .enumerate()
+    {
+        let i = i as u32;
+        assert_eq!(call.unwrap().unwrap(), vec![i * 10, (i + 100) * 10]);
+    }
+
+    // 16 inputs fill one batch; they need not all land in it, but most should.
+    let batches = batches.lock().unwrap().clone();
+    assert_eq!(batches.iter().sum::<usize>(), 16);
+    assert!(batches.len() < 8, "no batching happened: {:?}", batches);
+    assert!(batcher.call(Vec::new()).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+#[cfg(any(feature = "semantic", feature = "ner"))]
+async fn test_micro_batcher_reports_errors_to_every_caller() {
+    let batcher = Arc::new(MicroBatcher::new(
+        Arc::new(InferencePool::new(1)),
+        4,
+        Duration::from_millis(20),
+        |_: Vec<u32>| -> udo::Result<Vec<u32>> {
+            Err(udo::UdoError::AiModel("out of memory".to_string()))
+        },
+    ));
+    let (a, b) = tokio::join!(batcher.call(vec![1]), batcher.call(vec![2]));
+    for result in [a, b] {
+        let err = result.unwrap_err().to_string();
+        assert_eq!(err, "AI Model Error: out of memory");
+    }
+}
@@ -0,0 +1,303 @@
+#[cfg(feature = "ner")]
+use udo::processors::ner::NerAnalyzer;
+
+#[cfg(feature = "ner")]
+fn analyzer() -> NerAnalyzer {
+    let model_path = if std::path::Path::new("models/ner").exists() {
+        Some(std::path::PathBuf::from("models/ner"))
+    } else {
+        None
+    };
+
+    // This test might fail if internet is not available and no local model is found
+    NerAnalyzer::new(model_path).expect("Failed to load model")
+}
+
+#[test]
+#[cfg(feature = "ner")]
+fn test_ner_batch_aggregates_entities() {
+    let analyzer = analyzer().with_batch_size(2);
+    let texts = [
+        "Angela Merkel visited Paris.",
+        "Nothing to see here.",
+        "Ünal Yılmaz works at Siemens.",
+    ];
+    let batch = analyzer.predict_batch(&texts).expect("Failed to predict");
+    assert_eq!(batch.len(), 3);
+
+    let people: Vec<&str> = batch[0]
+        .iter()
+        .filter(|e| e.label == "PER")
+        .map(|e| &texts[0][e.start..e.end])
+        .collect();
+    assert_eq!(people, vec!["Angela Merkel"]);
+    assert!(batch[0].iter().any(|e| e.label == "LOC"));
+    assert!(batch[1].is_empty());
+
+    // Batched results match single-text inference, and scores are probabilities.
+    for (text, entities) in texts.iter().zip(&batch) {
+        let single = analyzer.predict(text).expect("Failed to predict");
+        assert_eq!(single.len(), entities.len());
+        for (a, b) in single.iter().zip(entities) {
+            assert_eq!((a.start, a.end, &a.label), (b.start, b.end, &b.label));
+            assert!(a.score > 0.0 && a.score <= 1.0);
+        }
+    }
+
+    // Byte offsets convert to character offsets for non-ASCII names.
+    let person = batch[2].iter().find(|e| e.label == "PER").unwrap();
+    assert_eq!(person.char_range(texts[2]), 0..11);
+}
+
+#[test]
+#[cfg(feature = "ner")]
+fn test_ner_long_text_uses_sliding_windows() {
+    let analyzer = analyzer()
+        .with_window(64, 16)
+        .expect("Failed to configure window");
+    let filler = "The weather was mild and the meeting ran long. ".repeat(20);
+    let text = format!("{}Barack Obama arrived late.", filler);
+
+    let entities = analyzer.predict(&text).expect("Failed to predict");
+    let person = entities
+        .iter()
+        .find(|e| e.label == "PER")
+        .expect("Entity past the first window was missed");
+    assert_eq!(&text[person.start..person.end], "Barack Obama");
+    assert!(analyzer.with_window(8, 16).is_err());
+}
+
+/// Writes a tiny randomly initialised token-classification model whose classifier
+/// always predicts `favored`, so entity aggregation can be checked without a download.
+#[cfg(feature = "ner")]
+fn tiny_model(dir: &std::path::Path, model_type: &str, labels: &[&str], favored: usize) {
+    use candle_core::{DType, Device, Tensor};
+    use candle_nn::{VarBuilder, VarMap};
+    use candle_transformers::models::{bert, distilbert};
+    use std::collections::HashMap;
+    use tokenizers::models::wordlevel::WordLevel;
+    use tokenizers::pre_tokenizers::bert::BertPreTokenizer;
+    use tokenizers::processors::bert::BertProcessing;
+    use tokenizers::Tokenizer;
+
+    let words = [
+        "[PAD]", "[UNK]", "[CLS]", "[SEP]", "alice", "met", "bob", "in", "paris", "the", "weather",
+        "was", "mild", ".",
+    ];
+    let vocab: HashMap<String, u32> = words
+        .iter()
+        .enumerate()
+        .map(|(id, w)| (w.to_string(), id as u32))
+        .collect();
+    let mut tokenizer = Tokenizer::new(
+        WordLevel::builder()
+            .vocab(vocab)
+            .unk_token("[UNK]".to_string())
+            .build()
+            .unwrap(),
+    );
+    tokenizer.with_pre_tokenizer(BertPreTokenizer);
+    tokenizer.with_post_processor(BertProcessing::new(
+        ("[SEP]".to_string(), 3),
+        ("[CLS]".to_string(), 2),
+    ));
+    tokenizer.save(dir.join("tokenizer.json"), false).unwrap();
+
+    let id2label: HashMap<String, &str> = labels
+        .iter()
+        .enumerate()
+        .map(|(id, l)| (id.to_string(), *l))
+        .collect();
+    let config = if model_type == "distilbert" {
+        serde_json::json!({
+            "model_type": "distilbert", "vocab_size": words.len(), "dim": 8, "n_layers": 1,
+            "n_heads": 2, "hidden_dim": 16, "activation": "gelu",
+            "max_position_embeddings": 64, "initializer_range": 0.02, "pad_token_id": 0,
+            "id2label": id2label
+        })
+    } else {
+        serde_json::json!({
+            "model_type": "bert", "vocab_size": words.len(), "hidden_size": 8,
+            "num_hidden_layers": 1, "num_attention_heads": 2, "intermediate_size": 16,
+            "hidden_act": "gelu", "hidden_dropout_prob": 0.0, "max_position_embeddings": 64,
+            "type_vocab_size": 2, "initializer_range": 0.02, "layer_norm_eps": 1e-12,
+            "pad_token_id": 0, "id2label": id2label
+        })
+    };
+    std::fs::write(dir.join("config.json"), config.to_string()).unwrap();
+
+    let device = Device::Cpu;
+    let mut varmap = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F32, &device);
+    if model_type == "distilbert" {
+        let config: distilbert::Config = serde_json::from_value(config).unwrap();
+        distilbert::DistilBertModel::load(vb.pp("distilbert"), &config).unwrap();
+    } else {
+        let config: bert::Config = serde_json::from_value(config).unwrap();
+        bert::BertModel::load(vb.pp("bert"), &config).unwrap();
+    }
+    candle_nn::linear(8, labels.len(), vb.pp("classifier")).unwrap();
+    let mut bias = vec![0f32; labels.len()];
+    bias[favored] = 10.0;
+    varmap
+        .set_one(
+            "classifier.weight",
+            Tensor::zeros((labels.len(), 8), DType::F32, &device).unwrap(),
+        )
+        .unwrap();
+    varmap
+        .set_one("classifier.bias", Tensor::new(bias, &device).unwrap())
+        .unwrap();
+    varmap.save(dir.join("model.safetensors")).unwrap();
+}
+
+#[test]
+#[cfg(feature = "ner")]
+fn test_ner_labels_come_from_model_config() {
+    for model_type in ["bert", "distilbert"] {
+        let dir = tempfile::tempdir().unwrap();
+        tiny_model(dir.path(), model_type, &["O", "B-NAME", "I-NAME"], 2);
+        let analyzer = NerAnalyzer::load("unused", None, Some(dir.path().to_path_buf()))
+            .unwrap()
+            .with_batch_size(2);
+        assert_eq!(analyzer.labels(), ["O", "B-NAME", "I-NAME"]);
+
+        // Every token is I-NAME, so adjacent words merge into one entity, including
+        // across the windows of a long text.
+        let long = "the weather was mild . ".repeat(30);
+        let texts = ["alice met bob", "", long.trim_end()];
+        let batch = analyzer.predict_batch(&texts).unwrap();
+        assert_eq!(batch.len(), 3);
+        assert_eq!(batch[0].len(), 1);
+        assert_eq!((batch[0][0].start, batch[0][0].end), (0, 13));
+        assert_eq!(batch[0][0].label, "NAME");
+        assert!(batch[0][0].score > 0.99);
+        assert!(batch[1].is_empty());
+        assert_eq!(batch[2].len(), 1);
+        assert_eq!(batch[2][0].end, texts[2].len());
+    }
+}
+
+#[test]
+#[cfg(feature = "ner")]
+fn test_ner_window_is_capped_by_the_model() {
+    let dir = tempfile::tempdir().unwrap();
+    tiny_model(dir.path(), "bert", &["O", "B-PER", "I-PER"], 1);
+    let analyzer = NerAnalyzer::load("unused", None, Some(dir.path().to_path_buf())).unwrap();
+    assert_eq!(analyzer.max_tokens(), 64);
+
+    let analyzer = analyzer.with_window(64, 16).unwrap();
+    assert!(analyzer.with_window(512, 128).is_err());
+}
+
+#[test]
+#[cfg(feature = "ner")]
+fn test_ner_rejects_mismatched_label_map() {
+    let dir = tempfile::tempdir().unwrap();
+    tiny_model(dir.path(), "bert", &["O", "B-PER", "I-PER"], 1);
+    let config = std::fs::read_to_string(dir.path().join("config.json")).unwrap();
+    let mut config: serde_json::Value = serde_json::from_str(&config).unwrap();
+
+    config["id2label"] = serde_json::json!({"0": "O", "1": "B-PER"});
+    std::fs::write(dir.path().join("config.json"), config.to_string()).unwrap();
+    assert!(NerAnalyzer::load("unused", None, Some(dir.path().to_path_buf())).is_err());
+
+    config["id2label"] = serde_json::json!({"0": "O", "1": "B-PER", "5": "I-PER"});
+    std::fs::write(dir.path().join("config.json"), config.to_string()).unwrap();
+    assert!(NerAnalyzer::load("unused", None, Some(dir.path().to_path_buf())).is_err());
+
+    config["model_type"] = serde_json::json!("gpt2");
+    config["id2label"] = serde_json::json!({"0": "O", "1": "B-PER", "2": "I-PER"});
+    std::fs::write(dir.path().join("config.json"), config.to_string()).unwrap();
+    assert!(NerAnalyzer::load("unused", None, Some(dir.path().to_path_buf())).is_err());
+}
+
+#[tokio::test]
+#[cfg(feature = "ner")]
+async fn test_ner_masker_applies_entity_rules() {
+    use simd_json::{json, prelude::*};
+    use std::collections::HashMap;
+    use udo::core::config::{EntityRule, PiiAction};
+    use udo::core::pipeline::DataProcessor;
+    use udo::processors::pii::entities::EntityRules;
+    use udo::processors::pii::tokenize::Pseudonymizer;
+    use udo::processors::pii::NerPiiMasker;
+
+    let dir = tempfile::tempdir().unwrap();
+    tiny_model(dir.path(), "bert", &["O", "B-PER", "I-PER"], 2);
+    let load = || NerAnalyzer::load("unused", None, Some(dir.path().to_path_buf())).unwrap();
+    let rule = |min_score, action| {
+        EntityRules::new(
+            HashMap::from([(
+                "PER".to_string(),
+                EntityRule {
+                    min_score: Some(min_score),
+                    action: Some(action),
+                },
+            )]),
+            0.0,
+        )
+        .unwrap()
+    };
+
+    let key = std::sync::Arc::new(Pseudonymizer::new(b"0123456789abcdef-key"));
+    let masker = NerPiiMasker::new(load(), "mask")
+        .with_entities(rule(0.5, PiiAction::Hash))
+        .with_pseudonymizer(key);
+    let out = masker
+        .process(json!({"note": "alice met bob"}))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(out.get("note").unwrap().as_str().unwrap().len(), 64);
+    assert!(masker.report().is_none());
+
+    // Below the type's threshold nothing happens.
+    let masker = NerPiiMasker::new(load(), "mask").with_entities(rule(1.5, PiiAction::Hash));
+    let out = masker
+        .process(json!({"note": "alice met bob"}))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(out.get("note").unwrap().as_str(), Some("alice met bob"));
+
+    // Tag-only types are reported, not rewritten.
+    let masker = NerPiiMasker::new(load(), "mask").with_entities(rule(0.0, PiiAction::DetectOnly));
+    let out = masker
+        .process(json!({"note": "alice met bob"}))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(out.get("note").unwrap().as_str(), Some("alice met bob"));
+    assert_eq!(masker.metrics(), vec![("detected.PER".to_string(), 1)]);
+    assert!(masker.report().is_some());
+}
+
+#[test]
+#[cfg(feature = "ner")]
+fn test_ner_loads_bert_in_reduced_precision() {
+    use udo::core::config::Precision;
+    use udo::core::model::ModelSpec;
+
+    let dir = tempfile::tempdir().unwrap();
+    tiny_model(dir.path(), "bert", &["O", "B-PER", "I-PER"], 2);
+    for precision in [Precision::F16, Precision::Bf16, Precision::Q8_0] {
+        let spec = ModelSpec::new("unused")
+            .with_path(Some(dir.path().to_path_buf()))
+            .with_precision(precision);
+        let analyzer = NerAnalyzer::from_spec(&spec).unwrap();
+        assert_eq!(analyzer.load_report().precision, precision);
+        let batch = analyzer.predict_batch(&["alice met bob"]).unwrap();
+        assert_eq!(batch[0].len(), 1);
+        assert_eq!(batch[0][0].label, "PER");
+    }
+
+    // Other encoders only run at F32.
+    let dir = tempfile::tempdir().unwrap();
+    tiny_model(dir.path(), "distilbert", &["O", "B-PER", "I-PER"], 2);
+    let spec = ModelSpec::new("unused")
+        .with_path(Some(dir.path().to_path_buf()))
+        .with_precision(Precision::F16);
+    let err = NerAnalyzer::from_spec(&spec).err().unwrap().to_string();
+    assert!(err.contains("f32"), "{}", err);
+}
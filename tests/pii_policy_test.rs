@@ -0,0 +1,268 @@
+use simd_json::{json, prelude::*, OwnedValue};
+use std::collections::HashMap;
+use std::sync::Arc;
+use udo::core::config::{EntityRule, FieldRule, PiiAction, PiiPolicy};
+use udo::core::pipeline::DataProcessor;
+use udo::processors::pii::entities::EntityRules;
+use udo::processors::pii::policy::FieldPolicy;
+use udo::processors::pii::tokenize::Pseudonymizer;
+use udo::processors::pii::PiiMasker;
+use udo::utils::path::{FieldSelector, PathSegment};
+
+fn path(segments: &[&str]) -> Vec<PathSegment> {
+    segments
+        .iter()
+        .map(|s| match s.parse::<usize>() {
+            Ok(idx) => PathSegment::Index(idx),
+            Err(_) => PathSegment::Key(s.to_string()),
+        })
+        .collect()
+}
+
+#[test]
+fn test_field_selector_matching() {
+    let exact = FieldSelector::parse("$.user.email").unwrap();
+    assert!(exact.matches(&path(&["user", "email"])));
+    assert!(!exact.matches(&path(&["user", "email", "0"])));
+    assert!(FieldSelector::parse("user.email")
+        .unwrap()
+        .matches(&path(&["user", "email"])));
+
+    let wildcard = FieldSelector::parse("$.items[*].note").unwrap();
+    assert!(wildcard.matches(&path(&["items", "3", "note"])));
+    assert!(!wildcard.matches(&path(&["items", "x", "note"])));
+
+    let descend = FieldSelector::parse("$..ssn").unwrap();
+    assert!(descend.matches(&path(&["ssn"])));
+    assert!(descend.matches(&path(&["a", "0", "b", "ssn"])));
+
+    let quoted = FieldSelector::parse(r#"$["odd key"][2]"#).unwrap();
+    assert!(quoted.matches(&path(&["odd key", "2"])));
+
+    assert!(FieldSelector::parse("$.a[").is_err());
+    assert!(FieldSelector::parse("$.a..").is_err());
+}
+
+fn policy(default: Option<PiiAction>, fields: &[(&str, PiiAction)]) -> PiiPolicy {
+    PiiPolicy {
+        default,
+        fields: fields
+            .iter()
+            .map(|(path, action)| FieldRule {
+                path: path.to_string(),
+                action: *action,
+            })
+            .collect(),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_policy_actions_per_field() {
+    let policy = policy(
+        None,
+        &[
+            ("$.user", PiiAction::Hash),
+            ("$.user.ssn", PiiAction::Drop),
+            ("$.product_description", PiiAction::Allow),
+            ("$.audit", PiiAction::DetectOnly),
+        ],
+    );
+    let masker = PiiMasker::new("mask")
+        .unwrap()
+        .with_policy(FieldPolicy::compile(&policy, "mask").unwrap())
+        .with_pseudonymizer(Arc::new(Pseudonymizer::new(b"0123456789abcdef-key")));
+
+    let record: OwnedValue = json!({
+        "user": {"email": "jane@corp.io", "ssn": "123-45-6789"},
+        "contact": "jane@corp.io",
+        "product_description": "Ships to sales@vendor.com",
+        "audit": "seen 10.0.0.1"
+    });
+    let processed = masker.process(record).await.unwrap().unwrap();
+
+    let user = processed.get("user").unwrap();
+    assert!(user.get("ssn").is_none());
+    assert_eq!(user.get("email").unwrap().as_str().unwrap().len(), 64);
+    assert_eq!(
+        processed.get("contact").unwrap().as_str(),
+        Some("****@masked.com")
+    );
+    assert_eq!(
+        processed.get("product_description").unwrap().as_str(),
+        Some("Ships to sales@vendor.com")
+    );
+    assert_eq!(
+        processed.get("audit").unwrap().as_str(),
+        Some("seen 10.0.0.1")
+    );
+    assert_eq!(masker.metrics(), vec![("detected.ipv4".to_string(), 1)]);
+}
+
+#[tokio::test]
+async fn test_tag_action_lists_entities_without_masking() {
+    let policy = policy(None, &[("$.notes", PiiAction::Tag)]);
+    let masker = PiiMasker::new("mask")
+        .unwrap()
+        .with_policy(FieldPolicy::compile(&policy, "mask").unwrap());
+
+    let record: OwnedValue = json!({
+        "contact": "jane@corp.io",
+        "notes": "call from 10.0.0.1, mail jane@corp.io"
+    });
+    let processed = masker.process(record).await.unwrap().unwrap();
+
+    assert_eq!(
+        processed.get("contact").unwrap().as_str(),
+        Some("****@masked.com")
+    );
+    let notes = processed.get("notes").unwrap().as_str().unwrap();
+    assert_eq!(notes, "call from 10.0.0.1, mail jane@corp.io");
+
+    let entities = processed.get("_entities").unwrap().as_array().unwrap();
+    assert_eq!(entities.len(), 2);
+    for entity in entities {
+        assert_eq!(entity.get("path").unwrap().as_str(), Some("$.notes"));
+        let start = entity.get("start").unwrap().as_usize().unwrap();
+        let end = entity.get("end").unwrap().as_usize().unwrap();
+        let expected = match entity.get("label").unwrap().as_str().unwrap() {
+            "ipv4" => "10.0.0.1",
+            "email" => "jane@corp.io",
+            other => panic!("unexpected label {other}"),
+        };
+        assert_eq!(&notes[start..end], expected);
+    }
+
+    // A second tagging masker appends to the same array.
+    let tagger = PiiMasker::new("tag").unwrap();
+    let tagged = tagger.process(processed).await.unwrap().unwrap();
+    let entities = tagged.get("_entities").unwrap().as_array().unwrap();
+    assert_eq!(entities.len(), 4);
+}
+
+#[tokio::test]
+async fn test_policy_include_exclude_and_default() {
+    let mut policy = policy(Some(PiiAction::Allow), &[("$..phone", PiiAction::Mask)]);
+    policy.include = vec!["$.tickets[*].body".to_string()];
+    policy.exclude = vec!["$.tickets[1]".to_string()];
+    let compiled = FieldPolicy::compile(&policy, "mask").unwrap();
+    assert_eq!(compiled.default_action(), PiiAction::Allow);
+
+    // With an `allow` default only explicit rules act.
+    let masker = PiiMasker::new("mask").unwrap().with_policy(compiled);
+    let record: OwnedValue = json!({
+        "tickets": [
+            {"body": "mail a@x.com", "phone": "call 555-123-4567"},
+            {"body": "mail b@x.com", "phone": "call 555-123-4567"}
+        ],
+        "email": "c@x.com"
+    });
+    let processed = masker.process(record).await.unwrap().unwrap();
+    let tickets = processed.get("tickets").unwrap().as_array().unwrap();
+    assert_eq!(
+        tickets[0].get("body").unwrap().as_str(),
+        Some("mail a@x.com")
+    );
+    assert_eq!(
+        tickets[0].get("phone").unwrap().as_str(),
+        Some("call [REDACTED]")
+    );
+    assert_eq!(
+        tickets[1].get("phone").unwrap().as_str(),
+        Some("call 555-123-4567")
+    );
+    assert_eq!(processed.get("email").unwrap().as_str(), Some("c@x.com"));
+
+    // Mask by default, but only inside the included paths.
+    let mut policy = policy.clone();
+    policy.default = None;
+    policy.fields.clear();
+    let masker = PiiMasker::new("mask")
+        .unwrap()
+        .with_policy(FieldPolicy::compile(&policy, "mask").unwrap());
+    let record: OwnedValue = json!({
+        "tickets": [{"body": "mail a@x.com"}, {"body": "mail b@x.com"}],
+        "email": "c@x.com"
+    });
+    let processed = masker.process(record).await.unwrap().unwrap();
+    let tickets = processed.get("tickets").unwrap().as_array().unwrap();
+    assert_eq!(
+        tickets[0].get("body").unwrap().as_str(),
+        Some("mail ****@masked.com")
+    );
+    assert_eq!(
+        tickets[1].get("body").unwrap().as_str(),
+        Some("mail b@x.com")
+    );
+    assert_eq!(processed.get("email").unwrap().as_str(), Some("c@x.com"));
+}
+
+#[test]
+fn test_policy_rejects_drop_default_and_bad_paths() {
+    assert!(FieldPolicy::compile(&policy(Some(PiiAction::Drop), &[]), "mask").is_err());
+    assert!(FieldPolicy::compile(&policy(None, &[("$.a[x]", PiiAction::Mask)]), "mask").is_err());
+}
+
+#[test]
+fn test_entity_rules_select_types_thresholds_and_actions() {
+    // No rules: every type, at the field's action.
+    let all = EntityRules::new(HashMap::new(), 0.5).unwrap();
+    assert_eq!(
+        all.action("MISC", 0.9, PiiAction::Mask),
+        Some(PiiAction::Mask)
+    );
+    assert_eq!(all.action("MISC", 0.4, PiiAction::Mask), None);
+
+    let rules = HashMap::from([
+        (
+            "PER".to_string(),
+            EntityRule {
+                min_score: Some(0.8),
+                action: Some(PiiAction::Hash),
+            },
+        ),
+        (
+            "ORG".to_string(),
+            EntityRule {
+                min_score: None,
+                action: Some(PiiAction::DetectOnly),
+            },
+        ),
+        ("LOC".to_string(), EntityRule::default()),
+    ]);
+    let rules = EntityRules::new(rules, 0.3).unwrap();
+    assert!(rules.uses(PiiAction::DetectOnly));
+    assert_eq!(rules.action("MISC", 0.99, PiiAction::Mask), None);
+    assert_eq!(
+        rules.action("PER", 0.9, PiiAction::Mask),
+        Some(PiiAction::Hash)
+    );
+    assert_eq!(rules.action("PER", 0.7, PiiAction::Mask), None);
+    assert_eq!(
+        rules.action("ORG", 0.4, PiiAction::Tokenize),
+        Some(PiiAction::DetectOnly)
+    );
+    assert_eq!(rules.action("ORG", 0.2, PiiAction::Tokenize), None);
+    assert_eq!(
+        rules.action("LOC", 0.5, PiiAction::Tokenize),
+        Some(PiiAction::Tokenize)
+    );
+    // Audit and tagged fields stay untouched whatever the type's action.
+    assert_eq!(
+        rules.action("PER", 0.9, PiiAction::DetectOnly),
+        Some(PiiAction::DetectOnly)
+    );
+    assert_eq!(
+        rules.action("PER", 0.9, PiiAction::Tag),
+        Some(PiiAction::Tag)
+    );
+
+    let drop = HashMap::from([(
+        "PER".to_string(),
+        EntityRule {
+            min_score: None,
+            action: Some(PiiAction::Drop),
+        },
+    )]);
+    assert!(EntityRules::new(drop, 0.0).is_err());
+}
@@ -0,0 +1,136 @@
+use simd_json::{json, prelude::*, OwnedValue};
+use std::io::Write;
+use tempfile::NamedTempFile;
+use udo::core::pipeline::DataProcessor;
+use udo::io::source::FileSource;
+use udo::processors::pii::redact::Span;
+use udo::processors::pii::report::{Detection, DetectionReport};
+use udo::processors::pii::PiiMasker;
+use udo::utils::path::PathSegment;
+use udo::PipelineRunner;
+
+#[tokio::test]
+async fn test_detect_only_leaves_records_untouched() {
+    let masker = PiiMasker::new("detect_only").unwrap();
+    let record: OwnedValue = json!({
+        "tickets": [
+            {"body": "Reach me at jane@corp.io or 10.0.0.1, thanks"},
+            {"body": "cc bob@corp.io"}
+        ],
+        "note": "nothing here"
+    });
+    let processed = masker.process(record.clone()).await.unwrap().unwrap();
+    assert_eq!(processed, record);
+    assert_eq!(
+        masker.metrics(),
+        vec![
+            ("detected.email".to_string(), 2),
+            ("detected.ipv4".to_string(), 1)
+        ]
+    );
+
+    let report = masker.report().unwrap();
+    let findings = report["findings"].as_array().unwrap();
+    assert_eq!(findings.len(), 2);
+    let email = findings.iter().find(|f| f["pii_type"] == "email").unwrap();
+    assert_eq!(email["path"], "$.tickets[*].body");
+    assert_eq!(email["count"], 2);
+    assert!(email["mean_confidence"].as_f64().unwrap() > 0.0);
+    let samples = email["samples"].as_array().unwrap();
+    assert_eq!(samples.len(), 2);
+    for sample in samples {
+        let sample = sample.as_str().unwrap();
+        assert!(sample.contains("<email>"));
+        assert!(!sample.contains("@corp.io"));
+        assert!(!sample.contains("10.0.0.1"));
+    }
+}
+
+#[test]
+fn test_report_samples_hide_adjacent_entities() {
+    // "Barack Obama" is reported; the adjacent "Michelle" is masked and "Chicago" fell
+    // below its threshold, so neither is reported but both must stay out of samples.
+    let text = "Barack Obama Michelle met in Chicago";
+    let report = DetectionReport::default();
+    let path = [PathSegment::Key("body".to_string())];
+    let detections = [Detection {
+        label: "PER",
+        start: 0,
+        end: 12,
+        confidence: 0.9,
+    }];
+    let other = [
+        Span {
+            label: "PER",
+            start: 13,
+            end: 21,
+        },
+        Span {
+            label: "LOC",
+            start: 29,
+            end: 36,
+        },
+    ];
+    report.record(&path, text, &detections, &other);
+
+    let findings = report.findings();
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].count, 1);
+    assert_eq!(findings[0].samples, vec!["<PER> <PER> met in <LOC>"]);
+
+    // Two adjacent reported entities share one sample text without leaking either.
+    let report = DetectionReport::default();
+    let detections = [
+        Detection {
+            label: "PER",
+            start: 0,
+            end: 12,
+            confidence: 0.9,
+        },
+        Detection {
+            label: "PER",
+            start: 13,
+            end: 21,
+            confidence: 0.8,
+        },
+    ];
+    report.record(&path, text, &detections, &[]);
+    let findings = report.findings();
+    assert_eq!(findings[0].count, 2);
+    for sample in &findings[0].samples {
+        assert!(!sample.contains("Obama"));
+        assert!(!sample.contains("Michelle"));
+    }
+}
+
+#[tokio::test]
+async fn test_masking_modes_have_no_report() {
+    let masker = PiiMasker::new("mask").unwrap();
+    masker
+        .process(json!({"email": "jane@corp.io"}))
+        .await
+        .unwrap();
+    assert!(masker.report().is_none());
+}
+
+#[tokio::test]
+async fn test_runner_collects_processor_reports() {
+    let mut input = NamedTempFile::new().unwrap();
+    writeln!(input, r#"{{"contact": "jane@corp.io"}}"#).unwrap();
+    writeln!(input, r#"{{"contact": "call 555-123-4567"}}"#).unwrap();
+    let source = FileSource::new(input.path().to_path_buf()).await.unwrap();
+
+    let mut runner = PipelineRunner::new(Box::new(source), 10);
+    runner.add_processor(Box::new(PiiMasker::new("detect_only").unwrap()));
+    runner.run(None).await.unwrap();
+
+    let reports = runner.processor_reports();
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].stage, 0);
+    let findings = reports[0].report["findings"].as_array().unwrap();
+    let types: Vec<&str> = findings
+        .iter()
+        .map(|f| f["pii_type"].as_str().unwrap())
+        .collect();
+    assert_eq!(types, vec!["email", "phone"]);
+}
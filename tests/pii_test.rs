@@ -1,5 +1,11 @@
 use simd_json::{prelude::*, OwnedValue};
+use std::collections::HashMap;
+use std::sync::Arc;
+use udo::core::config::PiiAction;
 use udo::core::pipeline::DataProcessor;
+use udo::processors::pii::detectors::{luhn_valid, DetectorRegistry};
+use udo::processors::pii::redact::{RedactionTemplates, Redactor, Span};
+use udo::processors::pii::tokenize::{Pseudonymizer, TokenVault};
 use udo::processors::pii::PiiMasker;
 
 #[tokio::test]
@@ -21,7 +27,10 @@ async fn test_pii_masking() {
 
 #[tokio::test]
 async fn test_pii_hashing() {
-    let masker = PiiMasker::new("hash").expect("Failed to create PiiMasker");
+    let pseudonymizer = Arc::new(Pseudonymizer::new(b"0123456789abcdef-key"));
+    let masker = PiiMasker::new("hash")
+        .expect("Failed to create PiiMasker")
+        .with_pseudonymizer(pseudonymizer.clone());
 
     let mut record = OwnedValue::object();
     record.insert("email", "test@example.com").unwrap();
@@ -31,4 +40,276 @@ async fn test_pii_hashing() {
     let hashed = processed.get("email").unwrap().as_str().unwrap();
     assert_ne!(hashed, "test@example.com");
     assert_eq!(hashed.len(), 64); // SHA-256 hex length
+    assert_eq!(hashed, pseudonymizer.hash("test@example.com"));
+}
+
+#[tokio::test]
+async fn test_hash_and_tokenize_require_a_key() {
+    for mode in ["hash", "tokenize"] {
+        let masker = PiiMasker::new(mode).unwrap();
+        let mut record = OwnedValue::object();
+        record.insert("email", "test@example.com").unwrap();
+        let err = masker.process(record).await.unwrap_err();
+        assert!(err.to_string().contains("requires a key"), "{}", err);
+    }
+}
+
+#[test]
+fn test_credit_card_requires_luhn() {
+    let registry = DetectorRegistry::builtin().unwrap();
+    let hits = registry.find("card 4111 1111 1111 1111 on file");
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].detector, "credit_card");
+    assert!(!registry.is_match("order 4111 1111 1111 1112"));
+}
+
+#[test]
+fn test_builtin_detectors_validate_matches() {
+    let registry = DetectorRegistry::builtin().unwrap();
+    let detected = |text: &str| registry.find(text).first().map(|m| m.detector);
+
+    assert_eq!(detected("GB82 WEST 1234 5698 7654 32"), Some("iban"));
+    assert_eq!(detected("ssn 123-45-6789"), Some("us_ssn"));
+    assert_eq!(detected("ssn 000-45-6789"), None);
+    assert_eq!(detected("call +1 (415) 555-2671"), Some("phone"));
+    assert_eq!(detected("host 192.168.10.20"), Some("ipv4"));
+    assert_eq!(detected("version 999.1.2.3"), None);
+    assert_eq!(detected("nic 00:1A:2B:3C:4D:5E"), Some("mac_address"));
+}
+
+#[test]
+fn test_ipv6_requires_address_boundaries() {
+    let registry = DetectorRegistry::builtin().unwrap();
+    let spans = |text: &str| -> Vec<String> {
+        registry
+            .find(text)
+            .into_iter()
+            .filter(|m| m.detector == "ipv6")
+            .map(|m| text[m.start..m.end].to_string())
+            .collect()
+    };
+
+    assert_eq!(
+        spans("peer 2001:db8::ff00:42:8329 up"),
+        vec!["2001:db8::ff00:42:8329"]
+    );
+    assert_eq!(
+        spans("fe80::1ff:fe23:4567:890a"),
+        vec!["fe80::1ff:fe23:4567:890a"]
+    );
+    assert_eq!(
+        spans("(2001:db8::) and fe80::1"),
+        vec!["2001:db8::", "fe80::1"]
+    );
+
+    for code in [
+        "use std::vec;",
+        "call Class::add(x)",
+        "Vec<u8>::new()",
+        "Note:: read this",
+        "bind :: then d:: and ::1",
+        "dead:beef:cafe:1:2:3:4:5abcd",
+        "a::bcdefg",
+    ] {
+        assert!(spans(code).is_empty(), "{} detected as IPv6", code);
+    }
+}
+
+#[test]
+fn test_registry_selection() {
+    let enabled = vec!["email".to_string(), "ipv4".to_string()];
+    let registry = DetectorRegistry::select(Some(&enabled), &["ipv4".to_string()]).unwrap();
+    assert_eq!(registry.ids(), vec!["email"]);
+    assert!(!registry.is_match("123-45-6789"));
+
+    let err = DetectorRegistry::select(Some(&["tax_id".to_string()]), &[]);
+    assert!(err.is_err());
+}
+
+#[tokio::test]
+async fn test_pii_masker_redacts_non_email_detectors() {
+    let masker = PiiMasker::new("mask").unwrap();
+
+    let mut record = OwnedValue::object();
+    record.insert("ip", "10.0.0.1").unwrap();
+    let processed = masker.process(record).await.unwrap().unwrap();
+    assert_eq!(processed.get("ip").unwrap().as_str(), Some("[REDACTED]"));
+}
+
+#[tokio::test]
+async fn test_pii_masker_redacts_only_matched_span() {
+    let masker = PiiMasker::new("mask").unwrap();
+
+    let mut record = OwnedValue::object();
+    record
+        .insert("ticket", "Please reset the account for jane@corp.io asap")
+        .unwrap();
+    let processed = masker.process(record).await.unwrap().unwrap();
+    assert_eq!(
+        processed.get("ticket").unwrap().as_str(),
+        Some("Please reset the account for ****@masked.com asap")
+    );
+}
+
+#[tokio::test]
+async fn test_redaction_templates_number_distinct_values() {
+    let templates = RedactionTemplates::new(HashMap::from([
+        ("email".to_string(), "<EMAIL_{n}>".to_string()),
+        ("default".to_string(), "<{label}>".to_string()),
+    ]));
+    let masker = PiiMasker::new("mask").unwrap().with_templates(templates);
+
+    let mut record = OwnedValue::object();
+    record
+        .insert("from", "a@x.com wrote to b@x.com from 10.0.0.1")
+        .unwrap();
+    record.insert("cc", "a@x.com").unwrap();
+    let processed = masker.process(record).await.unwrap().unwrap();
+    assert_eq!(
+        processed.get("from").unwrap().as_str(),
+        Some("<EMAIL_1> wrote to <EMAIL_2> from <ipv4>")
+    );
+    assert_eq!(processed.get("cc").unwrap().as_str(), Some("<EMAIL_1>"));
+}
+
+#[test]
+fn test_redactor_skips_invalid_spans() {
+    let templates = RedactionTemplates::default();
+    let pseudonymizer = Pseudonymizer::new(b"0123456789abcdef-key");
+    let mut redactor = Redactor::new(&templates, Some(&pseudonymizer));
+    let text = "héllo world";
+    let spans = [
+        Span {
+            label: "x",
+            start: 2,
+            end: 4,
+        },
+        Span {
+            label: "x",
+            start: 7,
+            end: 12,
+        },
+    ];
+    assert_eq!(
+        redactor.redact(text, &spans, PiiAction::Mask).unwrap(),
+        "héllo [REDACTED]"
+    );
+}
+
+#[test]
+fn test_redactor_applies_action_per_span() {
+    let templates =
+        RedactionTemplates::new(HashMap::from([("ORG".to_string(), "<ORG>".to_string())]));
+    let pseudonymizer = Pseudonymizer::new(b"0123456789abcdef-key");
+    let mut redactor = Redactor::new(&templates, Some(&pseudonymizer));
+    let text = "Ada joined Acme";
+    let span = |label, start, end| Span { label, start, end };
+    let out = redactor
+        .redact_each(
+            text,
+            &[
+                (span("ORG", 11, 15), PiiAction::Mask),
+                (span("PER", 0, 3), PiiAction::Hash),
+            ],
+        )
+        .unwrap();
+    assert_eq!(out, format!("{} joined <ORG>", pseudonymizer.hash("Ada")));
+}
+
+#[test]
+fn test_keyed_hash_depends_on_key() {
+    let a = Pseudonymizer::new(b"0123456789abcdef-key-a");
+    let b = Pseudonymizer::new(b"0123456789abcdef-key-b");
+    assert_eq!(a.hash("jane@corp.io"), a.hash("jane@corp.io"));
+    assert_ne!(a.hash("jane@corp.io"), b.hash("jane@corp.io"));
+    // Not the unsalted digest a dictionary attack would target.
+    use sha2::{Digest, Sha256};
+    let plain = format!("{:x}", Sha256::digest(b"jane@corp.io"));
+    assert_ne!(a.hash("jane@corp.io"), plain);
+}
+
+#[test]
+fn test_tokenize_preserves_format() {
+    let p = Pseudonymizer::new(b"0123456789abcdef-key");
+
+    let email = p.tokenize("email", "Jane.Doe@corp.io").unwrap();
+    assert_eq!(email, p.tokenize("email", "Jane.Doe@corp.io").unwrap());
+    assert_ne!(email, "Jane.Doe@corp.io");
+    assert!(email.ends_with(".io"));
+    assert_eq!(email.len(), "Jane.Doe@corp.io".len());
+    assert!(DetectorRegistry::builtin().unwrap().is_match(&email));
+
+    let card = p.tokenize("credit_card", "4111 1111 1111 1111").unwrap();
+    assert_ne!(card, "4111 1111 1111 1111");
+    assert!(card.ends_with(" 1111"));
+    assert_eq!(card.chars().filter(|c| c.is_ascii_digit()).count(), 16);
+    assert!(luhn_valid(&card));
+}
+
+#[tokio::test]
+async fn test_token_vault_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("vault.ndjson");
+    let key = b"0123456789abcdef-vault-key";
+
+    let p = Arc::new(Pseudonymizer::new(key).with_vault(TokenVault::open(&path, key).unwrap()));
+    let masker = PiiMasker::new("tokenize").unwrap().with_pseudonymizer(p);
+
+    let mut record = OwnedValue::object();
+    record.insert("note", "mail jane@corp.io today").unwrap();
+    let processed = masker.process(record).await.unwrap().unwrap();
+    let note = processed.get("note").unwrap().as_str().unwrap().to_string();
+    let token = note
+        .strip_prefix("mail ")
+        .and_then(|s| s.strip_suffix(" today"))
+        .unwrap();
+    assert_ne!(token, "jane@corp.io");
+
+    let vault = TokenVault::open(&path, key).unwrap();
+    assert_eq!(
+        vault.reveal(token).unwrap().as_deref(),
+        Some("jane@corp.io")
+    );
+    assert_eq!(vault.reveal("nobody@x.io").unwrap(), None);
+
+    let wrong = TokenVault::open(&path, b"another-key-of-16-bytes").unwrap();
+    assert!(wrong.reveal(token).is_err());
+}
+
+#[test]
+fn test_token_vault_redraws_colliding_tokens() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("vault.ndjson");
+    let key = b"0123456789abcdef-vault-key";
+
+    // Two-letter names have 676 possible tokens, so some pair of them shares one.
+    let plain = Pseudonymizer::new(key);
+    let mut seen: HashMap<String, String> = HashMap::new();
+    let (first, second) = ('a'..='z')
+        .flat_map(|a| ('a'..='z').map(move |b| format!("{}{}", a, b)))
+        .find_map(|name| {
+            let token = plain.tokenize("PER", &name).unwrap();
+            seen.insert(token, name.clone()).map(|other| (other, name))
+        })
+        .unwrap();
+    let shared = plain.tokenize("PER", &first).unwrap();
+    assert_eq!(shared, plain.tokenize("PER", &second).unwrap());
+
+    let p = Pseudonymizer::new(key).with_vault(TokenVault::open(&path, key).unwrap());
+    let first_token = p.tokenize("PER", &first).unwrap();
+    let second_token = p.tokenize("PER", &second).unwrap();
+    assert_eq!(first_token, shared);
+    assert_ne!(second_token, shared);
+    assert_eq!(second_token.len(), 2);
+    assert!(second_token.chars().all(|c| c.is_ascii_lowercase()));
+    assert_eq!(p.tokenize("PER", &second).unwrap(), second_token);
+
+    // A later run with the same vault picks the same tokens, in either order.
+    let again = Pseudonymizer::new(key).with_vault(TokenVault::open(&path, key).unwrap());
+    assert_eq!(again.tokenize("PER", &second).unwrap(), second_token);
+    assert_eq!(again.tokenize("PER", &first).unwrap(), first_token);
+
+    let vault = TokenVault::open(&path, key).unwrap();
+    assert_eq!(vault.reveal(&first_token).unwrap(), Some(first));
+    assert_eq!(vault.reveal(&second_token).unwrap(), Some(second));
 }
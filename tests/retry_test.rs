@@ -0,0 +1,120 @@
+use async_trait::async_trait;
+use simd_json::OwnedValue;
+use std::io::Write;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use tempfile::NamedTempFile;
+use udo::core::config::RetryPolicy;
+use udo::core::pipeline::{DataProcessor, DeadLetter, DlqSink};
+use udo::io::source::FileSource;
+use udo::{PipelineRunner, UdoError};
+
+/// Fails the first `failures` calls with the given error, then passes records through.
+struct FlakyProcessor {
+    calls: Arc<AtomicU32>,
+    failures: u32,
+    error: fn() -> UdoError,
+}
+
+#[async_trait]
+impl DataProcessor for FlakyProcessor {
+    async fn process(&self, record: OwnedValue) -> udo::Result<Option<OwnedValue>> {
+        if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+            Err((self.error)())
+        } else {
+            Ok(Some(record))
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+struct MemoryDlq {
+    letters: Arc<Mutex<Vec<DeadLetter>>>,
+}
+
+#[async_trait]
+impl DlqSink for MemoryDlq {
+    async fn write_dead_letter(&mut self, letter: DeadLetter) -> udo::Result<()> {
+        self.letters.lock().unwrap().push(letter);
+        Ok(())
+    }
+}
+
+fn fast_retry(max_attempts: u32) -> RetryPolicy {
+    RetryPolicy {
+        max_attempts,
+        initial_backoff_ms: 1,
+        max_backoff_ms: 5,
+        ..Default::default()
+    }
+}
+
+async fn run_single_record(processor: FlakyProcessor, retry: RetryPolicy) -> Vec<DeadLetter> {
+    let mut input = NamedTempFile::new().unwrap();
+    writeln!(input, r#"{{"id": 1}}"#).unwrap();
+    let source = FileSource::new(input.path().to_path_buf()).await.unwrap();
+
+    let dlq = MemoryDlq::default();
+    let mut runner = PipelineRunner::new(Box::new(source), 10);
+    runner.set_dlq(Box::new(dlq.clone()));
+    runner.add_processor_with_retry(Box::new(processor), retry);
+    runner.run(None).await.unwrap();
+
+    dlq.letters.lock().unwrap().clone()
+}
+
+#[tokio::test]
+async fn test_transient_failure_is_retried() {
+    let calls = Arc::new(AtomicU32::new(0));
+    let processor = FlakyProcessor {
+        calls: calls.clone(),
+        failures: 2,
+        error: || UdoError::AiModel("out of memory".to_string()),
+    };
+
+    let letters = run_single_record(processor, fast_retry(3)).await;
+    assert!(letters.is_empty());
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_exhausted_retries_record_attempts() {
+    let processor = FlakyProcessor {
+        calls: Arc::new(AtomicU32::new(0)),
+        failures: u32::MAX,
+        error: || UdoError::AiModel("out of memory".to_string()),
+    };
+
+    let letters = run_single_record(processor, fast_retry(3)).await;
+    assert_eq!(letters.len(), 1);
+    assert_eq!(letters[0].attempts, 3);
+    assert_eq!(letters[0].processor.as_deref(), Some("FlakyProcessor"));
+}
+
+#[tokio::test]
+async fn test_non_retryable_error_fails_immediately() {
+    let calls = Arc::new(AtomicU32::new(0));
+    let processor = FlakyProcessor {
+        calls: calls.clone(),
+        failures: u32::MAX,
+        error: || UdoError::Config("bad".to_string()),
+    };
+
+    let letters = run_single_record(processor, fast_retry(5)).await;
+    assert_eq!(letters.len(), 1);
+    assert_eq!(letters[0].attempts, 1);
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_backoff_grows_and_caps() {
+    let policy = RetryPolicy {
+        initial_backoff_ms: 100,
+        max_backoff_ms: 1000,
+        jitter: 0.0,
+        ..Default::default()
+    };
+    assert_eq!(policy.backoff(1).as_millis(), 100);
+    assert_eq!(policy.backoff(2).as_millis(), 200);
+    assert_eq!(policy.backoff(10).as_millis(), 1000);
+}
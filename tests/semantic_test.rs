@@ -43,3 +43,768 @@ fn test_semantic_embedding() {
 
     assert!(sim_1_2 > sim_1_3, "Semantic similarity logic failed");
 }
+
+/// Writes a tiny randomly initialised BERT encoder, so batching and caching can be
+/// checked without a download.
+#[cfg(feature = "semantic")]
+fn tiny_bert(dir: &std::path::Path) {
+    tiny_bert_sized(dir, 8);
+}
+
+#[cfg(feature = "semantic")]
+fn tiny_bert_sized(dir: &std::path::Path, hidden_size: usize) {
+    use candle_core::{DType, Device};
+    use candle_nn::{VarBuilder, VarMap};
+    use candle_transformers::models::bert;
+    use std::collections::HashMap;
+    use tokenizers::models::wordlevel::WordLevel;
+    use tokenizers::pre_tokenizers::bert::BertPreTokenizer;
+    use tokenizers::processors::bert::BertProcessing;
+    use tokenizers::Tokenizer;
+
+    let words = [
+        "[PAD]", "[UNK]", "[CLS]", "[SEP]", "user", "email", "order", "id", "total", "price",
+        "find",
+    ];
+    let vocab: HashMap<String, u32> = words
+        .iter()
+        .enumerate()
+        .map(|(id, w)| (w.to_string(), id as u32))
+        .collect();
+    let mut tokenizer = Tokenizer::new(
+        WordLevel::builder()
+            .vocab(vocab)
+            .unk_token("[UNK]".to_string())
+            .build()
+            .unwrap(),
+    );
+    tokenizer.with_pre_tokenizer(BertPreTokenizer);
+    tokenizer.with_post_processor(BertProcessing::new(
+        ("[SEP]".to_string(), 3),
+        ("[CLS]".to_string(), 2),
+    ));
+    tokenizer.save(dir.join("tokenizer.json"), false).unwrap();
+
+    let config = serde_json::json!({
+        "vocab_size": words.len(), "hidden_size": hidden_size, "num_hidden_layers": 1,
+        "num_attention_heads": 2, "intermediate_size": hidden_size * 2, "hidden_act": "gelu",
+        "hidden_dropout_prob": 0.0, "max_position_embeddings": 64, "type_vocab_size": 2,
+        "initializer_range": 0.02, "layer_norm_eps": 1e-12, "pad_token_id": 0
+    });
+    std::fs::write(dir.join("config.json"), config.to_string()).unwrap();
+    let varmap = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F32, &Device::Cpu);
+    let config: bert::Config = serde_json::from_value(config).unwrap();
+    bert::BertModel::load(vb, &config).unwrap();
+    varmap.save(dir.join("model.safetensors")).unwrap();
+}
+
+#[test]
+#[cfg(feature = "semantic")]
+fn test_batched_embeddings_match_single() {
+    let dir = tempfile::tempdir().unwrap();
+    tiny_bert(dir.path());
+    let analyzer = IntentAnalyzer::new(Some(dir.path().to_path_buf()))
+        .unwrap()
+        .with_batch_size(2);
+
+    let texts = ["user email", "order id total price", "find", "user"];
+    let batch = analyzer.embed(&texts).unwrap();
+    assert_eq!(batch.len(), texts.len());
+    for (text, embedding) in texts.iter().zip(&batch) {
+        let single = analyzer.get_embedding(text).unwrap();
+        let norm: f32 = embedding.iter().map(|v| v * v).sum::<f32>().sqrt();
+        assert!((norm - 1.0).abs() < 1e-4);
+        // Padding in a batch must not change a text's embedding.
+        assert!(cosine_similarity(&single, embedding) > 0.9999);
+    }
+
+    let ranked = analyzer
+        .rank_columns(
+            "find user",
+            &["order_id".to_string(), "user_email".to_string()],
+        )
+        .unwrap();
+    assert_eq!(ranked.len(), 2);
+    assert!(ranked[0].1 >= ranked[1].1);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[cfg(feature = "semantic")]
+async fn test_pooled_analyzers_share_weights_and_batch_async_embeds() {
+    use std::sync::Arc;
+    use udo::core::config::ModelsConfig;
+    use udo::core::model::{ModelPool, ModelSpec};
+
+    let dir = tempfile::tempdir().unwrap();
+    tiny_bert(dir.path());
+    let pool = ModelPool::new(&ModelsConfig {
+        threads: Some(2),
+        batch_wait_ms: Some(20),
+        ..Default::default()
+    });
+    let spec = ModelSpec::new("tiny").with_path(Some(dir.path().to_path_buf()));
+    let first = Arc::new(IntentAnalyzer::from_pool(&pool, &spec).unwrap());
+    let second = IntentAnalyzer::from_pool(&pool, &spec).unwrap();
+    assert_eq!(pool.len(), 1);
+
+    let texts = ["user email", "order id", "find", "total price"];
+    let calls = texts.iter().map(|text| {
+        let analyzer = first.clone();
+        let text = text.to_string();
+        tokio::spawn(async move { analyzer.embed_async(vec![text]).await })
+    });
+    let results = futures::future::join_all(calls).await;
+    for (text, result) in texts.iter().zip(results) {
+        let embedding = result.unwrap().unwrap().pop().unwrap();
+        let expected = second.get_embedding(text).unwrap();
+        assert!(cosine_similarity(&expected, &embedding) > 0.9999);
+    }
+    assert_eq!(first.computed(), texts.len() as u64);
+}
+
+#[test]
+#[cfg(feature = "semantic")]
+fn test_reduced_precision_embeddings_track_f32() {
+    use udo::core::config::Precision;
+    use udo::core::model::ModelSpec;
+
+    // Wide enough for the linear layers to split into quantization blocks.
+    let dir = tempfile::tempdir().unwrap();
+    tiny_bert_sized(dir.path(), 64);
+    let load = |precision| {
+        let spec = ModelSpec::new("tiny")
+            .with_path(Some(dir.path().to_path_buf()))
+            .with_precision(precision);
+        IntentAnalyzer::from_spec(&spec).unwrap()
+    };
+    let texts = ["user email", "order id total price", "find"];
+    let reference = load(Precision::F32);
+    let expected = reference.embed(&texts).unwrap();
+
+    let mut sizes = vec![reference.load_report().weights_bytes];
+    for (precision, min_similarity) in [
+        (Precision::F16, 0.999),
+        (Precision::Bf16, 0.99),
+        (Precision::Q8_0, 0.99),
+        (Precision::Q4_0, 0.9),
+    ] {
+        let analyzer = load(precision);
+        let report = analyzer.load_report();
+        assert_eq!(report.precision, precision);
+        assert!(report.forward_ms > 0.0);
+        sizes.push(report.weights_bytes);
+
+        let embeddings = analyzer.embed(&texts).unwrap();
+        for (actual, expected) in embeddings.iter().zip(&expected) {
+            let similarity = cosine_similarity(actual, expected);
+            assert!(similarity > min_similarity, "{}: {}", precision, similarity);
+        }
+    }
+    // f32 > bf16 >= f16 > q8_0 > q4_0; bf16 keeps its biases and norms in F32.
+    assert!(sizes[0] > sizes[2] && sizes[2] >= sizes[1], "{:?}", sizes);
+    assert!(sizes[1] > sizes[3] && sizes[3] > sizes[4], "{:?}", sizes);
+}
+
+#[test]
+#[cfg(feature = "semantic")]
+fn test_gguf_roundtrip_matches_load_time_quantization() {
+    use udo::core::bert::write_gguf;
+    use udo::core::config::Precision;
+    use udo::core::model::ModelSpec;
+
+    let dir = tempfile::tempdir().unwrap();
+    tiny_bert_sized(dir.path(), 64);
+    let out = tempfile::tempdir().unwrap();
+    std::fs::copy(
+        dir.path().join("config.json"),
+        out.path().join("config.json"),
+    )
+    .unwrap();
+    std::fs::copy(
+        dir.path().join("tokenizer.json"),
+        out.path().join("tokenizer.json"),
+    )
+    .unwrap();
+    let size = write_gguf(
+        &dir.path().join("model.safetensors"),
+        Precision::Q8_0,
+        &out.path().join("model.gguf"),
+    )
+    .unwrap();
+    let original = std::fs::metadata(dir.path().join("model.safetensors"))
+        .unwrap()
+        .len();
+    assert!(size < original / 2, "{} vs {}", size, original);
+    assert!(write_gguf(
+        &dir.path().join("model.safetensors"),
+        Precision::F16,
+        &out.path().join("other.gguf"),
+    )
+    .is_err());
+
+    let load = |path: &std::path::Path, precision| {
+        let spec = ModelSpec::new("tiny")
+            .with_path(Some(path.to_path_buf()))
+            .with_precision(precision);
+        IntentAnalyzer::from_spec(&spec).unwrap()
+    };
+    let quantized = load(dir.path(), Precision::Q8_0);
+    let gguf = load(out.path(), Precision::Gguf);
+    let texts = ["user email", "order id"];
+    for (a, b) in quantized
+        .embed(&texts)
+        .unwrap()
+        .iter()
+        .zip(&gguf.embed(&texts).unwrap())
+    {
+        assert!(cosine_similarity(a, b) > 0.9999);
+    }
+
+    // Converted weights cache apart from the F32 ones they came from.
+    let fingerprint = |analyzer: IntentAnalyzer| {
+        let cache = tempfile::tempdir().unwrap();
+        let analyzer = analyzer.with_cache(cache.path()).unwrap();
+        analyzer.embed(&["find"]).unwrap();
+        let entry = std::fs::read_dir(cache.path()).unwrap().next().unwrap();
+        entry.unwrap().file_name().into_string().unwrap()
+    };
+    assert_ne!(
+        fingerprint(load(dir.path(), Precision::F32)),
+        fingerprint(quantized)
+    );
+}
+
+#[tokio::test]
+#[cfg(feature = "semantic")]
+async fn test_classifier_labels_records_by_description_embeddings() {
+    use simd_json::prelude::*;
+    use udo::core::config::ClassLabel;
+    use udo::core::pipeline::DataProcessor;
+    use udo::processors::classify::RecordClassifier;
+
+    let dir = tempfile::tempdir().unwrap();
+    tiny_bert(dir.path());
+    let analyzer = IntentAnalyzer::new(Some(dir.path().to_path_buf())).unwrap();
+    let label = |name: &str, description: Option<&str>| ClassLabel {
+        name: name.to_string(),
+        description: description.map(str::to_string),
+        keywords: Vec::new(),
+    };
+    let classifier = RecordClassifier::from_embeddings(
+        analyzer,
+        vec![
+            label("billing", Some("order total price")),
+            label("account", Some("user email")),
+            label("find", None),
+        ],
+        vec!["text".to_string()],
+    )
+    .unwrap()
+    .with_threshold(0.99)
+    .with_fallback("other");
+
+    // The random tiny model only knows a text is closest to itself.
+    for (text, expected) in [
+        ("user email", "account"),
+        ("order total price", "billing"),
+        ("find", "find"),
+        ("", "other"),
+    ] {
+        let mut bytes = format!(r#"{{"text": "{}"}}"#, text).into_bytes();
+        let record = simd_json::to_owned_value(&mut bytes).unwrap();
+        let out = classifier.process(record).await.unwrap().unwrap();
+        assert_eq!(out["_label"].as_str(), Some(expected), "{}", text);
+        if expected != "other" {
+            assert!(out["_label_score"].as_f64().unwrap() > 0.999);
+        }
+    }
+}
+
+#[test]
+#[cfg(feature = "semantic")]
+fn test_embedding_cache_evicts_lru_and_compacts_on_open() {
+    use udo::processors::semantic::cache::EmbeddingCache;
+
+    let dir = tempfile::tempdir().unwrap();
+    let lines = |cache: &EmbeddingCache| {
+        std::fs::read_to_string(cache.path())
+            .unwrap()
+            .lines()
+            .count()
+    };
+
+    let cache = EmbeddingCache::open_with_max_entries(dir.path(), "model", 2).unwrap();
+    cache.insert("a", &[1.0]).unwrap();
+    cache.insert("b", &[2.0]).unwrap();
+    assert!(cache.get("a").is_some());
+    cache.insert("c", &[3.0]).unwrap();
+    assert_eq!(cache.len(), 2);
+    assert!(cache.get("b").is_none());
+    assert_eq!(cache.get("a").unwrap(), vec![1.0]);
+
+    // Appends are compacted once the file is twice the cache's size.
+    cache.insert("d", &[4.0]).unwrap();
+    cache.insert("e", &[5.0]).unwrap();
+    assert!(lines(&cache) <= 4);
+    cache.insert("f", &[6.0]).unwrap();
+    drop(cache);
+
+    let reopened = EmbeddingCache::open_with_max_entries(dir.path(), "model", 2).unwrap();
+    assert_eq!(reopened.len(), 2);
+    assert_eq!(lines(&reopened), 2);
+    assert_eq!(reopened.get("f").unwrap(), vec![6.0]);
+}
+
+#[test]
+#[cfg(feature = "semantic")]
+fn test_embedding_cache_is_reused_across_runs() {
+    use udo::processors::semantic::cache::EmbeddingCache;
+
+    let dir = tempfile::tempdir().unwrap();
+    tiny_bert(dir.path());
+    let cache_dir = dir.path().join("cache");
+
+    let first = IntentAnalyzer::new(Some(dir.path().to_path_buf()))
+        .unwrap()
+        .with_cache(&cache_dir)
+        .unwrap();
+    let embeddings = first.embed(&["user email", "order id"]).unwrap();
+
+    let files: Vec<_> = std::fs::read_dir(&cache_dir).unwrap().collect();
+    assert_eq!(files.len(), 1);
+    let path = files[0].as_ref().unwrap().path();
+    let fingerprint = path.file_stem().unwrap().to_str().unwrap().to_string();
+    assert_eq!(fingerprint.len(), 64);
+
+    let cache = EmbeddingCache::open(&cache_dir, &fingerprint).unwrap();
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.get("user email").unwrap(), embeddings[0]);
+    assert!(cache.get("total").is_none());
+    assert!(EmbeddingCache::open(&cache_dir, "other-model")
+        .unwrap()
+        .is_empty());
+
+    let second = IntentAnalyzer::new(Some(dir.path().to_path_buf()))
+        .unwrap()
+        .with_cache(&cache_dir)
+        .unwrap();
+    assert_eq!(second.embed(&["order id"]).unwrap()[0], embeddings[1]);
+}
+
+#[test]
+#[cfg(feature = "semantic")]
+fn test_bm25_prefers_documents_with_query_terms() {
+    use udo::processors::semantic::lexical::{tokenize, Bm25};
+
+    assert_eq!(
+        tokenize("usr_brwsr.UA-v2"),
+        vec!["usr", "brwsr", "ua", "v2"]
+    );
+
+    let bm25 = Bm25::new(&["user email", "order id", "user browser user agent"]);
+    let scores = bm25.normalized_scores("find user email");
+    assert_eq!(scores[0], 1.0);
+    assert_eq!(scores[1], 0.0);
+    assert!(scores[2] > 0.0 && scores[2] < 1.0);
+    assert_eq!(bm25.normalized_scores("nothing"), vec![0.0; 3]);
+}
+
+#[tokio::test]
+#[cfg(feature = "semantic")]
+async fn test_pruner_uses_descriptions_samples_and_nested_paths() {
+    use arrow::datatypes::{DataType, Field, Fields, Schema};
+    use simd_json::prelude::*;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use udo::core::pipeline::DataProcessor;
+    use udo::processors::semantic::SemanticProcessor;
+
+    let dir = tempfile::tempdir().unwrap();
+    tiny_bert(dir.path());
+    let dictionary = dir.path().join("dictionary.yaml");
+    std::fs::write(&dictionary, "c17: \"email address of the user\"\n").unwrap();
+    let descriptions = udo::processors::semantic::load_dictionary(&dictionary).unwrap();
+
+    let analyzer = IntentAnalyzer::new(Some(dir.path().to_path_buf())).unwrap();
+    // Lexical matching only, so the random test encoder cannot sway the decisions.
+    let processor = SemanticProcessor::new(analyzer, "find user email".to_string(), 0.5)
+        .with_descriptions(descriptions)
+        .with_lexical_weight(1.0);
+
+    let records: Vec<simd_json::OwnedValue> = (0..3)
+        .map(|i| {
+            let mut line = format!(
+                r#"{{"c17": "x{i}", "c18": "u{i}@user.example", "c19": 42, "meta": {{"user": {{"email": "x"}}}}, "total": {i}}}"#
+            )
+            .into_bytes();
+            simd_json::to_owned_value(&mut line).unwrap()
+        })
+        .collect();
+    processor.observe_samples(&records);
+
+    let nested = Fields::from(vec![Field::new(
+        "user",
+        DataType::Struct(Fields::from(vec![Field::new(
+            "email",
+            DataType::Utf8,
+            true,
+        )])),
+        true,
+    )]);
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("c17", DataType::Utf8, true),
+        Field::new("c18", DataType::Utf8, true),
+        Field::new("c19", DataType::Int64, true),
+        Field::new("meta", DataType::Struct(nested), true),
+        Field::new("total", DataType::Int64, true),
+    ]));
+    let pruned = processor.update_schema(&schema).unwrap();
+    let mut kept: Vec<&str> = pruned.fields().iter().map(|f| f.name().as_str()).collect();
+    kept.sort();
+    assert_eq!(kept, vec!["c17", "c18", "meta"]);
+
+    let decisions: HashMap<String, _> = processor
+        .decisions()
+        .into_iter()
+        .map(|d| (d.column.clone(), d))
+        .collect();
+    assert!(!decisions["total"].keep);
+    assert_eq!(decisions["total"].lexical, 0.0);
+    assert!(decisions["total"].reason.contains("< threshold"));
+    assert!(decisions["c17"].keep && decisions["c17"].lexical > 0.5);
+    assert!(decisions["c17"].embedding.abs() <= 1.0001);
+
+    let report = processor.report().unwrap();
+    assert_eq!(report["columns"].as_array().unwrap().len(), 5);
+    assert_eq!(report["lexical_weight"], 1.0);
+
+    let record_out = processor
+        .process(records[0].clone())
+        .await
+        .unwrap()
+        .unwrap();
+    let obj = record_out.as_object().unwrap();
+    assert!(obj.contains_key("c18") && !obj.contains_key("total"));
+}
+
+#[tokio::test]
+#[cfg(feature = "semantic")]
+async fn test_pruner_samples_rows_when_schema_is_supplied() {
+    use arrow::datatypes::{DataType, Field, Schema};
+    use std::io::Write;
+    use std::sync::Arc;
+    use udo::io::source::FileSource;
+    use udo::processors::semantic::SemanticProcessor;
+    use udo::PipelineRunner;
+
+    let dir = tempfile::tempdir().unwrap();
+    tiny_bert(dir.path());
+    let mut input = tempfile::NamedTempFile::new().unwrap();
+    for i in 0..3 {
+        writeln!(input, r#"{{"c18": "u{i}@user.example", "total": {i}}}"#).unwrap();
+    }
+
+    let analyzer = IntentAnalyzer::new(Some(dir.path().to_path_buf())).unwrap();
+    let processor = SemanticProcessor::new(analyzer, "find user email".to_string(), 0.5)
+        .with_lexical_weight(1.0);
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("c18", DataType::Utf8, true),
+        Field::new("total", DataType::Int64, true),
+    ]));
+
+    let source = FileSource::new(input.path().to_path_buf()).await.unwrap();
+    let mut runner = PipelineRunner::new(Box::new(source), 10);
+    runner.add_processor(Box::new(processor));
+    runner.run(Some(schema)).await.unwrap();
+
+    // Only the sampled values tie `c18` to the query.
+    let report = &runner.processor_reports()[0].report;
+    let columns = report["columns"].as_array().unwrap();
+    let c18 = columns.iter().find(|c| c["column"] == "c18").unwrap();
+    assert_eq!(c18["keep"], true);
+    let total = columns.iter().find(|c| c["column"] == "total").unwrap();
+    assert_eq!(total["keep"], false);
+}
+
+#[test]
+#[cfg(feature = "semantic")]
+fn test_pruner_top_k_relative_threshold_and_keep_lists() {
+    use arrow::datatypes::{DataType, Field, Schema};
+    use std::sync::Arc;
+    use udo::core::pipeline::DataProcessor;
+    use udo::processors::semantic::SemanticProcessor;
+
+    let dir = tempfile::tempdir().unwrap();
+    tiny_bert(dir.path());
+    let schema = Arc::new(Schema::new(
+        ["order_total", "user_id", "user_email", "order_id"]
+            .iter()
+            .map(|name| Field::new(*name, DataType::Utf8, true))
+            .collect::<Vec<_>>(),
+    ));
+    // Lexical matching only: user_email scores 1.0, user_id less, the order columns 0.
+    let pruner = |threshold: Option<f32>| {
+        let analyzer = IntentAnalyzer::new(Some(dir.path().to_path_buf())).unwrap();
+        SemanticProcessor::new(analyzer, "find user email".to_string(), threshold)
+            .with_lexical_weight(1.0)
+            .with_sample_values(0)
+    };
+    let kept = |schema: Arc<Schema>| {
+        let mut names: Vec<String> = schema.fields().iter().map(|f| f.name().clone()).collect();
+        names.sort();
+        names
+    };
+
+    let top = pruner(None)
+        .with_top_k(1)
+        .with_always_keep(vec!["order_id".to_string()]);
+    assert_eq!(
+        kept(top.update_schema(&schema).unwrap()),
+        vec!["order_id", "user_email"]
+    );
+    let reasons: Vec<String> = top.decisions().into_iter().map(|d| d.reason).collect();
+    assert!(reasons
+        .iter()
+        .any(|r| r.starts_with("listed in always_keep")));
+    assert!(reasons.iter().any(|r| r == "rank 2 outside top_k 1"));
+
+    let relative = pruner(None)
+        .with_relative_threshold(0.5)
+        .with_never_keep(vec!["user_email".to_string()]);
+    assert_eq!(
+        kept(relative.update_schema(&schema).unwrap()),
+        vec!["user_id"]
+    );
+
+    let strict = pruner(Some(2.0));
+    assert_eq!(strict.update_schema(&schema).unwrap().fields().len(), 4);
+    let err = pruner(Some(2.0))
+        .with_fail_if_empty(true)
+        .update_schema(&schema)
+        .unwrap_err();
+    assert!(err.to_string().contains("selected no columns"));
+}
+
+#[tokio::test]
+#[cfg(feature = "semantic")]
+async fn test_row_filter_scores_and_thresholds_rows() {
+    use simd_json::prelude::*;
+    use udo::core::pipeline::DataProcessor;
+    use udo::processors::semantic::SemanticRowFilter;
+
+    let dir = tempfile::tempdir().unwrap();
+    tiny_bert(dir.path());
+    let analyzer = IntentAnalyzer::new(Some(dir.path().to_path_buf())).unwrap();
+    let expected = analyzer.get_embedding("user\nemail").unwrap();
+    let query = analyzer.get_embedding("find user email").unwrap();
+    let similarity = cosine_similarity(&expected, &query);
+
+    let filter = SemanticRowFilter::new(
+        analyzer,
+        "find user email".to_string(),
+        vec!["title".to_string(), "meta.kind".to_string()],
+    )
+    .with_threshold(similarity - 0.01);
+    let record = |line: &str| simd_json::to_owned_value(&mut line.as_bytes().to_vec()).unwrap();
+
+    let kept = filter
+        .process(record(
+            r#"{"title": "user", "meta": {"kind": "email"}, "id": 1}"#,
+        ))
+        .await
+        .unwrap()
+        .unwrap();
+    let relevance = kept["_relevance"].as_f64().unwrap();
+    assert!((relevance - similarity as f64).abs() < 1e-4);
+
+    // Rows without any of the fields have no relevance and never pass a threshold.
+    assert!(filter
+        .process(record(r#"{"id": 2}"#))
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(
+        filter.metrics(),
+        vec![
+            ("rows_scored".to_string(), 2),
+            ("rows_dropped".to_string(), 1)
+        ]
+    );
+}
+
+#[tokio::test]
+#[cfg(feature = "semantic")]
+async fn test_row_filter_keeps_top_n_per_written_batch() {
+    use arrow::array::{Array, Float64Array};
+    use arrow::record_batch::RecordBatch;
+    use async_trait::async_trait;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+    use udo::core::pipeline::OutputSink;
+    use udo::io::source::FileSource;
+    use udo::processors::semantic::SemanticRowFilter;
+    use udo::PipelineRunner;
+
+    #[derive(Clone, Default)]
+    struct MemorySink {
+        batches: Arc<Mutex<Vec<RecordBatch>>>,
+    }
+
+    #[async_trait]
+    impl OutputSink for MemorySink {
+        async fn write_batch(&mut self, batch: RecordBatch) -> udo::Result<()> {
+            self.batches.lock().unwrap().push(batch);
+            Ok(())
+        }
+        async fn close(&mut self) -> udo::Result<()> {
+            Ok(())
+        }
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    tiny_bert(dir.path());
+    let mut input = tempfile::NamedTempFile::new().unwrap();
+    let texts = [
+        "user email",
+        "order id",
+        "total price",
+        "user",
+        "find order",
+    ];
+    for i in 0..25 {
+        writeln!(
+            input,
+            r#"{{"id": {}, "text": "{}"}}"#,
+            i,
+            texts[i % texts.len()]
+        )
+        .unwrap();
+    }
+
+    let analyzer = IntentAnalyzer::new(Some(dir.path().to_path_buf())).unwrap();
+    let filter =
+        SemanticRowFilter::new(analyzer, "find user email".to_string(), vec!["text".into()])
+            .with_top_n(2);
+    let sink = MemorySink::default();
+    let factory_sink = sink.clone();
+
+    let source = FileSource::new(input.path().to_path_buf()).await.unwrap();
+    let mut runner = PipelineRunner::new(Box::new(source), 10);
+    runner.set_warmup_rows(5);
+    runner.add_processor(Box::new(filter));
+    runner.set_sink_factory(move |_| Ok(Box::new(factory_sink.clone())));
+    runner.run(None).await.unwrap();
+
+    // The 5 warm-up rows start the first batch, so batches of 10, 10 and 5 rows keep
+    // two rows each.
+    let batches = sink.batches.lock().unwrap();
+    assert_eq!(batches.len(), 3);
+    for batch in batches.iter() {
+        assert_eq!(batch.num_rows(), 2);
+        let relevance = batch
+            .column_by_name("_relevance")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(relevance.null_count(), 0);
+    }
+    let metrics = runner.processor_metrics();
+    assert!(metrics.contains(&("SemanticRowFilter.rows_scored".to_string(), 25)));
+    assert!(metrics.contains(&("SemanticRowFilter.rows_dropped".to_string(), 19)));
+}
+
+#[tokio::test]
+#[cfg(feature = "semantic")]
+async fn test_embedding_processor_writes_vector_columns() {
+    use arrow::array::{Array, FixedSizeListArray, Float32Array};
+    use arrow::datatypes::DataType;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use std::io::Write;
+    use udo::io::sink::ParquetSink;
+    use udo::io::source::FileSource;
+    use udo::processors::semantic::EmbeddingProcessor;
+    use udo::PipelineRunner;
+
+    let dir = tempfile::tempdir().unwrap();
+    tiny_bert(dir.path());
+    let cache_dir = dir.path().join("cache");
+    let mut input = tempfile::NamedTempFile::new().unwrap();
+    let titles = ["user email", "order id", "user email", "total price"];
+    for (i, title) in titles.iter().enumerate() {
+        writeln!(
+            input,
+            r#"{{"id": {}, "title": "{}", "meta": {{"note": null}}}}"#,
+            i, title
+        )
+        .unwrap();
+    }
+
+    let run = |output: std::path::PathBuf| {
+        let model_dir = dir.path().to_path_buf();
+        let cache_dir = cache_dir.clone();
+        let input = input.path().to_path_buf();
+        async move {
+            let analyzer = IntentAnalyzer::new(Some(model_dir))
+                .unwrap()
+                .with_cache(&cache_dir)
+                .unwrap();
+            let expected = analyzer.get_embedding("order id").unwrap();
+            let processor = EmbeddingProcessor::new(
+                analyzer,
+                vec!["title".to_string(), "meta.note".to_string()],
+            );
+            assert_eq!(
+                processor.columns(),
+                vec!["title_embedding", "meta_note_embedding"]
+            );
+
+            let mut runner =
+                PipelineRunner::new(Box::new(FileSource::new(input).await.unwrap()), 10);
+            runner.add_processor(Box::new(processor));
+            runner.set_sink_factory(move |schema| {
+                Ok(Box::new(ParquetSink::new(output.clone(), schema)?))
+            });
+            runner.run(None).await.unwrap();
+            (runner.processor_metrics().to_vec(), expected)
+        }
+    };
+
+    let first = dir.path().join("first.parquet");
+    let (metrics, expected) = run(first.clone()).await;
+    // Looking up the expected vector embeds "order id" first; the batch then computes
+    // the two other distinct titles and reuses the cached one.
+    assert!(metrics.contains(&("EmbeddingProcessor.embeddings_written".to_string(), 4)));
+    assert!(metrics.contains(&("EmbeddingProcessor.embeddings_computed".to_string(), 3)));
+
+    let file = std::fs::File::open(&first).unwrap();
+    let batch = ParquetRecordBatchReaderBuilder::try_new(file)
+        .unwrap()
+        .build()
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap();
+    let column = batch.column_by_name("title_embedding").unwrap();
+    assert!(matches!(column.data_type(), DataType::FixedSizeList(_, 8)));
+    let vectors = column
+        .as_any()
+        .downcast_ref::<FixedSizeListArray>()
+        .unwrap();
+    assert_eq!(vectors.len(), 4);
+    let second_row = vectors.value(1);
+    let values = second_row.as_any().downcast_ref::<Float32Array>().unwrap();
+    assert_eq!(values.values().to_vec(), expected);
+    assert_eq!(
+        batch
+            .column_by_name("meta_note_embedding")
+            .unwrap()
+            .null_count(),
+        4
+    );
+
+    // Unchanged text is served from the cache on the next run.
+    let (metrics, _) = run(dir.path().join("second.parquet")).await;
+    assert!(metrics.contains(&("EmbeddingProcessor.embeddings_computed".to_string(), 0)));
+}
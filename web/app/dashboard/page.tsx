@@ -7,7 +7,7 @@ interface Metric {
   id: number;
   timestamp: string;
   processed_rows: number;
-  latency_ms: number;
+  latency_ms: number | null;
   tokens_saved: number;
   operation: string;
 }
@@ -142,7 +142,7 @@ export default function Dashboard() {
                     <td className="px-6 py-4 font-mono text-xs text-slate-500">#{metric.id}</td>
                     <td className="px-6 py-4 font-medium text-white">{metric.operation}</td>
                     <td className="px-6 py-4">{metric.processed_rows}</td>
-                    <td className="px-6 py-4 text-blue-400">{metric.latency_ms.toFixed(2)}ms</td>
+                    <td className="px-6 py-4 text-blue-400">{metric.latency_ms !== null ? `${metric.latency_ms.toFixed(2)}ms` : '-'}</td>
                     <td className="px-6 py-4 text-emerald-400 font-medium">+{metric.tokens_saved}</td>
                     <td className="px-6 py-4 text-right text-xs text-slate-500">{metric.timestamp}</td>
                   </tr>